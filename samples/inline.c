@@ -0,0 +1,14 @@
+static inline int scale(int a) {
+    return a * 3;
+}
+
+static inline int offset(int a) {
+    return scale(a) + 1;
+}
+
+int main(int argc, char **argv)
+{
+    volatile int a = argc;
+
+    return offset(a);
+}
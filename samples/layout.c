@@ -0,0 +1,10 @@
+struct layout {
+    char tag;
+    int count;
+    unsigned int flags : 3;
+    unsigned int mode : 5;
+    long value;
+    short id;
+};
+
+struct layout instance;
@@ -0,0 +1,16 @@
+int lib_counter;
+
+int lib_add(int a, int b)
+{
+    return a + b;
+}
+
+int lib_mul(int a, int b)
+{
+    return a * b;
+}
+
+int lib_next(void)
+{
+    return ++lib_counter;
+}
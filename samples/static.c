@@ -0,0 +1,8 @@
+/* Static PIE holding a pointer, so it needs a relative relocation. */
+int value = 42;
+int *pointer = &value;
+
+void _start(void)
+{
+    for (;;);
+}
@@ -0,0 +1,556 @@
+//! Regarding static archives (`ar` files) of objects.
+//!
+//! An archive starts with a magic string followed by members, each with
+//! a 60 bytes text header and 2 bytes aligned contents. Some members are
+//! special:
+//! - `/` and `/SYM64/`: the GNU symbol index, with 32 or 64-bit big
+//!   endian offsets of the member defining each symbol.
+//! - `__.SYMDEF`: the BSD symbol index, with little endian offsets.
+//! - `//`: the GNU long names table, referred to by names like `/123`.
+//!
+//! BSD long names (`#1/N`) are stored right before the member contents
+//! instead. Thin archives (`!<thin>`) only store the paths of their
+//! members, which are read from disk relative to the archive.
+//!
+//! Archives are written in GNU format, with the symbol index generated
+//! from the global symbols defined by every object member.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::object::Object;
+
+/// Magic string of a regular archive.
+pub const MAGIC: &[u8] = b"!<arch>\n";
+
+/// Magic string of a thin archive.
+pub const THIN_MAGIC: &[u8] = b"!<thin>\n";
+
+/// Size in bytes of a member header.
+pub const HEADER_SIZE: usize = 60;
+
+/// Errors found while reading an archive.
+#[derive(Debug)]
+pub enum Error {
+    /// The contents do not start with an archive magic string.
+    NotArchive,
+    /// The member header at the given offset is malformed.
+    Header(u64),
+    /// The archive, or the member of a thin archive at the given path,
+    /// could not be read.
+    Io(PathBuf, std::io::Error),
+}
+
+/// A member of an archive.
+#[derive(Clone)]
+pub struct Member {
+    /// Name of the member, or path for thin archives.
+    pub name:       String,
+    /// Modification time, in seconds since the epoch.
+    pub date:       u64,
+    /// Owner user id.
+    pub uid:        u32,
+    /// Owner group id.
+    pub gid:        u32,
+    /// File mode.
+    pub mode:       u32,
+    /// Contents of the member.
+    pub data:       Vec<u8>,
+    /// Offset of the member header in the archive.
+    pub offset:     u64,
+}
+
+/// A static archive.
+pub struct Archive {
+    /// Wether the archive only refers to its members.
+    pub(crate) thin:    bool,
+    /// Regular members, in order.
+    pub(crate) members: Vec<Member>,
+    /// Symbol index: every symbol name with the index of the member
+    /// defining it.
+    pub(crate) symbols: Vec<(String, usize)>,
+    /// Wether to write zero dates and owners, and `0644` modes.
+    deterministic:      bool,
+}
+
+/// Simple member methods.
+impl Member {
+    /// Creates a member with the given name and contents, owned by root
+    /// with mode `0644` and date zero.
+    pub fn new(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            name:   name.to_string(),
+            date:   0,
+            uid:    0,
+            gid:    0,
+            mode:   0o100644,
+            data,
+            offset: 0,
+        }
+    }
+
+    /// Check if the member is an ELF file.
+    pub fn is_elf(&self) -> bool {
+        self.data.starts_with(b"\x7fELF")
+    }
+
+    /// Parse the member as an object, if it is one `Object` can read
+    /// (see `Object::is_readable`).
+    pub fn object(&self) -> Option<Object> {
+        if !Object::is_readable(&self.data) {
+            return None;
+        }
+        Some(Object::from_bytes(&self.data))
+    }
+}
+
+/// Simple archive methods.
+impl Archive {
+    /// Creates an empty archive.
+    pub fn empty() -> Self {
+        Self {
+            thin:           false,
+            members:        vec![],
+            symbols:        vec![],
+            deterministic:  false,
+        }
+    }
+
+    /// Check if the archive is thin.
+    pub fn is_thin(&self) -> bool {
+        self.thin
+    }
+
+    /// Get the regular members.
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Get the symbol index, as every symbol name with the index of the
+    /// member defining it.
+    pub fn symbols(&self) -> &[(String, usize)] {
+        &self.symbols
+    }
+
+    /// Sets wether the archive is thin, only referring to its members by
+    /// path when written.
+    pub fn thin(&mut self, thin: bool) {
+        self.thin = thin;
+    }
+
+    /// Sets wether to write every member with a zero date, owned by
+    /// root and with mode `0644`, for reproducible builds.
+    pub fn deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Adds a member, indexing the global symbols it defines when it is
+    /// a readable object.
+    pub fn add_member(&mut self, member: Member) {
+        let i = self.members.len();
+        if let Some(obj) = member.object() {
+            for sym in obj.symbols() {
+                if sym.is_local() || sym.is_undefined() {
+                    continue;
+                }
+                self.symbols.push((sym.name.clone().unwrap_or_default(), i));
+            }
+        }
+        self.members.push(member);
+    }
+
+    /// Get the member defining a symbol, according to the symbol index.
+    pub fn find_symbol(&self, name: &str) -> Option<&Member> {
+        self.symbols.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, i)| &self.members[*i])
+    }
+
+    /// Parse every readable object member, along with its member.
+    ///
+    /// Other members, like text files, truncated or 32-bit objects, are
+    /// skipped.
+    pub fn objects(&self) -> Vec<(&Member, Object)> {
+        self.members.iter()
+            .filter_map(|m| Some((m, m.object()?)))
+            .collect()
+    }
+}
+
+/// File IO methods.
+mod io {
+    use super::*;
+    use std::io::{self, Write};
+    use crate::util;
+
+    /// Name of the GNU long names table member.
+    const LONG_NAMES: &str = "//";
+
+    /// A raw member header.
+    struct Header<'a> {
+        /// Raw name field, trimmed.
+        name:   &'a str,
+        date:   u64,
+        uid:    u32,
+        gid:    u32,
+        mode:   u32,
+        /// Size of the contents.
+        size:   usize,
+    }
+
+    /// Parse a decimal (or octal) text field, empty meaning zero.
+    fn field(raw: &[u8], radix: u32) -> Option<u64> {
+        let text = std::str::from_utf8(raw).ok()?.trim();
+        if text.is_empty() {
+            return Some(0);
+        }
+        u64::from_str_radix(text, radix).ok()
+    }
+
+    impl<'a> Header<'a> {
+        /// Parse a member header, unless it is malformed.
+        fn parse(raw: &'a [u8]) -> Option<Self> {
+            if &raw[58..60] != b"`\n" {
+                return None;
+            }
+            Some(Self {
+                name:   std::str::from_utf8(&raw[0..16]).ok()?.trim_end(),
+                date:   field(&raw[16..28], 10)?,
+                uid:    field(&raw[28..34], 10)? as u32,
+                gid:    field(&raw[34..40], 10)? as u32,
+                mode:   field(&raw[40..48], 8)? as u32,
+                size:   field(&raw[48..58], 10)? as usize,
+            })
+        }
+    }
+
+    impl Archive {
+        /// Reads an archive from the given file name.
+        ///
+        /// Members of thin archives are read relative to its directory.
+        pub fn from_file(filename: &str) -> Result<Self, Error> {
+            let data = std::fs::read(filename)
+                .map_err(|err| Error::Io(PathBuf::from(filename), err))?;
+            let dir = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+            Self::extract(&data, dir)
+        }
+
+        /// Reads an archive from its contents.
+        ///
+        /// Members of thin archives are read relative to the current
+        /// directory.
+        pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+            Self::extract(data, Path::new(""))
+        }
+
+        /// Writes the archive into a file with the given name.
+        pub fn to_file(&self, filename: &str) -> io::Result<()> {
+            let mut file = std::fs::File::create(filename)?;
+            self.write_to(&mut file)
+        }
+
+        /// Writes the archive in GNU format, with a symbol index when any
+        /// symbol is indexed.
+        ///
+        /// Names too long for the member header, and every name of thin
+        /// archives, go into the long names table.
+        pub fn write_to<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            file.write_all(if self.thin { THIN_MAGIC } else { MAGIC })?;
+
+            /* long names table, and the name field of every member */
+            let mut names = vec![];
+            let mut fields = vec![];
+            for member in &self.members {
+                if self.thin || member.name.len() > 15 {
+                    fields.push(format!("/{}", names.len()));
+                    names.extend_from_slice(member.name.as_bytes());
+                    names.extend_from_slice(b"/\n");
+                } else {
+                    fields.push(format!("{}/", member.name));
+                }
+            }
+            /* both tables are padded within their size */
+            if !names.len().is_multiple_of(2) {
+                names.push(b'\n');
+            }
+
+            /* member offsets, past the index and long names */
+            let strings: usize = self.symbols.iter().map(|(n, _)| n.len() + 1).sum();
+            let index_size = |word: usize| {
+                util::align((word * (self.symbols.len() + 1) + strings) as u64, 2) as usize
+            };
+            let member_offsets = |word: usize| -> Vec<u64> {
+                let mut off = MAGIC.len();
+                if !self.symbols.is_empty() {
+                    off += HEADER_SIZE + index_size(word);
+                }
+                if !names.is_empty() {
+                    off += HEADER_SIZE + names.len();
+                }
+                let mut offsets = vec![];
+                for member in &self.members {
+                    offsets.push(off as u64);
+                    if !self.thin {
+                        off += util::align(member.data.len() as u64, 2) as usize;
+                    }
+                    off += HEADER_SIZE;
+                }
+                offsets
+            };
+            let mut word = 4;
+            let mut offsets = member_offsets(word);
+            if offsets.last().is_some_and(|&o| o > u32::MAX as u64) {
+                word = 8;
+                offsets = member_offsets(word);
+            }
+
+            /* symbol index */
+            if !self.symbols.is_empty() {
+                let name = if word == 4 { "/" } else { "/SYM64/" };
+                write_header(file, name, "0", "0", "0", "0", index_size(word))?;
+                let mut data = vec![];
+                let mut put = |v: u64| data.extend_from_slice(&v.to_be_bytes()[8 - word..]);
+                put(self.symbols.len() as u64);
+                for (_, i) in &self.symbols {
+                    put(offsets[*i]);
+                }
+                for (name, _) in &self.symbols {
+                    data.extend_from_slice(name.as_bytes());
+                    data.push(0);
+                }
+                data.resize(index_size(word), 0);
+                write_padded(file, &data)?;
+            }
+
+            if !names.is_empty() {
+                write_header(file, LONG_NAMES, "", "", "", "", names.len())?;
+                write_padded(file, &names)?;
+            }
+
+            for (member, name) in self.members.iter().zip(&fields) {
+                let (date, uid, gid, mode) = if self.deterministic {
+                    (0, 0, 0, 0o644)
+                } else {
+                    (member.date, member.uid, member.gid, member.mode)
+                };
+                write_header(file, name, &date.to_string(), &uid.to_string(),
+                    &gid.to_string(), &format!("{:o}", mode), member.data.len())?;
+                if !self.thin {
+                    write_padded(file, &member.data)?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Extract every member and the symbol index.
+        fn extract(data: &[u8], dir: &Path) -> Result<Self, Error> {
+            let mut archive = Self::empty();
+            archive.thin = data.starts_with(THIN_MAGIC);
+            if !archive.thin && !data.starts_with(MAGIC) {
+                return Err(Error::NotArchive);
+            }
+
+            let mut names: &[u8] = &[];
+            let mut index = vec![];
+            let mut off = MAGIC.len();
+            while off + HEADER_SIZE <= data.len() {
+                let bad = Error::Header(off as u64);
+                let header = match Header::parse(&data[off..off + HEADER_SIZE]) {
+                    Some(header) => header,
+                    None         => return Err(bad),
+                };
+                let start = off + HEADER_SIZE;
+                let special = matches!(header.name, "/" | "/SYM64/" | "//")
+                    || header.name.starts_with("#1/");
+
+                /* thin archives only store special members */
+                let stored = if archive.thin && !special { 0 } else { header.size };
+                let end = start.saturating_add(stored).min(data.len());
+                let mut contents = &data[start..end];
+
+                let name = if let Some(len) = header.name.strip_prefix("#1/") {
+                    /* BSD: the name comes first in the contents */
+                    let len = match len.parse::<usize>() {
+                        Ok(len) if len <= contents.len() => len,
+                        _ => return Err(bad),
+                    };
+                    let name = util::get_str(&contents[..len], 0);
+                    contents = &contents[len..];
+                    name
+                } else if let Some(ndx) = header.name.strip_prefix('/').filter(|n| {
+                    n.starts_with(|c: char| c.is_ascii_digit())
+                }) {
+                    /* GNU: offset into the long names table */
+                    let name = match ndx.parse::<usize>().ok().and_then(|n| names.get(n..)) {
+                        Some(name) => name,
+                        None       => return Err(bad),
+                    };
+                    let len = name.iter().position(|&c| c == b'\n').unwrap_or(name.len());
+                    String::from_utf8_lossy(&name[..len]).trim_end_matches('/').to_string()
+                } else if special {
+                    header.name.to_string()
+                } else {
+                    header.name.trim_end_matches('/').to_string()
+                };
+
+                match name.as_str() {
+                    "/"       => index = extract_gnu_index(contents, 4),
+                    "/SYM64/" => index = extract_gnu_index(contents, 8),
+                    "//"      => names = contents,
+                    "__.SYMDEF" | "__.SYMDEF SORTED" => index = extract_bsd_index(contents),
+                    _ => {
+                        let data = if archive.thin {
+                            let path: PathBuf = dir.join(&name);
+                            std::fs::read(&path).map_err(|err| Error::Io(path, err))?
+                        } else {
+                            contents.to_vec()
+                        };
+                        archive.members.push(Member {
+                            name,
+                            date:   header.date,
+                            uid:    header.uid,
+                            gid:    header.gid,
+                            mode:   header.mode,
+                            data,
+                            offset: off as u64,
+                        });
+                    }
+                }
+                off = util::align(end as u64, 2) as usize;
+            }
+
+            /* index offsets point at member headers */
+            for (name, offset) in index {
+                if let Some(i) = archive.members.iter().position(|m| m.offset == offset) {
+                    archive.symbols.push((name, i));
+                }
+            }
+            Ok(archive)
+        }
+    }
+
+    /// Write a member header.
+    fn write_header<W: Write>(file: &mut W, name: &str, date: &str, uid: &str,
+                              gid: &str, mode: &str, size: usize) -> io::Result<()> {
+        writeln!(file, "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`", name, date, uid, gid, mode, size)
+    }
+
+    /// Write member contents, padded to 2 bytes.
+    fn write_padded<W: Write>(file: &mut W, data: &[u8]) -> io::Result<()> {
+        file.write_all(data)?;
+        if !data.len().is_multiple_of(2) {
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Extract a GNU symbol index with offsets of `size` bytes.
+    ///
+    /// Entries past the end of a truncated index are dropped.
+    fn extract_gnu_index(data: &[u8], size: usize) -> Vec<(String, u64)> {
+        let word = |off: usize| -> u64 {
+            data[off..off + size].iter().fold(0, |v, &b| v << 8 | b as u64)
+        };
+        if data.len() < size {
+            return vec![];
+        }
+        let count = (word(0) as usize).min(data.len() / size - 1);
+        let mut strings = size * (count + 1);
+        let mut index = vec![];
+        for i in 0..count {
+            let name = util::get_str(data, strings);
+            strings += name.len() + 1;
+            index.push((name, word(size * (i + 1))));
+        }
+        index
+    }
+
+    /// Extract a BSD symbol index.
+    ///
+    /// Entries past the end of a truncated index are dropped.
+    fn extract_bsd_index(data: &[u8]) -> Vec<(String, u64)> {
+        if data.len() < 4 {
+            return vec![];
+        }
+        let len = (util::get_u32(data, 0) as usize).min(data.len() - 4);
+        let strings = 4 + len + 4;
+        (0..len / 8)
+            .map(|i| {
+                let strx = util::get_u32(data, 4 + i * 8) as usize;
+                let offset = util::get_u32(data, 8 + i * 8) as u64;
+                (util::get_str(data, strings + strx), offset)
+            })
+            .collect()
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe an archive error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::NotArchive       => write!(f, "not an archive"),
+                Error::Header(off)      => write!(f, "malformed member header at offset {:#x}", off),
+                Error::Io(path, err)    => write!(f, "cannot read {}: {}", path.display(), err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    #[test]
+    fn round_trip() {
+        let mut archive = Archive::empty();
+        archive.add_member(Member::new("main.o", sample("main.o")));
+        let mut data = vec![];
+        archive.write_to(&mut data).unwrap();
+
+        let read = Archive::from_bytes(&data).unwrap();
+        assert_eq!(read.members().len(), 1);
+        assert!(read.members()[0].data == sample("main.o"));
+        assert!(read.find_symbol("global_f").is_some());
+    }
+
+    #[test]
+    fn skip_unreadable() {
+        let mut truncated = sample("main.o");
+        truncated.truncate(truncated.len() / 2);
+        let mut archive = Archive::empty();
+        archive.add_member(Member::new("notes.txt", b"not an object\n".to_vec()));
+        archive.add_member(Member::new("truncated.o", truncated));
+        archive.add_member(Member::new("main.o", sample("main.o")));
+        let mut data = vec![];
+        archive.write_to(&mut data).unwrap();
+
+        let read = Archive::from_bytes(&data).unwrap();
+        assert_eq!(read.members().len(), 3);
+        assert!(read.members()[..2].iter().all(|m| m.object().is_none()));
+        let names: Vec<_> = read.objects().iter().map(|(m, _)| m.name.clone()).collect();
+        assert_eq!(names, ["main.o"]);
+        assert!(read.symbols().iter().all(|(_, i)| *i == 2));
+        assert!(read.find_symbol("global_f").is_some());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(matches!(Archive::from_bytes(b"not an archive"), Err(Error::NotArchive)));
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[b'?'; HEADER_SIZE]);
+        assert!(matches!(Archive::from_bytes(&data), Err(Error::Header(8))));
+
+        let mut archive = Archive::empty();
+        archive.thin(true);
+        archive.add_member(Member::new("/nonexistent/member.o", vec![]));
+        let mut data = vec![];
+        archive.write_to(&mut data).unwrap();
+        assert!(matches!(Archive::from_bytes(&data), Err(Error::Io(_, _))));
+    }
+}
@@ -0,0 +1,356 @@
+//! Regarding building relocatable objects from scratch.
+//!
+//! The builder collects sections, symbols and relocations, and takes
+//! care of the bookkeeping sections on `build`:
+//! - `.group` for every section group.
+//! - `.rela.*` for every section with relocations.
+//! - `.symtab` with local symbols first, and `info` pointing at the
+//!   first global one.
+//! - `.strtab` and `.shstrtab` for all the names.
+
+use crate::header::{Header, Type as HeaderType};
+use crate::object::Object;
+use crate::rel::{Rela, RELA_SIZE};
+use crate::section::{self, Section, Type as SectionType};
+use crate::sym::{Sym, Bind, Type as SymType, SHN_UNDEF};
+
+/// Size in bytes of a symbol entry.
+const SYM_SIZE: u64 = 24;
+
+/// Symbol id for relocations against no symbol (index 0).
+pub const NO_SYMBOL: usize = usize::MAX;
+
+/// A section group being built.
+struct Group {
+    /// Id of the signature symbol.
+    signature:  usize,
+    /// Group flags (`GRP_COMDAT`).
+    flags:      u32,
+    /// Ids of the member sections.
+    members:    Vec<usize>,
+}
+
+/// Builds a relocatable object file.
+pub struct ObjectBuilder {
+    /// Machine of the object.
+    machine:    u16,
+    /// Sections added so far.
+    sections:   Vec<Section>,
+    /// Symbols added so far.
+    symbols:    Vec<Sym>,
+    /// Id of the section each symbol is defined in, if any.
+    symsecs:    Vec<Option<usize>>,
+    /// Section symbol of each section, if already created.
+    secsyms:    Vec<Option<usize>>,
+    /// Relocations added so far, with the section they apply to.
+    relocs:     Vec<(usize, Rela)>,
+    /// Section groups added so far.
+    groups:     Vec<Group>,
+}
+
+/// Simple builder methods.
+impl ObjectBuilder {
+    /// Creates an empty builder for the given machine.
+    pub fn new(machine: u16) -> Self {
+        Self {
+            machine,
+            sections:   vec![],
+            symbols:    vec![],
+            symsecs:    vec![],
+            secsyms:    vec![],
+            relocs:     vec![],
+            groups:     vec![],
+        }
+    }
+
+    /// Adds a section with the given contents and gets its id.
+    pub fn add_section(&mut self, name: &str, flags: u64, data: Vec<u8>, align: usize) -> usize {
+        let mut section = Section::new(name, SectionType::Progbits, flags);
+        section.size = data.len() as u64;
+        section.data = data;
+        section.addralign = align;
+        self.add_raw_section(section)
+    }
+
+    /// Adds a zero filled section occupying no file space, like `.bss`,
+    /// and gets its id.
+    pub fn add_nobits(&mut self, name: &str, flags: u64, size: u64, align: usize) -> usize {
+        let mut section = Section::new(name, SectionType::Nobits, flags);
+        section.size = size;
+        section.addralign = align;
+        self.add_raw_section(section)
+    }
+
+    /// Adds a section as is, keeping its type, flags, alignment and
+    /// entry size, and gets its id.
+    ///
+    /// Its `link` and `info` are not translated.
+    pub fn add_raw_section(&mut self, section: Section) -> usize {
+        self.sections.push(section);
+        self.secsyms.push(None);
+        self.sections.len() - 1
+    }
+
+    /// Defines a symbol in the section with the given id and gets its
+    /// id.
+    pub fn define_symbol(&mut self, name: &str, bind: Bind, etype: SymType,
+                         section: usize, value: u64, size: u64) -> usize {
+        let sym = Sym {
+            etype,
+            bind,
+            value,
+            size,
+            name: Some(name.to_string()),
+            ..Sym::empty()
+        };
+        self.add_symbol(sym, Some(section))
+    }
+
+    /// Declares an undefined global symbol and gets its id.
+    ///
+    /// Declaring the same name twice returns the same symbol.
+    pub fn declare_symbol(&mut self, name: &str) -> usize {
+        let found = self.symbols.iter().position(|s| {
+            s.is_undefined() && s.name.as_deref() == Some(name)
+        });
+        if let Some(id) = found {
+            return id;
+        }
+        let sym = Sym {
+            bind:  Bind::Global,
+            shndx: SHN_UNDEF,
+            name:  Some(name.to_string()),
+            ..Sym::empty()
+        };
+        self.add_symbol(sym, None)
+    }
+
+    /// Adds a symbol as is and gets its id.
+    ///
+    /// When a section id is given the symbol is defined in it, otherwise
+    /// its `shndx` is kept (undefined, absolute or common).
+    pub fn add_symbol(&mut self, sym: Sym, section: Option<usize>) -> usize {
+        self.symbols.push(sym);
+        self.symsecs.push(section);
+        self.symbols.len() - 1
+    }
+
+    /// Gets the id of the symbol representing the section with the
+    /// given id, creating it if needed.
+    pub fn section_symbol(&mut self, section: usize) -> usize {
+        if let Some(id) = self.secsyms[section] {
+            return id;
+        }
+        let sym = Sym {
+            etype: SymType::Section,
+            name:  self.sections[section].name.clone(),
+            ..Sym::empty()
+        };
+        let id = self.add_symbol(sym, Some(section));
+        self.secsyms[section] = Some(id);
+        id
+    }
+
+    /// Adds a relocation at `offset` into the section with the given id,
+    /// against the symbol with the given id, or `NO_SYMBOL`.
+    pub fn add_relocation(&mut self, section: usize, offset: u64,
+                          sym: usize, rtype: u32, addend: i64) {
+        self.relocs.push((section, Rela::new(offset, sym, rtype, addend)));
+    }
+
+    /// Adds a section group, identified by the symbol with the given id,
+    /// holding the sections with the given ids.
+    ///
+    /// Members should carry the `SHF_GROUP` flag. Their relocation
+    /// sections join the group automatically.
+    pub fn add_group(&mut self, signature: usize, flags: u32, members: &[usize]) {
+        self.groups.push(Group {
+            signature,
+            flags,
+            members: members.to_vec(),
+        });
+    }
+}
+
+/// Building methods.
+mod build {
+    use super::*;
+
+    impl ObjectBuilder {
+        /// Builds the object.
+        ///
+        /// The resulting sections are laid out as:
+        /// - The null section.
+        /// - One `.group` section for every section group.
+        /// - Every section added, in order.
+        /// - An empty `.note.GNU-stack`, unless one was added.
+        /// - One `.rela.*` section for every section with relocations.
+        /// - `.symtab`, `.strtab` and `.shstrtab`.
+        pub fn build(mut self) -> Object {
+            let mut obj = Object::empty();
+            obj.header = Header::new(HeaderType::Rel, self.machine);
+
+            /* final index of every section id */
+            let first = 1 + self.groups.len();
+            let ndx = |id: usize| first + id;
+
+            /* symbol table: locals first */
+            let mut order: Vec<usize> = (0..self.symbols.len()).collect();
+            order.sort_by_key(|&i| !self.symbols[i].is_local());
+            let mut map = vec![0; self.symbols.len()];
+            let mut syms = vec![Sym::empty()];
+            syms[0].name = Some(String::new());
+            for &i in &order {
+                map[i] = syms.len();
+                let mut sym = self.symbols[i].clone();
+                if let Some(id) = self.symsecs[i] {
+                    sym.shndx = ndx(id);
+                }
+                syms.push(sym);
+            }
+            let first_global = syms.iter().position(|s| !s.is_local()).unwrap_or(syms.len());
+
+            /* indices of the bookkeeping sections */
+            let has_stack = self.sections.iter()
+                .any(|s| s.name.as_deref() == Some(".note.GNU-stack"));
+            let mut targets: Vec<usize> = self.relocs.iter().map(|(s, _)| *s).collect();
+            targets.sort_unstable();
+            targets.dedup();
+            let rela_ndx = ndx(self.sections.len()) + !has_stack as usize;
+            let symtab_ndx = rela_ndx + targets.len();
+            let strtab_ndx = symtab_ndx + 1;
+            let shstrtab_ndx = symtab_ndx + 2;
+
+            /* group sections */
+            obj.sections.push(Section::new("", SectionType::Null, 0));
+            for group in &self.groups {
+                let mut section = Section::new(".group", SectionType::Group, 0);
+                section.link = symtab_ndx;
+                section.info = map[group.signature] as u32;
+                section.entsize = 4;
+                section.addralign = 4;
+                section.data.extend_from_slice(&group.flags.to_le_bytes());
+                for &id in &group.members {
+                    section.data.extend_from_slice(&(ndx(id) as u32).to_le_bytes());
+                }
+                for (i, &target) in targets.iter().enumerate() {
+                    if group.members.contains(&target) {
+                        let rela = (rela_ndx + i) as u32;
+                        section.data.extend_from_slice(&rela.to_le_bytes());
+                    }
+                }
+                obj.sections.push(section);
+            }
+
+            /* user sections */
+            obj.sections.append(&mut self.sections);
+            if !has_stack {
+                let mut stack = Section::new(".note.GNU-stack", SectionType::Progbits, 0);
+                stack.addralign = 1;
+                obj.sections.push(stack);
+            }
+
+            /* relocation sections */
+            for &target in &targets {
+                let name = format!(".rela{}", obj.sections[ndx(target)].name.as_deref().unwrap());
+                let mut flags = section::SHF_INFO_LINK;
+                flags |= obj.sections[ndx(target)].flags & section::SHF_GROUP;
+                let mut rela = Section::new(&name, SectionType::Rela, flags);
+                rela.link = symtab_ndx;
+                rela.info = ndx(target) as u32;
+                rela.entsize = RELA_SIZE;
+                rela.addralign = 8;
+                for (_, r) in self.relocs.iter().filter(|(s, _)| *s == target) {
+                    let sym = map.get(r.sym).copied().unwrap_or(0);
+                    let r = Rela::new(r.offset, sym, r.rtype, r.addend);
+                    r.write(&mut rela.data).unwrap();
+                }
+                obj.sections.push(rela);
+            }
+
+            /* symbol and string tables */
+            let (symdata, strdata) = Sym::encode_table(&mut syms);
+            let mut symtab = Section::new(".symtab", SectionType::Symtab, 0);
+            symtab.link = strtab_ndx;
+            symtab.info = first_global as u32;
+            symtab.entsize = SYM_SIZE;
+            symtab.addralign = 8;
+            symtab.data = symdata;
+            obj.sections.push(symtab);
+
+            let mut strtab = Section::new(".strtab", SectionType::Strtab, 0);
+            strtab.addralign = 1;
+            strtab.data = strdata;
+            obj.sections.push(strtab);
+
+            let mut shstrtab = Section::new(".shstrtab", SectionType::Strtab, 0);
+            shstrtab.addralign = 1;
+            obj.sections.push(shstrtab);
+            obj.header.shstrndx = shstrtab_ndx;
+
+            obj.symbols = syms;
+            obj.layout();
+            obj
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::EM_X86_64;
+    use crate::rel::x86_64::{R_X86_64_PC32, R_X86_64_PLT32};
+    use crate::tests::write;
+
+    #[test]
+    fn build_and_read_back() {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let text = b.add_section(".text", section::SHF_ALLOC | section::SHF_EXECINSTR,
+            vec![0xe8, 0, 0, 0, 0, 0x8b, 0x05, 0, 0, 0, 0, 0xc3], 16);
+        let data = b.add_section(".data", section::SHF_ALLOC | section::SHF_WRITE, vec![0; 8], 8);
+        /* globals first, to check the locals get moved before them */
+        b.define_symbol("f", Bind::Global, SymType::Func, text, 0, 12);
+        let puts = b.declare_symbol("puts");
+        let counter = b.define_symbol("counter", Bind::Local, SymType::Object, data, 4, 4);
+        b.add_relocation(text, 1, puts, R_X86_64_PLT32, -4);
+        b.add_relocation(text, 7, counter, R_X86_64_PC32, -4);
+        assert_eq!(b.declare_symbol("puts"), puts);
+
+        /* a group holding a section with relocations */
+        let flags = section::SHF_ALLOC | section::SHF_EXECINSTR | section::SHF_GROUP;
+        let inline = b.add_section(".text.g", flags, vec![0xe8, 0, 0, 0, 0, 0xc3], 1);
+        let g = b.define_symbol("g", Bind::Weak, SymType::Func, inline, 0, 6);
+        b.add_relocation(inline, 1, puts, R_X86_64_PLT32, -4);
+        b.add_group(g, section::GRP_COMDAT, &[inline]);
+
+        let obj = Object::from_bytes(&write(&b.build()));
+        let syms = obj.symbols();
+        let names: Vec<_> = syms.iter().map(|s| s.name.as_deref().unwrap()).collect();
+        assert_eq!(names, ["", "counter", "f", "puts", "g"]);
+
+        let symtab = obj.section_index(".symtab").unwrap();
+        let first_global = syms.iter().position(|s| !s.is_local()).unwrap();
+        assert_eq!(obj.sections()[symtab].info as usize, first_global);
+        assert!(syms[..first_global].iter().all(|s| s.is_local()));
+        assert_eq!(obj.sections()[symtab].link, obj.section_index(".strtab").unwrap());
+
+        let rela = obj.section_index(".rela.text").unwrap();
+        assert_eq!(obj.sections()[rela].link, symtab);
+        assert_eq!(obj.sections()[rela].info as usize, obj.section_index(".text").unwrap());
+        let targets: Vec<_> = obj.relocations(rela).iter()
+            .map(|r| (r.offset, names[r.sym], r.rtype))
+            .collect();
+        assert_eq!(targets, [(1, "puts", R_X86_64_PLT32), (7, "counter", R_X86_64_PC32)]);
+        assert_eq!(syms[2].shndx, obj.section_index(".text").unwrap());
+        assert!(syms[3].is_undefined());
+        assert!(obj.section_index(".note.GNU-stack").is_some());
+
+        let group = &obj.sections()[obj.section_index(".group").unwrap()];
+        let words: Vec<usize> = group.data.chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]) as usize)
+            .collect();
+        let ndx = |name| obj.section_index(name).unwrap();
+        assert_eq!(words, [section::GRP_COMDAT as usize, ndx(".text.g"), ndx(".rela.text.g")]);
+        assert_eq!((group.link, group.info as usize), (symtab, 4));
+    }
+}
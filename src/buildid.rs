@@ -0,0 +1,278 @@
+//! Regarding build IDs (`NT_GNU_BUILD_ID` notes), like
+//! `ld --build-id` computes them.
+//!
+//! The ID is a digest of the whole file as it would be written, with
+//! the descriptor of the build ID note itself zeroed:
+//! - `sha1`: 20 bytes SHA-1 digest.
+//! - `md5`: 16 bytes MD5 digest.
+//! - `uuid`: 16 random bytes from `/dev/urandom`, not derived from the
+//!   contents.
+//!
+//! The linker only hashes what it holds in memory, so the IDs differ
+//! from the ones it would give, but the same contents always get the
+//! same ID.
+//!
+//! The existing note is rewritten in place when it has the right size.
+//! The note of a relocatable object, or an unloaded one, can also change
+//! size, but a loaded note cannot, since the segments around it would
+//! have to move.
+//!
+//! Otherwise a `.note.gnu.build-id` section is added. Executables and
+//! shared objects get it loaded at the end of the extension segment
+//! used by `patch`, along with a `PT_NOTE` segment, so it can be found
+//! at run time. The program headers need one more entry for it, so this
+//! fails once they have already moved there.
+
+use std::io::{self, Read};
+
+use crate::digest;
+use crate::note::{Note, GNU, NT_GNU_BUILD_ID};
+use crate::object::Object;
+use crate::section::{self, Section, Type as SectionType};
+use crate::segment::{self, Segment, Type as SegmentType};
+
+/// Name of the section added for the build ID.
+pub const BUILD_ID: &str = ".note.gnu.build-id";
+
+/// How a build ID is computed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    /// SHA-1 digest of the contents.
+    Sha1,
+    /// MD5 digest of the contents.
+    Md5,
+    /// Random bytes.
+    Uuid,
+}
+
+/// Simple style methods.
+impl Style {
+    /// Get the size in bytes of the IDs.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Sha1              => 20,
+            Self::Md5 | Self::Uuid  => 16,
+        }
+    }
+
+    /// Get string slice for the style, as given to `ld --build-id`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha1  => "sha1",
+            Self::Md5   => "md5",
+            Self::Uuid  => "uuid",
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Get the build ID, if any.
+        pub fn build_id(&self) -> Option<Vec<u8>> {
+            self.build_id_note().map(|(_, n)| n.desc)
+        }
+
+        /// Sets the build ID, adding a note if needed.
+        ///
+        /// The loaded note of an executable or shared object cannot
+        /// change size.
+        pub fn set_build_id(&mut self, id: &[u8]) -> io::Result<()> {
+            let (ndx, note) = match self.build_id_note() {
+                Some(found) => found,
+                None => return self.add_build_id_note(id),
+            };
+
+            let section = &mut self.sections[ndx];
+            let at = note.desc_offset();
+            if note.desc.len() == id.len() {
+                section.data[at..at + id.len()].copy_from_slice(id);
+                return Ok(());
+            }
+            if section.is_alloc() && !self.header.is_rel() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    "cannot resize the loaded build ID note"));
+            }
+            let old = note.encode();
+            let new = Note { desc: id.to_vec(), ..note.clone() }.encode();
+            section.data.splice(note.offset..note.offset + old.len(), new);
+            self.layout();
+            Ok(())
+        }
+
+        /// Adds a build ID note, loaded along with a `PT_NOTE` segment
+        /// unless the object is relocatable.
+        fn add_build_id_note(&mut self, id: &[u8]) -> io::Result<()> {
+            let data = Note::new(GNU, NT_GNU_BUILD_ID, id.to_vec()).encode();
+            let mut section = Section::new(BUILD_ID, SectionType::Note, section::SHF_ALLOC);
+            section.addralign = 4;
+            if self.header.is_rel() {
+                section.data = data;
+                self.sections.push(section);
+                self.layout();
+                return Ok(());
+            }
+
+            /* the new segment is counted when the program headers move */
+            let count = self.segments.len();
+            let at = self.segments.iter()
+                .rposition(|s| matches!(s.etype, SegmentType::Note))
+                .or_else(|| self.segments.iter().rposition(|s| s.is_load()))
+                .map_or(count, |i| i + 1);
+            let mut seg = Segment::new(SegmentType::Note, segment::PF_R);
+            seg.align = 4;
+            self.segments.insert(at, seg);
+            let ext = self.extension_segment();
+            if self.segments.len() == count + 1 {
+                self.segments.remove(at);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    "no room for another program header"));
+            }
+
+            /* moved past the end of the segment, then trimmed of the spare room */
+            let len = data.len();
+            self.sections.push(section);
+            let ndx = self.sections.len() - 1;
+            self.resize_section(ndx, data);
+            let section = &mut self.sections[ndx];
+            section.data.truncate(len);
+            let (offset, addr) = (section.offset, section.addr);
+            let end = offset + len as u64;
+            let load = &mut self.segments[ext];
+            load.filesz = end - load.offset;
+            load.memsz = load.filesz;
+
+            let note = &mut self.segments[if ext <= at { at + 1 } else { at }];
+            note.offset = offset;
+            note.vaddr = addr;
+            note.paddr = addr;
+            note.filesz = len as u64;
+            note.memsz = len as u64;
+            self.layout();
+            Ok(())
+        }
+
+        /// Computes the build ID of the object as it would be written,
+        /// the build ID itself excluded.
+        ///
+        /// The ID should already have the right size, see
+        /// `update_build_id`.
+        ///
+        /// `Uuid` IDs are read from `/dev/urandom`, so they fail with an
+        /// `Unsupported` error on platforms without it.
+        pub fn compute_build_id(&self, style: Style) -> io::Result<Vec<u8>> {
+            if style == Style::Uuid {
+                let mut id = vec![0u8; style.size()];
+                let mut random = std::fs::File::open("/dev/urandom").map_err(|err| {
+                    let msg = format!("uuid build ids need /dev/urandom: {}", err);
+                    io::Error::new(io::ErrorKind::Unsupported, msg)
+                })?;
+                random.read_exact(&mut id)?;
+                return Ok(id);
+            }
+
+            let mut file = io::Cursor::new(vec![]);
+            self.write_to(&mut file)?;
+            let mut contents = file.into_inner();
+            if let Some((ndx, note)) = self.build_id_note() {
+                let at = self.sections[ndx].offset as usize + note.desc_offset();
+                contents[at..at + note.desc.len()].fill(0);
+            }
+            Ok(match style {
+                Style::Sha1 => digest::sha1(&contents).to_vec(),
+                _           => digest::md5(&contents).to_vec(),
+            })
+        }
+
+        /// Computes the build ID and writes it, adding a note if needed.
+        pub fn update_build_id(&mut self, style: Style) -> io::Result<Vec<u8>> {
+            self.set_build_id(&vec![0u8; style.size()])?;
+            let id = self.compute_build_id(style)?;
+            self.set_build_id(&id)?;
+            Ok(id)
+        }
+    }
+}
+
+/// Build ID methods.
+mod lookup {
+    use super::*;
+
+    impl Object {
+        /// Get the index of the section holding the build ID note, and
+        /// the note.
+        pub(crate) fn build_id_note(&self) -> Option<(usize, Note)> {
+            self.notes().into_iter().find(|(_, n)| n.is_build_id())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample, write};
+
+    #[test]
+    fn loaded_note_keeps_size() {
+        let mut obj = Object::from_bytes(&sample("main"));
+        let old = obj.build_id().unwrap();
+        assert_eq!(old.len(), 20);
+        assert!(obj.update_build_id(Style::Md5).is_err());
+        assert_eq!(obj.build_id().unwrap(), old);
+
+        let id = obj.update_build_id(Style::Sha1).unwrap();
+        let again = Object::from_bytes(&write(&obj));
+        assert_eq!(again.build_id().unwrap(), id);
+        assert_eq!(again.compute_build_id(Style::Sha1).unwrap(), id);
+    }
+
+    #[test]
+    fn relocatable_note_resizes() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        assert_eq!(obj.update_build_id(Style::Sha1).unwrap().len(), 20);
+        let id = obj.update_build_id(Style::Md5).unwrap();
+        assert_eq!(id.len(), 16);
+        let again = Object::from_bytes(&write(&obj));
+        assert_eq!(again.build_id().unwrap(), id);
+        assert_eq!(again.compute_build_id(Style::Md5).unwrap(), id);
+    }
+
+    #[test]
+    fn added_note_is_loaded() {
+        /* hide the existing note by changing its type */
+        let mut obj = Object::from_bytes(&sample("main"));
+        let (ndx, note) = obj.build_id_note().unwrap();
+        obj.sections[ndx].data[note.offset + 8] ^= 0xff;
+        assert!(obj.build_id().is_none());
+
+        let id = obj.update_build_id(Style::Md5).unwrap();
+        let obj = Object::from_bytes(&write(&obj));
+        assert_eq!(obj.build_id().unwrap(), id);
+        let (ndx, _) = obj.build_id_note().unwrap();
+        let section = &obj.sections[ndx];
+        assert!(section.is_alloc());
+        let covers = |s: &Segment| s.offset <= section.offset
+            && section.offset + section.size <= s.offset + s.filesz
+            && s.vaddr + section.offset - s.offset == section.addr;
+        assert!(obj.segments.iter().any(|s| matches!(s.etype, SegmentType::Note) && covers(s)));
+        assert!(obj.segments.iter().any(|s| s.is_load() && covers(s)));
+
+        /* the program headers have already moved */
+        let mut obj = obj;
+        let (ndx, note) = obj.build_id_note().unwrap();
+        obj.sections[ndx].data[note.offset + 8] ^= 0xff;
+        assert!(obj.set_build_id(&id).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn uuid_is_random() {
+        let obj = Object::from_bytes(&sample("main"));
+        let a = obj.compute_build_id(Style::Uuid).unwrap();
+        let b = obj.compute_build_id(Style::Uuid).unwrap();
+        assert_eq!(a.len(), 16);
+        assert_ne!(a, b);
+    }
+}
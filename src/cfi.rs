@@ -0,0 +1,1013 @@
+//! Regarding call frame information (`.eh_frame` and `.debug_frame`),
+//! telling how to unwind the stack at any instruction.
+//!
+//! The information is made of entries: common information entries
+//! (CIEs) shared by many frame description entries (FDEs), each
+//! covering the instructions of a function. Their instructions build a
+//! table whose rows tell, from an address on, how to compute the
+//! canonical frame address (CFA) and where the registers of the caller
+//! were saved.
+//!
+//! `.eh_frame` is the loaded flavour, with augmentations telling how
+//! pointers are encoded, and an `.eh_frame_hdr` binary search table
+//! pointing at the FDE of an address. `.debug_frame` is the debugging
+//! one, with absolute addresses.
+//!
+//! Registers are numbered as DWARF does for x86-64, the return address
+//! being register 16. Addresses are the ones of the object, so those of
+//! position independent objects have to be rebased by their caller.
+
+use std::collections::HashMap;
+
+use crate::dwarf::Reader;
+use crate::object::Object;
+
+pub const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+pub const DW_CFA_OFFSET: u8 = 0x80;
+pub const DW_CFA_RESTORE: u8 = 0xc0;
+pub const DW_CFA_NOP: u8 = 0x00;
+pub const DW_CFA_SET_LOC: u8 = 0x01;
+pub const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+pub const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+pub const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+pub const DW_CFA_OFFSET_EXTENDED: u8 = 0x05;
+pub const DW_CFA_RESTORE_EXTENDED: u8 = 0x06;
+pub const DW_CFA_UNDEFINED: u8 = 0x07;
+pub const DW_CFA_SAME_VALUE: u8 = 0x08;
+pub const DW_CFA_REGISTER: u8 = 0x09;
+pub const DW_CFA_REMEMBER_STATE: u8 = 0x0a;
+pub const DW_CFA_RESTORE_STATE: u8 = 0x0b;
+pub const DW_CFA_DEF_CFA: u8 = 0x0c;
+pub const DW_CFA_DEF_CFA_REGISTER: u8 = 0x0d;
+pub const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+pub const DW_CFA_DEF_CFA_EXPRESSION: u8 = 0x0f;
+pub const DW_CFA_EXPRESSION: u8 = 0x10;
+pub const DW_CFA_OFFSET_EXTENDED_SF: u8 = 0x11;
+pub const DW_CFA_DEF_CFA_SF: u8 = 0x12;
+pub const DW_CFA_DEF_CFA_OFFSET_SF: u8 = 0x13;
+pub const DW_CFA_VAL_OFFSET: u8 = 0x14;
+pub const DW_CFA_VAL_OFFSET_SF: u8 = 0x15;
+pub const DW_CFA_VAL_EXPRESSION: u8 = 0x16;
+pub const DW_CFA_GNU_ARGS_SIZE: u8 = 0x2e;
+pub const DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED: u8 = 0x2f;
+
+pub const DW_EH_PE_ABSPTR: u8 = 0x00;
+pub const DW_EH_PE_ULEB128: u8 = 0x01;
+pub const DW_EH_PE_UDATA2: u8 = 0x02;
+pub const DW_EH_PE_UDATA4: u8 = 0x03;
+pub const DW_EH_PE_UDATA8: u8 = 0x04;
+pub const DW_EH_PE_SLEB128: u8 = 0x09;
+pub const DW_EH_PE_SDATA2: u8 = 0x0a;
+pub const DW_EH_PE_SDATA4: u8 = 0x0b;
+pub const DW_EH_PE_SDATA8: u8 = 0x0c;
+pub const DW_EH_PE_PCREL: u8 = 0x10;
+pub const DW_EH_PE_TEXTREL: u8 = 0x20;
+pub const DW_EH_PE_DATAREL: u8 = 0x30;
+pub const DW_EH_PE_FUNCREL: u8 = 0x40;
+pub const DW_EH_PE_ALIGNED: u8 = 0x50;
+pub const DW_EH_PE_INDIRECT: u8 = 0x80;
+pub const DW_EH_PE_OMIT: u8 = 0xff;
+
+/// Register holding the stack pointer.
+pub const REG_SP: u16 = 7;
+/// Register holding the frame pointer.
+pub const REG_FP: u16 = 6;
+/// Register holding the return address.
+pub const REG_RA: u16 = 16;
+
+/// Most frames a backtrace goes through.
+const MAX_FRAMES: usize = 256;
+
+/// A common information entry.
+#[derive(Clone, Debug)]
+pub struct Cie {
+    /// Offset of the entry in its section.
+    pub offset:         u64,
+    /// Version of the entry format.
+    pub version:        u8,
+    /// Augmentation string.
+    pub augmentation:   String,
+    /// Size in bytes of addresses.
+    pub address_size:   u8,
+    /// Factor of locations advances.
+    pub code_align:     u64,
+    /// Factor of offsets.
+    pub data_align:     i64,
+    /// Register holding the return address.
+    pub ra_register:    u16,
+    /// Encoding of the addresses of FDEs.
+    pub fde_encoding:   u8,
+    /// Encoding of the LSDA pointers of FDEs.
+    pub lsda_encoding:  u8,
+    /// Address of the personality routine, or of where it is stored
+    /// for indirect encodings.
+    pub personality:    Option<u64>,
+    /// Wether FDEs are signal handler frames.
+    pub signal_frame:   bool,
+    /// Initial instructions.
+    pub instructions:   Vec<u8>,
+}
+
+/// A frame description entry.
+#[derive(Clone, Debug)]
+pub struct Fde {
+    /// Offset of the entry in its section.
+    pub offset:         u64,
+    /// Offset of its CIE in the section.
+    pub cie:            u64,
+    /// First address covered.
+    pub start:          u64,
+    /// Address past the last covered.
+    pub end:            u64,
+    /// Address of the language specific data area, if any.
+    pub lsda:           Option<u64>,
+    /// Instructions.
+    pub instructions:   Vec<u8>,
+}
+
+/// How to recover the value of a register of the caller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rule {
+    /// The value is lost.
+    Undefined,
+    /// The value is unchanged.
+    SameValue,
+    /// The value is saved at the CFA plus an offset.
+    Offset(i64),
+    /// The value is the CFA plus an offset.
+    ValOffset(i64),
+    /// The value is in another register.
+    Register(u16),
+    /// The value is saved at the address computed by an expression.
+    Expression(Vec<u8>),
+    /// The value is computed by an expression.
+    ValExpression(Vec<u8>),
+}
+
+/// How to compute the canonical frame address.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cfa {
+    /// A register plus an offset.
+    RegOffset(u16, i64),
+    /// An expression.
+    Expression(Vec<u8>),
+}
+
+/// A row of the unwind table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnwindRow {
+    /// First address of the row.
+    pub start:          u64,
+    /// Address past the last of the row.
+    pub end:            u64,
+    /// How to compute the CFA.
+    pub cfa:            Cfa,
+    /// Rules of the registers which are not the same value, by
+    /// register.
+    pub rules:          Vec<(u16, Rule)>,
+    /// Register holding the return address.
+    pub ra_register:    u16,
+    /// Wether the frame is a signal handler frame.
+    pub signal_frame:   bool,
+}
+
+/// Values of registers, along with the program counter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Registers {
+    /// Program counter.
+    pub pc:     u64,
+    /// Values, by register, none if unknown.
+    values:     Vec<Option<u64>>,
+}
+
+/// Which section frame entries come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Source {
+    /// `.eh_frame`.
+    EhFrame,
+    /// `.debug_frame`.
+    DebugFrame,
+}
+
+/// Finds the unwind rows of addresses, and unwinds stacks.
+pub struct FrameTable {
+    /// Address and contents of `.eh_frame`.
+    eh_frame:       (u64, Vec<u8>),
+    /// Contents of `.debug_frame`.
+    debug_frame:    Vec<u8>,
+    /// Address of `.text`, for text relative pointers.
+    text:           u64,
+    /// Address of `.got`, for data relative pointers of `.eh_frame`.
+    got:            u64,
+    /// Start address and FDE address of every entry of the
+    /// `.eh_frame_hdr` table, sorted by address.
+    search_table:   Vec<(u64, u64)>,
+    /// Range of every FDE with their section and offset, sorted by
+    /// address, for the ones the search table does not cover.
+    fdes:           Vec<(u64, u64, Source, usize)>,
+    /// CIEs by section and offset.
+    cies:           HashMap<(Source, u64), Cie>,
+}
+
+/// Simple register methods.
+impl Registers {
+    /// Creates registers with only the program counter known.
+    pub fn new(pc: u64) -> Self {
+        Self { pc, values: vec![] }
+    }
+
+    /// Get the value of a register, if known.
+    pub fn get(&self, reg: u16) -> Option<u64> {
+        self.values.get(reg as usize).copied().flatten()
+    }
+
+    /// Set the value of a register.
+    pub fn set(&mut self, reg: u16, value: u64) {
+        if self.values.len() <= reg as usize {
+            self.values.resize(reg as usize + 1, None);
+        }
+        self.values[reg as usize] = Some(value);
+    }
+
+    /// Forget the value of a register.
+    pub fn unset(&mut self, reg: u16) {
+        if let Some(value) = self.values.get_mut(reg as usize) {
+            *value = None;
+        }
+    }
+}
+
+/// Simple unwind row methods.
+impl UnwindRow {
+    /// Get the rule of a register, the same value if none is given.
+    pub fn rule(&self, reg: u16) -> Rule {
+        self.rules.iter()
+            .find(|(r, _)| *r == reg)
+            .map_or(Rule::SameValue, |(_, rule)| rule.clone())
+    }
+}
+
+/// Simple frame table methods.
+impl FrameTable {
+    /// Get the unwind row covering an address, if any.
+    pub fn row(&self, addr: u64) -> Option<UnwindRow> {
+        let (source, fde) = self.fde(addr)?;
+        let cie = self.cies.get(&(source, fde.cie))?;
+        Some(evaluation::execute(cie, &fde, addr, self.encoding_bases(source)))
+    }
+
+    /// Get the FDE covering an address, along with its section.
+    ///
+    /// The `.eh_frame_hdr` table is searched first, then every other
+    /// FDE.
+    fn fde(&self, addr: u64) -> Option<(Source, Fde)> {
+        let at = self.search_table.partition_point(|e| e.0 <= addr);
+        if let Some(&(_, fde_addr)) = at.checked_sub(1).map(|i| &self.search_table[i]) {
+            let offset = fde_addr.wrapping_sub(self.eh_frame.0) as usize;
+            if let Some(fde) = self.parse_fde(Source::EhFrame, offset).filter(|f| addr < f.end) {
+                return Some((Source::EhFrame, fde));
+            }
+        }
+        let at = self.fdes.partition_point(|f| f.0 <= addr);
+        let &(_, _, source, offset) = self.fdes[..at].iter().rev().find(|f| addr < f.1)?;
+        Some((source, self.parse_fde(source, offset)?))
+    }
+
+    /// Get the unwind rows of every FDE, by address.
+    pub fn rows(&self) -> Vec<UnwindRow> {
+        let indexed = self.search_table.iter()
+            .map(|&(_, fde)| (Source::EhFrame, fde.wrapping_sub(self.eh_frame.0) as usize));
+        let others = self.fdes.iter().map(|&(_, _, source, offset)| (source, offset));
+        let mut rows = vec![];
+        for (source, offset) in indexed.chain(others) {
+            let fde = match self.parse_fde(source, offset) {
+                Some(fde) => fde,
+                None      => continue,
+            };
+            let cie = match self.cies.get(&(source, fde.cie)) {
+                Some(cie) => cie,
+                None      => continue,
+            };
+            let mut addr = fde.start;
+            while addr < fde.end {
+                let row = evaluation::execute(cie, &fde, addr, self.encoding_bases(source));
+                addr = row.end;
+                rows.push(row);
+            }
+        }
+        rows.sort_by_key(|r| r.start);
+        rows
+    }
+
+    /// Computes the registers of the caller, given the ones of a frame
+    /// and a way to read a word of memory.
+    ///
+    /// The program counter of the caller is its return address. None is
+    /// returned when it is unknown, at the outermost frame.
+    pub fn step<F>(&self, regs: &Registers, read: &mut F, innermost: bool) -> Option<Registers>
+    where
+        F: FnMut(u64) -> Option<u64>,
+    {
+        /* return addresses may be past the end of the calling function */
+        let lookup = if innermost { regs.pc } else { regs.pc.wrapping_sub(1) };
+        let mut row = self.row(lookup)?;
+        if !innermost && row.signal_frame {
+            row = self.row(regs.pc)?;
+        }
+
+        let mut pc_regs = regs.clone();
+        pc_regs.set(REG_RA, regs.pc);
+        let cfa = match &row.cfa {
+            Cfa::RegOffset(reg, offset) => pc_regs.get(*reg)?.wrapping_add(*offset as u64),
+            Cfa::Expression(expr)       => evaluation::evaluate(expr, &pc_regs, read, None)?,
+        };
+        let mut caller = regs.clone();
+        for (reg, rule) in &row.rules {
+            let value = match rule {
+                Rule::Undefined             => None,
+                Rule::SameValue             => pc_regs.get(*reg),
+                Rule::Offset(offset)        => read(cfa.wrapping_add(*offset as u64)),
+                Rule::ValOffset(offset)     => Some(cfa.wrapping_add(*offset as u64)),
+                Rule::Register(other)       => pc_regs.get(*other),
+                Rule::Expression(expr)      => {
+                    evaluation::evaluate(expr, &pc_regs, read, Some(cfa)).and_then(&mut *read)
+                }
+                Rule::ValExpression(expr)   => evaluation::evaluate(expr, &pc_regs, read, Some(cfa)),
+            };
+            match value {
+                Some(value) => caller.set(*reg, value),
+                None        => caller.unset(*reg),
+            }
+        }
+        caller.set(REG_SP, cfa);
+        caller.pc = match row.rule(row.ra_register) {
+            Rule::Undefined => return None,
+            _ => caller.get(row.ra_register)?,
+        };
+        caller.unset(REG_RA);
+        Some(caller)
+    }
+
+    /// Get the program counters of the frames of a stack, the innermost
+    /// first, given the registers of the innermost frame and a way to
+    /// read a word of memory.
+    ///
+    /// Unwinding stops at the outermost frame, at an address without
+    /// frame information, or when a caller would repeat the frame, with
+    /// the same program counter and a stack pointer that does not grow.
+    pub fn unwind<F>(&self, regs: &Registers, mut read: F) -> Vec<u64>
+    where
+        F: FnMut(u64) -> Option<u64>,
+    {
+        let mut pcs = vec![regs.pc];
+        let mut regs = regs.clone();
+        while pcs.len() < MAX_FRAMES {
+            let caller = match self.step(&regs, &mut read, pcs.len() == 1) {
+                Some(caller) if caller.pc != 0 => caller,
+                _ => break,
+            };
+            if let (Some(sp), Some(caller_sp)) = (regs.get(REG_SP), caller.get(REG_SP)) {
+                if caller_sp <= sp && caller.pc == regs.pc {
+                    break;
+                }
+            }
+            pcs.push(caller.pc);
+            regs = caller;
+        }
+        pcs
+    }
+
+    /// Get the bases of pointer encodings of a section: its address, and
+    /// the text and data addresses.
+    fn encoding_bases(&self, source: Source) -> (u64, u64, u64) {
+        match source {
+            Source::EhFrame     => (self.eh_frame.0, self.text, self.got),
+            Source::DebugFrame  => (0, 0, 0),
+        }
+    }
+
+    /// Get the contents of a section.
+    fn data(&self, source: Source) -> &[u8] {
+        match source {
+            Source::EhFrame     => &self.eh_frame.1,
+            Source::DebugFrame  => &self.debug_frame,
+        }
+    }
+}
+
+/// Parsing methods.
+mod parsing {
+    use super::*;
+
+    /// What an entry header tells.
+    enum Entry {
+        /// A CIE.
+        Cie,
+        /// An FDE, with the offset of its CIE.
+        Fde(u64),
+        /// A terminator.
+        End,
+    }
+
+    impl FrameTable {
+        /// Gathers the frame information of an object.
+        ///
+        /// The `.eh_frame_hdr` table is used when present, and then the
+        /// FDEs of `.eh_frame` are only parsed when looked up. Other
+        /// FDEs are indexed.
+        pub fn new(obj: &Object) -> Self {
+            let section = |name| obj.section_index(name).map(|i| &obj.sections[i]);
+            let eh_frame = section(".eh_frame")
+                .map_or((0, vec![]), |s| (s.addr, s.data.clone()));
+            let debug_frame = obj.dwarf_section(".debug_frame").map_or(vec![], |d| d.into_owned());
+            let mut table = Self {
+                eh_frame,
+                debug_frame,
+                text:           section(".text").map_or(0, |s| s.addr),
+                got:            section(".got").map_or(0, |s| s.addr),
+                search_table:   vec![],
+                fdes:           vec![],
+                cies:           HashMap::new(),
+            };
+            if let Some(hdr) = section(".eh_frame_hdr") {
+                table.search_table = search_table(hdr.addr, &hdr.data);
+            }
+
+            for source in [Source::EhFrame, Source::DebugFrame] {
+                let indexed = source == Source::EhFrame && !table.search_table.is_empty();
+                let mut offset = 0;
+                while offset < table.data(source).len() {
+                    let (entry, next) = match table.entry_header(source, offset) {
+                        Some(header) => header,
+                        None         => break,
+                    };
+                    match entry {
+                        Entry::Cie => {
+                            if let Some(cie) = table.parse_cie(source, offset) {
+                                table.cies.insert((source, offset as u64), cie);
+                            }
+                        }
+                        Entry::Fde(_) if indexed => (),
+                        Entry::Fde(_) => {
+                            if let Some(fde) = table.parse_fde(source, offset) {
+                                if fde.start < fde.end {
+                                    table.fdes.push((fde.start, fde.end, source, offset));
+                                }
+                            }
+                        }
+                        Entry::End => break,
+                    }
+                    offset = next;
+                }
+            }
+            table.fdes.sort_unstable_by_key(|f| (f.0, f.1));
+            table
+        }
+
+        /// Reads the header of the entry at an offset, and get the offset
+        /// of the next one, unless it is truncated.
+        fn entry_header(&self, source: Source, offset: usize) -> Option<(Entry, usize)> {
+            let mut r = Reader::at(self.data(source), offset);
+            let (next, is64) = r.unit_end()?;
+            if next == r.pos {
+                return Some((Entry::End, next));
+            }
+            let id_pos = r.pos as u64;
+            let id = r.offset(is64)?;
+            let entry = match source {
+                Source::EhFrame if id == 0 => Entry::Cie,
+                Source::EhFrame => Entry::Fde(id_pos.wrapping_sub(id)),
+                Source::DebugFrame if id == 0xffff_ffff || id == u64::MAX => Entry::Cie,
+                Source::DebugFrame => Entry::Fde(id),
+            };
+            Some((entry, next))
+        }
+
+        /// Parses the CIE at an offset, unless it is truncated.
+        fn parse_cie(&self, source: Source, offset: usize) -> Option<Cie> {
+            let data = self.data(source);
+            let (end, is64) = Reader::at(data, offset).unit_end()?;
+            let data = &data[..end];
+            let mut r = Reader::at(data, offset);
+            r.initial_length()?;
+            let _id = r.offset(is64)?;
+            let version = r.u8()?;
+            let augmentation = r.cstr()?;
+            let mut address_size = 8;
+            if version >= 4 {
+                address_size = r.u8()?;
+                let _segment_size = r.u8()?;
+            }
+            let code_align = r.uleb()?;
+            let data_align = r.sleb()?;
+            let ra_register = if version == 1 { r.u8()? as u64 } else { r.uleb()? } as u16;
+
+            let mut cie = Cie {
+                offset:         offset as u64,
+                version,
+                augmentation:   augmentation.clone(),
+                address_size,
+                code_align,
+                data_align,
+                ra_register,
+                fde_encoding:   DW_EH_PE_ABSPTR,
+                lsda_encoding:  DW_EH_PE_OMIT,
+                personality:    None,
+                signal_frame:   false,
+                instructions:   vec![],
+            };
+            if augmentation.starts_with('z') {
+                let len = r.uleb()? as usize;
+                let after = r.pos.saturating_add(len);
+                for c in augmentation.chars().skip(1) {
+                    match c {
+                        'R' => cie.fde_encoding = r.u8()?,
+                        'L' => cie.lsda_encoding = r.u8()?,
+                        'P' => {
+                            let encoding = r.u8()?;
+                            cie.personality = self.pointer(&mut r, source, encoding, address_size, 0);
+                        }
+                        'S' => cie.signal_frame = true,
+                        _ => break,
+                    }
+                }
+                r.pos = after;
+            } else if augmentation == "eh" {
+                let _eh_data = r.address(address_size)?;
+            }
+            cie.instructions = data.get(r.pos..)?.to_vec();
+            Some(cie)
+        }
+
+        /// Parses the FDE at an offset, unless its CIE is unknown.
+        pub(crate) fn parse_fde(&self, source: Source, offset: usize) -> Option<Fde> {
+            let data = self.data(source);
+            if offset >= data.len() {
+                return None;
+            }
+            let (cie_offset, end) = match self.entry_header(source, offset)? {
+                (Entry::Fde(cie), end) => (cie, end),
+                _ => return None,
+            };
+            let cie = match self.cies.get(&(source, cie_offset)) {
+                Some(cie) => cie.clone(),
+                None      => self.parse_cie(source, cie_offset as usize)?,
+            };
+
+            let data = &data[..end];
+            let mut r = Reader::at(data, offset);
+            let (_, is64) = r.initial_length()?;
+            let _cie = r.offset(is64)?;
+            let size = cie.address_size;
+            let start = self.pointer(&mut r, source, cie.fde_encoding, size, 0)?;
+            /* the length is encoded as the start, without application */
+            let length = self.pointer(&mut r, source, cie.fde_encoding & 0x0f, size, 0)?;
+            let mut lsda = None;
+            if cie.augmentation.starts_with('z') {
+                let len = r.uleb()? as usize;
+                let after = r.pos.saturating_add(len);
+                if cie.lsda_encoding != DW_EH_PE_OMIT && len != 0 {
+                    lsda = self.pointer(&mut r, source, cie.lsda_encoding, size, start);
+                }
+                r.pos = after;
+            }
+            Some(Fde {
+                offset:         offset as u64,
+                cie:            cie_offset,
+                start,
+                end:            start.wrapping_add(length),
+                lsda,
+                instructions:   data.get(r.pos..)?.to_vec(),
+            })
+        }
+
+        /// Reads a pointer with the given encoding.
+        ///
+        /// Indirect pointers are not followed, the address holding them
+        /// is returned. Omitted pointers are none.
+        pub(crate) fn pointer(&self, r: &mut Reader, source: Source, encoding: u8, size: u8, func: u64) -> Option<u64> {
+            let (section, text, data) = self.encoding_bases(source);
+            read_pointer(r, encoding, size, (section, text, data, func))
+        }
+    }
+
+    /// Reads a pointer with the given encoding, given the bases of the
+    /// section, text, data and function.
+    ///
+    /// Pointers of an unknown encoding are none, as their size is
+    /// unknown too.
+    pub(crate) fn read_pointer(r: &mut Reader, encoding: u8, size: u8, bases: (u64, u64, u64, u64)) -> Option<u64> {
+        if encoding == DW_EH_PE_OMIT {
+            return None;
+        }
+        let here = bases.0.wrapping_add(r.pos as u64);
+        if encoding & 0x70 == DW_EH_PE_ALIGNED {
+            let size = (size as usize).max(1);
+            r.pos = r.pos.checked_next_multiple_of(size)?;
+        }
+        let value = match encoding & 0x0f {
+            DW_EH_PE_ABSPTR     => r.address(size)?,
+            DW_EH_PE_ULEB128    => r.uleb()?,
+            DW_EH_PE_UDATA2     => r.u16()? as u64,
+            DW_EH_PE_UDATA4     => r.u32()? as u64,
+            DW_EH_PE_UDATA8     => r.u64()?,
+            DW_EH_PE_SLEB128    => r.sleb()? as u64,
+            DW_EH_PE_SDATA2     => r.u16()? as i16 as u64,
+            DW_EH_PE_SDATA4     => r.u32()? as i32 as u64,
+            DW_EH_PE_SDATA8     => r.u64()?,
+            _ => return None,
+        };
+        let base = match encoding & 0x70 {
+            DW_EH_PE_PCREL      => here,
+            DW_EH_PE_TEXTREL    => bases.1,
+            DW_EH_PE_DATAREL    => bases.2,
+            DW_EH_PE_FUNCREL    => bases.3,
+            _ => 0,
+        };
+        Some(base.wrapping_add(value))
+    }
+
+    /// Reads the binary search table of `.eh_frame_hdr`, empty if it has
+    /// none.
+    fn search_table(addr: u64, data: &[u8]) -> Vec<(u64, u64)> {
+        if data.len() < 4 || data[0] != 1 {
+            return vec![];
+        }
+        let mut r = Reader::at(data, 4);
+        /* data relative pointers are relative to the header */
+        let bases = (addr, 0, addr, 0);
+        let (eh_frame_ptr_enc, fde_count_enc, table_enc) = (data[1], data[2], data[3]);
+        let _eh_frame = read_pointer(&mut r, eh_frame_ptr_enc, 8, bases);
+        let count = match read_pointer(&mut r, fde_count_enc, 8, bases) {
+            Some(count) if table_enc != DW_EH_PE_OMIT => count,
+            _ => return vec![],
+        };
+        let mut table = vec![];
+        for _ in 0..count {
+            let start = read_pointer(&mut r, table_enc, 8, bases);
+            let fde = read_pointer(&mut r, table_enc, 8, bases);
+            match (start, fde) {
+                (Some(start), Some(fde)) => table.push((start, fde)),
+                _ => break,
+            }
+        }
+        table.sort_unstable();
+        table
+    }
+}
+
+/// Evaluation methods.
+mod evaluation {
+    use super::*;
+
+    /// Registers rules and CFA.
+    #[derive(Clone)]
+    struct State {
+        cfa:    Cfa,
+        rules:  Vec<(u16, Rule)>,
+    }
+
+    /// Sets the rule of a register.
+    fn set_rule(rules: &mut Vec<(u16, Rule)>, reg: u16, rule: Rule) {
+        match rules.iter_mut().find(|(r, _)| *r == reg) {
+            Some(entry) => entry.1 = rule,
+            None        => rules.push((reg, rule)),
+        }
+    }
+
+    /// Executes the instructions of a CIE then an FDE, up to the row
+    /// covering an address.
+    pub(crate) fn execute(cie: &Cie, fde: &Fde, addr: u64, bases: (u64, u64, u64)) -> UnwindRow {
+        let mut state = State { cfa: Cfa::RegOffset(REG_SP, 0), rules: vec![] };
+        let mut loc = fde.start;
+        run(cie, &cie.instructions, &mut state, None, &mut loc, u64::MAX, bases, fde.start);
+        let initial = state.rules.clone();
+        let mut start = fde.start;
+        let end = run(cie, &fde.instructions, &mut state, Some(&initial), &mut start, addr, bases, fde.start)
+            .unwrap_or(fde.end);
+
+        let mut rules: Vec<(u16, Rule)> = state.rules.into_iter()
+            .filter(|(_, rule)| *rule != Rule::SameValue)
+            .collect();
+        rules.sort_by_key(|(reg, _)| *reg);
+        UnwindRow {
+            start,
+            end,
+            cfa:            state.cfa,
+            rules,
+            ra_register:    cie.ra_register,
+            signal_frame:   cie.signal_frame,
+        }
+    }
+
+    /// Runs instructions while the location is at most an address.
+    ///
+    /// The location is left at the start of the current row, and the
+    /// location of the next row is returned, if any. An unknown
+    /// instruction stops them, as its operands cannot be skipped.
+    #[allow(clippy::too_many_arguments)]
+    fn run(cie: &Cie, instructions: &[u8], state: &mut State, initial: Option<&[(u16, Rule)]>,
+           loc: &mut u64, addr: u64, bases: (u64, u64, u64), func: u64) -> Option<u64> {
+        let mut r = Reader::new(instructions);
+        let mut stack = vec![];
+        let factored = |offset: u64| (offset as i64).wrapping_mul(cie.data_align);
+        let advance = |loc: u64, delta: u64| loc.wrapping_add(delta.wrapping_mul(cie.code_align));
+        let restore = |reg: u16| {
+            initial.and_then(|rules| rules.iter().find(|(r, _)| *r == reg))
+                .map_or(Rule::SameValue, |(_, rule)| rule.clone())
+        };
+        while !r.is_empty() {
+            let opcode = r.u8()?;
+            let mut next = None;
+            match (opcode & 0xc0, opcode) {
+                (DW_CFA_ADVANCE_LOC, _) => next = Some(advance(*loc, (opcode & 0x3f) as u64)),
+                (DW_CFA_OFFSET, _) => {
+                    let offset = factored(r.uleb()?);
+                    set_rule(&mut state.rules, (opcode & 0x3f) as u16, Rule::Offset(offset));
+                }
+                (DW_CFA_RESTORE, _) => {
+                    let reg = (opcode & 0x3f) as u16;
+                    set_rule(&mut state.rules, reg, restore(reg));
+                }
+                (_, DW_CFA_NOP) => (),
+                (_, DW_CFA_SET_LOC) => {
+                    let base = (bases.0, bases.1, bases.2, func);
+                    next = parsing::read_pointer(&mut r, cie.fde_encoding, cie.address_size, base);
+                }
+                (_, DW_CFA_ADVANCE_LOC1) => next = Some(advance(*loc, r.u8()? as u64)),
+                (_, DW_CFA_ADVANCE_LOC2) => next = Some(advance(*loc, r.u16()? as u64)),
+                (_, DW_CFA_ADVANCE_LOC4) => next = Some(advance(*loc, r.u32()? as u64)),
+                (_, DW_CFA_OFFSET_EXTENDED) => {
+                    let reg = r.uleb()? as u16;
+                    let offset = factored(r.uleb()?);
+                    set_rule(&mut state.rules, reg, Rule::Offset(offset));
+                }
+                (_, DW_CFA_RESTORE_EXTENDED) => {
+                    let reg = r.uleb()? as u16;
+                    set_rule(&mut state.rules, reg, restore(reg));
+                }
+                (_, DW_CFA_UNDEFINED) => set_rule(&mut state.rules, r.uleb()? as u16, Rule::Undefined),
+                (_, DW_CFA_SAME_VALUE) => set_rule(&mut state.rules, r.uleb()? as u16, Rule::SameValue),
+                (_, DW_CFA_REGISTER) => {
+                    let reg = r.uleb()? as u16;
+                    let other = r.uleb()? as u16;
+                    set_rule(&mut state.rules, reg, Rule::Register(other));
+                }
+                (_, DW_CFA_REMEMBER_STATE) => stack.push(state.clone()),
+                (_, DW_CFA_RESTORE_STATE) => {
+                    if let Some(saved) = stack.pop() {
+                        *state = saved;
+                    }
+                }
+                (_, DW_CFA_DEF_CFA) => {
+                    let reg = r.uleb()? as u16;
+                    state.cfa = Cfa::RegOffset(reg, r.uleb()? as i64);
+                }
+                (_, DW_CFA_DEF_CFA_SF) => {
+                    let reg = r.uleb()? as u16;
+                    state.cfa = Cfa::RegOffset(reg, r.sleb()?.wrapping_mul(cie.data_align));
+                }
+                (_, DW_CFA_DEF_CFA_REGISTER) => {
+                    let reg = r.uleb()? as u16;
+                    if let Cfa::RegOffset(_, offset) = state.cfa {
+                        state.cfa = Cfa::RegOffset(reg, offset);
+                    }
+                }
+                (_, DW_CFA_DEF_CFA_OFFSET) => {
+                    let offset = r.uleb()? as i64;
+                    if let Cfa::RegOffset(reg, _) = state.cfa {
+                        state.cfa = Cfa::RegOffset(reg, offset);
+                    }
+                }
+                (_, DW_CFA_DEF_CFA_OFFSET_SF) => {
+                    let offset = r.sleb()?.wrapping_mul(cie.data_align);
+                    if let Cfa::RegOffset(reg, _) = state.cfa {
+                        state.cfa = Cfa::RegOffset(reg, offset);
+                    }
+                }
+                (_, DW_CFA_DEF_CFA_EXPRESSION) => {
+                    let len = r.uleb()? as usize;
+                    state.cfa = Cfa::Expression(r.bytes(len)?.to_vec());
+                }
+                (_, DW_CFA_EXPRESSION) | (_, DW_CFA_VAL_EXPRESSION) => {
+                    let reg = r.uleb()? as u16;
+                    let len = r.uleb()? as usize;
+                    let expr = r.bytes(len)?.to_vec();
+                    let rule = if opcode == DW_CFA_EXPRESSION { Rule::Expression(expr) } else { Rule::ValExpression(expr) };
+                    set_rule(&mut state.rules, reg, rule);
+                }
+                (_, DW_CFA_OFFSET_EXTENDED_SF) => {
+                    let reg = r.uleb()? as u16;
+                    let offset = r.sleb()?.wrapping_mul(cie.data_align);
+                    set_rule(&mut state.rules, reg, Rule::Offset(offset));
+                }
+                (_, DW_CFA_VAL_OFFSET) => {
+                    let reg = r.uleb()? as u16;
+                    let offset = factored(r.uleb()?);
+                    set_rule(&mut state.rules, reg, Rule::ValOffset(offset));
+                }
+                (_, DW_CFA_VAL_OFFSET_SF) => {
+                    let reg = r.uleb()? as u16;
+                    let offset = r.sleb()?.wrapping_mul(cie.data_align);
+                    set_rule(&mut state.rules, reg, Rule::ValOffset(offset));
+                }
+                (_, DW_CFA_GNU_ARGS_SIZE) => {
+                    r.uleb()?;
+                }
+                (_, DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED) => {
+                    let reg = r.uleb()? as u16;
+                    let offset = factored(r.uleb()?).wrapping_neg();
+                    set_rule(&mut state.rules, reg, Rule::Offset(offset));
+                }
+                _ => return None,
+            }
+            if let Some(next) = next {
+                if next > addr {
+                    return Some(next);
+                }
+                *loc = next;
+            }
+        }
+        None
+    }
+
+    /// Evaluates a DWARF expression of the call frame information, the
+    /// CFA pushed first for register rules.
+    ///
+    /// Only the operations found in call frame information are handled.
+    /// None is returned when a register or memory is unknown.
+    pub(crate) fn evaluate<F>(expr: &[u8], regs: &Registers, read: &mut F, cfa: Option<u64>) -> Option<u64>
+    where
+        F: FnMut(u64) -> Option<u64>,
+    {
+        let mut r = Reader::new(expr);
+        let mut stack: Vec<u64> = cfa.into_iter().collect();
+        while !r.is_empty() {
+            let op = r.u8()?;
+            match op {
+                0x06 => { let addr = stack.pop()?; stack.push(read(addr)?); }          /* deref */
+                0x08 => stack.push(r.u8()? as u64),                                      /* const1u */
+                0x09 => stack.push(r.u8()? as i8 as u64),                                /* const1s */
+                0x0a => stack.push(r.u16()? as u64),                                     /* const2u */
+                0x0b => stack.push(r.u16()? as i16 as u64),                              /* const2s */
+                0x0c => stack.push(r.u32()? as u64),                                     /* const4u */
+                0x0d => stack.push(r.u32()? as i32 as u64),                              /* const4s */
+                0x0e | 0x0f => stack.push(r.u64()?),                                     /* const8u/s */
+                0x10 => stack.push(r.uleb()?),                                           /* constu */
+                0x11 => stack.push(r.sleb()? as u64),                                    /* consts */
+                0x12 => stack.push(*stack.last()?),                                     /* dup */
+                0x13 => { stack.pop()?; }                                               /* drop */
+                0x14 => stack.push(*stack.get(stack.len().checked_sub(2)?)?),           /* over */
+                0x16 => { let n = stack.len(); if n < 2 { return None; } stack.swap(n - 1, n - 2); } /* swap */
+                0x23 => { let v = stack.pop()?; stack.push(v.wrapping_add(r.uleb()?)); } /* plus_uconst */
+                0x19 | 0x1f | 0x20 => {                                                 /* abs, neg, not */
+                    let v = stack.pop()?;
+                    stack.push(match op {
+                        0x19 => (v as i64).unsigned_abs(),
+                        0x1f => v.wrapping_neg(),
+                        _    => !v,
+                    });
+                }
+                0x1a..=0x1e | 0x21 | 0x22 | 0x24..=0x27 | 0x29..=0x2e => {
+                    let b = stack.pop()?;
+                    let a = stack.pop()?;
+                    stack.push(match op {
+                        0x1a => a & b,                                                  /* and */
+                        0x1b => (a as i64).checked_div(b as i64)? as u64,              /* div */
+                        0x1c => a.wrapping_sub(b),                                      /* minus */
+                        0x1d => a.checked_rem(b)?,                                      /* mod */
+                        0x1e => a.wrapping_mul(b),                                      /* mul */
+                        0x21 => a | b,                                                  /* or */
+                        0x22 => a.wrapping_add(b),                                      /* plus */
+                        0x24 => a.wrapping_shl(b as u32),                               /* shl */
+                        0x25 => a.wrapping_shr(b as u32),                               /* shr */
+                        0x26 => (a as i64).wrapping_shr(b as u32) as u64,               /* shra */
+                        0x27 => a ^ b,                                                  /* xor */
+                        0x29 => (a == b) as u64,                                        /* eq */
+                        0x2a => ((a as i64) >= (b as i64)) as u64,                      /* ge */
+                        0x2b => ((a as i64) > (b as i64)) as u64,                       /* gt */
+                        0x2c => ((a as i64) <= (b as i64)) as u64,                      /* le */
+                        0x2d => ((a as i64) < (b as i64)) as u64,                       /* lt */
+                        _    => (a != b) as u64,                                        /* ne */
+                    });
+                }
+                0x2f => {                                                               /* skip */
+                    let skip = r.u16()? as i16 as isize;
+                    r.pos = (r.pos as isize + skip) as usize;
+                }
+                0x28 => {                                                               /* bra */
+                    let skip = r.u16()? as i16 as isize;
+                    if stack.pop()? != 0 {
+                        r.pos = (r.pos as isize + skip) as usize;
+                    }
+                }
+                0x30..=0x4f => stack.push((op - 0x30) as u64),                          /* lit0-31 */
+                0x50..=0x6f => stack.push(regs.get((op - 0x50) as u16)?),               /* reg0-31 */
+                0x70..=0x8f => {                                                        /* breg0-31 */
+                    let value = regs.get((op - 0x70) as u16)?;
+                    stack.push(value.wrapping_add(r.sleb()? as u64));
+                }
+                0x90 => { let reg = r.uleb()? as u16; stack.push(regs.get(reg)?); }      /* regx */
+                0x92 => {                                                               /* bregx */
+                    let value = regs.get(r.uleb()? as u16)?;
+                    stack.push(value.wrapping_add(r.sleb()? as u64));
+                }
+                0x96 => (),                                                             /* nop */
+                _ => return None,
+            }
+        }
+        stack.pop()
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Gathers the call frame information.
+        pub fn frame_table(&self) -> FrameTable {
+            FrameTable::new(self)
+        }
+
+        /// Get the program counters of the frames of a stack, the
+        /// innermost first, given the registers of the innermost frame
+        /// and a way to read a word of memory.
+        ///
+        /// Use a `FrameTable` for many backtraces.
+        pub fn unwind<F>(&self, regs: &Registers, read: F) -> Vec<u64>
+        where
+            F: FnMut(u64) -> Option<u64>,
+        {
+            self.frame_table().unwind(regs, read)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    #[test]
+    fn indexed_rows() {
+        let obj = Object::from_bytes(&sample("main"));
+        let table = FrameTable::new(&obj);
+        assert!(!table.search_table.is_empty() && table.fdes.is_empty());
+
+        /* main pushes its frame pointer, then uses it */
+        let cfa = |addr| table.row(addr).unwrap().cfa;
+        assert_eq!(cfa(0x113f), Cfa::RegOffset(REG_SP, 8));
+        assert_eq!(cfa(0x1140), Cfa::RegOffset(REG_SP, 16));
+        assert_eq!(cfa(0x1143), Cfa::RegOffset(6, 16));
+
+        let rows = table.rows();
+        assert!(rows.windows(2).all(|w| w[0].start <= w[1].start));
+        assert!(rows.iter().any(|r| r.start == 0x1143 && r.cfa == Cfa::RegOffset(6, 16)));
+    }
+
+    #[test]
+    fn unwind_through_main() {
+        let table = FrameTable::new(&Object::from_bytes(&sample("main")));
+        let mut regs = Registers::new(0x112f);
+        regs.set(6, 0x7f00);
+        regs.set(REG_SP, 0x7f00);
+        let stack: HashMap<u64, u64> = [(0x7f00, 0x7f40), (0x7f08, 0x116c), (0x7f48, 0)]
+            .iter()
+            .copied()
+            .collect();
+        let pcs = table.unwind(&regs, |addr| stack.get(&addr).copied());
+        assert_eq!(pcs, [0x112f, 0x116c]);
+    }
+
+    #[test]
+    fn truncated() {
+        let obj = Object::from_bytes(&sample("main"));
+        let size = FrameTable::new(&obj).eh_frame.1.len();
+        for len in 0..size {
+            let mut table = FrameTable::new(&obj);
+            table.eh_frame.1.truncate(len);
+            table.rows();
+            table.row(0x1143);
+        }
+    }
+
+    #[test]
+    fn unknown_encoding() {
+        let mut r = Reader::new(&[0x10, 0x20, 0x30, 0x40]);
+        assert_eq!(parsing::read_pointer(&mut r, DW_EH_PE_UDATA2, 8, (0, 0, 0, 0)), Some(0x2010));
+        assert_eq!(parsing::read_pointer(&mut r, 0x05, 8, (0, 0, 0, 0)), None);
+        assert_eq!(parsing::read_pointer(&mut r, DW_EH_PE_ALIGNED | DW_EH_PE_UDATA2, 0, (0, 0, 0, 0)), Some(0x4030));
+    }
+
+    #[test]
+    fn unknown_instruction() {
+        let mut table = FrameTable::new(&Object::from_bytes(&sample("main")));
+        /* in place of the frame pointer becoming the CFA register of main */
+        assert_eq!(table.eh_frame.1[0x86..0x89], [0x43, DW_CFA_DEF_CFA_REGISTER, 6]);
+        table.eh_frame.1[0x87] = 0x3f;
+        let row = table.row(0x1143).unwrap();
+        assert_eq!((row.start, row.cfa), (0x1143, Cfa::RegOffset(REG_SP, 16)));
+        assert_eq!(table.row(0x1140).unwrap().cfa, Cfa::RegOffset(REG_SP, 16));
+    }
+}
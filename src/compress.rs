@@ -0,0 +1,401 @@
+//! Regarding compressed sections.
+//!
+//! There are two ways sections get compressed:
+//! - `SHF_COMPRESSED` sections start with a compression header, giving
+//!   the algorithm, the uncompressed size and alignment.
+//! - Legacy `.zdebug_*` sections start with `ZLIB` and the uncompressed
+//!   size, as a big endian 64-bits value.
+//!
+//! Both are decompressed transparently by `section_data`, and sections
+//! can be compressed back with either zlib or zstd. The algorithms need
+//! the `zlib` and `zstd` features, otherwise the sections are reported
+//! as unsupported.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::object::Object;
+use crate::section::SHF_COMPRESSED;
+use crate::util;
+
+/// Size in bytes of a compression header.
+pub const CHDR_SIZE: usize = 24;
+
+/// Magic of legacy `.zdebug_*` sections.
+const ZLIB_MAGIC: &[u8] = b"ZLIB";
+
+/// Most space reserved per compressed byte before decompressing, since
+/// the recorded size is not trusted.
+const RESERVE_RATIO: usize = 16;
+
+/// Compression algorithm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Type {
+    /// zlib (`ELFCOMPRESS_ZLIB`).
+    Zlib,
+    /// Zstandard (`ELFCOMPRESS_ZSTD`).
+    Zstd,
+    /// An unknown algorithm.
+    Unhandled(u32),
+}
+
+/// Represents a compression header (`Elf64_Chdr`).
+#[derive(Clone, Copy, Debug)]
+pub struct Chdr {
+    /// Compression algorithm.
+    pub ctype:      Type,       // 32-bits
+                                // 32-bits reserved
+    /// Uncompressed size.
+    pub size:       u64,        // 64-bits
+    /// Uncompressed alignment.
+    pub addralign:  u64,        // 64-bits
+}
+
+/// Errors found while compressing or decompressing.
+#[derive(Debug)]
+pub enum Error {
+    /// The algorithm is unknown, or its feature is disabled.
+    Unsupported(Type),
+    /// The contents could not be decompressed.
+    Corrupt(String),
+    /// The section is already, or not, compressed, or it is loaded in
+    /// memory.
+    State(usize),
+}
+
+/// Simple type methods.
+impl Type {
+    /// Default type.
+    pub fn empty() -> Self {
+        Self::Unhandled(0)
+    }
+
+    /// Type from real value.
+    pub fn new(ctype: u32) -> Self {
+        match ctype {
+            1 => Self::Zlib,
+            2 => Self::Zstd,
+            v => Self::Unhandled(v),
+        }
+    }
+
+    /// Get string slice for the type.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zlib          => "ZLIB",
+            Self::Zstd          => "ZSTD",
+            Self::Unhandled(_)  => "Unknown",
+        }
+    }
+
+    /// Get real value of the type.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Zlib          => 1,
+            Self::Zstd          => 2,
+            Self::Unhandled(v)  => *v,
+        }
+    }
+}
+
+/// Buffer IO methods.
+pub mod io {
+    use super::*;
+
+    impl Chdr {
+        /// Extract a compression header from the start of the contents.
+        pub fn extract(data: &[u8]) -> Option<Self> {
+            if data.len() < CHDR_SIZE {
+                return None;
+            }
+            Some(Self {
+                ctype:      Type::new(util::get_u32(data, 0)),
+                size:       util::get_u64(data, 8),
+                addralign:  util::get_u64(data, 16),
+            })
+        }
+
+        /// Encode a compression header.
+        pub fn encode(&self) -> Vec<u8> {
+            let mut data = vec![];
+            data.extend_from_slice(&self.ctype.value().to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&self.size.to_le_bytes());
+            data.extend_from_slice(&self.addralign.to_le_bytes());
+            data
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use super::codec::*;
+
+    impl Object {
+        /// Get the contents of a section, decompressed if needed.
+        pub fn section_data(&self, ndx: usize) -> Result<Cow<'_, [u8]>, Error> {
+            let section = &self.sections[ndx];
+            if section.is_compressed() {
+                let chdr = Chdr::extract(&section.data)
+                    .ok_or_else(|| Error::Corrupt("truncated header".to_string()))?;
+                let data = decompress(chdr.ctype, &section.data[CHDR_SIZE..], chdr.size)?;
+                return Ok(Cow::Owned(data));
+            }
+            if is_zdebug(section.name.as_deref().unwrap_or(""), &section.data) {
+                let size = section.data[4..12].iter().fold(0u64, |v, &b| v << 8 | b as u64);
+                let data = decompress(Type::Zlib, &section.data[12..], size)?;
+                return Ok(Cow::Owned(data));
+            }
+            Ok(Cow::Borrowed(&section.data))
+        }
+
+        /// Check if the contents of a section are compressed, either way.
+        pub fn is_section_compressed(&self, ndx: usize) -> bool {
+            let section = &self.sections[ndx];
+            section.is_compressed()
+                || is_zdebug(section.name.as_deref().unwrap_or(""), &section.data)
+        }
+
+        /// Compresses a section with the given algorithm.
+        ///
+        /// Sections loaded in memory (`SHF_ALLOC`) cannot be compressed.
+        pub fn compress_section(&mut self, ndx: usize, ctype: Type) -> Result<(), Error> {
+            let section = &self.sections[ndx];
+            if self.is_section_compressed(ndx) || section.is_nobits() || section.is_alloc() {
+                return Err(Error::State(ndx));
+            }
+            let section = &mut self.sections[ndx];
+            let chdr = Chdr {
+                ctype,
+                size:       section.data.len() as u64,
+                addralign:  section.addralign.max(1) as u64,
+            };
+            let mut data = chdr.encode();
+            data.extend_from_slice(&compress(ctype, &section.data)?);
+            section.data = data;
+            section.flags |= SHF_COMPRESSED;
+            section.addralign = 8;
+            self.layout();
+            Ok(())
+        }
+
+        /// Decompresses a section in place.
+        ///
+        /// Legacy `.zdebug_*` sections get their `.debug_*` name back.
+        pub fn decompress_section(&mut self, ndx: usize) -> Result<(), Error> {
+            if !self.is_section_compressed(ndx) {
+                return Err(Error::State(ndx));
+            }
+            let data = self.section_data(ndx)?.into_owned();
+            let section = &mut self.sections[ndx];
+            if section.is_compressed() {
+                let chdr = Chdr::extract(&section.data).unwrap();
+                section.flags &= !SHF_COMPRESSED;
+                section.addralign = chdr.addralign as usize;
+                section.data = data;
+            } else {
+                section.data = data;
+                let name = section.name.as_deref().unwrap_or("");
+                let name = format!(".{}", &name[2..]);
+                self.rename_section(ndx, &name);
+            }
+            self.layout();
+            Ok(())
+        }
+
+        /// Compresses every debugging section not compressed yet, like
+        /// `objcopy --compress-debug-sections`.
+        pub fn compress_debug_sections(&mut self, ctype: Type) -> Result<(), Error> {
+            for ndx in 0..self.sections.len() {
+                let section = &self.sections[ndx];
+                if section.is_debug() && !section.is_alloc() && !section.is_nobits()
+                    && !section.data.is_empty() && !self.is_section_compressed(ndx) {
+                    self.compress_section(ndx, ctype)?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Decompresses every compressed debugging section, like
+        /// `objcopy --decompress-debug-sections`.
+        pub fn decompress_debug_sections(&mut self) -> Result<(), Error> {
+            for ndx in 0..self.sections.len() {
+                if self.sections[ndx].is_debug() && self.is_section_compressed(ndx) {
+                    self.decompress_section(ndx)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Compression methods.
+mod codec {
+    use super::*;
+    #[cfg(any(feature = "zlib", feature = "zstd"))]
+    use std::io::Read;
+    #[cfg(feature = "zlib")]
+    use std::io::Write;
+
+    /// Check if contents are in the legacy `.zdebug_*` format.
+    pub(crate) fn is_zdebug(name: &str, data: &[u8]) -> bool {
+        name.starts_with(".zdebug") && data.len() >= 12 && data.starts_with(ZLIB_MAGIC)
+    }
+
+    /// Decompresses contents of the given uncompressed size.
+    ///
+    /// Decompression stops one byte past that size, so bogus sizes are
+    /// reported without inflating everything.
+    pub(crate) fn decompress(ctype: Type, data: &[u8], size: u64) -> Result<Vec<u8>, Error> {
+        let out = inflate(ctype, data, size)?;
+        if out.len() as u64 != size {
+            return Err(Error::Corrupt(format!("{} bytes instead of {}", out.len(), size)));
+        }
+        Ok(out)
+    }
+
+    /// Decompresses at most `size + 1` bytes with the given algorithm.
+    fn inflate(ctype: Type, data: &[u8], size: u64) -> Result<Vec<u8>, Error> {
+        let capacity = size.min(data.len().saturating_mul(RESERVE_RATIO) as u64) as usize;
+        let limit = size.saturating_add(1);
+        match ctype {
+            #[cfg(feature = "zlib")]
+            Type::Zlib => {
+                let mut out = Vec::with_capacity(capacity);
+                flate2::read::ZlibDecoder::new(data)
+                    .take(limit)
+                    .read_to_end(&mut out)
+                    .map_err(|e| Error::Corrupt(e.to_string()))?;
+                Ok(out)
+            }
+            #[cfg(feature = "zstd")]
+            Type::Zstd => {
+                let mut out = Vec::with_capacity(capacity);
+                let mut data = data;
+                ruzstd::decoding::StreamingDecoder::new(&mut data)
+                    .map_err(|e| Error::Corrupt(e.to_string()))?
+                    .take(limit)
+                    .read_to_end(&mut out)
+                    .map_err(|e| Error::Corrupt(e.to_string()))?;
+                Ok(out)
+            }
+            _ => {
+                let _ = (data, capacity, limit);
+                Err(Error::Unsupported(ctype))
+            }
+        }
+    }
+
+    /// Compresses contents.
+    pub(crate) fn compress(ctype: Type, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match ctype {
+            #[cfg(feature = "zlib")]
+            Type::Zlib => {
+                let mut encoder = flate2::write::ZlibEncoder::new(vec![],
+                    flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                Ok(encoder.finish().unwrap())
+            }
+            #[cfg(feature = "zstd")]
+            Type::Zstd => {
+                Ok(ruzstd::encoding::compress_to_vec(data,
+                    ruzstd::encoding::CompressionLevel::Fastest))
+            }
+            _ => {
+                let _ = data;
+                Err(Error::Unsupported(ctype))
+            }
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Type {
+        /// Convert a compression type to string.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.as_str())
+        }
+    }
+
+    impl fmt::Display for Error {
+        /// Describe a compression error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Unsupported(t)   => write!(f, "unsupported compression {}", t.value()),
+                Error::Corrupt(why)     => write!(f, "corrupt compressed data: {}", why),
+                Error::State(ndx)       => write!(f, "section {} in the wrong compression state", ndx),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    /// Check every debugging section of the `inline` sample reads the
+    /// same from a compressed copy of it, then decompresses back to its
+    /// name and contents.
+    #[cfg(any(feature = "zlib", feature = "zstd"))]
+    fn check_decompress(name: &str, prefix: &str) {
+        let orig = Object::from_bytes(&sample("inline"));
+        let mut obj = Object::from_bytes(&sample(name));
+        let mut compressed = 0;
+        for section in orig.sections().iter().filter(|s| s.is_debug()) {
+            let debug = section.name.as_deref().unwrap();
+            let zdebug = debug.replacen(".debug", prefix, 1);
+            let ndx = obj.section_index(&zdebug).or_else(|| obj.section_index(debug)).unwrap();
+            compressed += obj.is_section_compressed(ndx) as usize;
+            assert_eq!(obj.section_data(ndx).unwrap(), &section.data[..]);
+        }
+        assert!(compressed > 0);
+
+        obj.decompress_debug_sections().unwrap();
+        let obj = Object::from_bytes(&crate::tests::write(&obj));
+        for section in orig.sections().iter().filter(|s| s.is_debug()) {
+            let ndx = obj.section_index(section.name.as_deref().unwrap()).unwrap();
+            assert!(!obj.is_section_compressed(ndx));
+            assert!(obj.sections()[ndx].data == section.data);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_sections() {
+        check_decompress("inline-zstd", ".debug");
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn legacy_zdebug_sections() {
+        check_decompress("inline-zdebug", ".zdebug");
+    }
+
+    #[test]
+    fn loaded_sections_stay() {
+        let mut obj = Object::from_bytes(&sample("main"));
+        let text = obj.section_index(".text").unwrap();
+        assert!(matches!(obj.compress_section(text, Type::Zlib), Err(Error::State(i)) if i == text));
+        assert!(!obj.is_section_compressed(text));
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn bogus_size() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        let ndx = obj.sections.iter().position(|s| s.name.as_deref() == Some(".comment")).unwrap();
+        let data = obj.sections[ndx].data.clone();
+        obj.compress_section(ndx, Type::Zlib).unwrap();
+        assert_eq!(obj.section_data(ndx).unwrap(), &data[..]);
+
+        for size in [0, data.len() as u64 - 1, data.len() as u64 + 1, u64::MAX] {
+            obj.sections[ndx].data[8..16].copy_from_slice(&size.to_le_bytes());
+            assert!(matches!(obj.section_data(ndx), Err(Error::Corrupt(_))));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Regarding locating the separate debug file of an object, the way
+//! debuggers do.
+//!
+//! Candidates are tried in order:
+//! - `<debug dir>/.build-id/xx/yyyy.debug`, from the build ID, with
+//!   `xx` its first byte in hexadecimal and `yyyy` the rest.
+//! - The `.gnu_debuglink` file name, next to the object, in a `.debug`
+//!   directory next to it, then under each debug directory followed by
+//!   the object directory. Its CRC-32 must match.
+//! - `<cache dir>/<build ID>/debuginfo`, the layout of `debuginfod`
+//!   client caches.
+//!
+//! Candidates found through the build ID must have the same one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::object::Object;
+use crate::util;
+
+/// Default debug directory.
+pub const DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// Finds separate debug files.
+pub struct DebugResolver {
+    /// Debug directories, searched in order.
+    debug_dirs: Vec<PathBuf>,
+    /// `debuginfod` client cache directory, if any.
+    cache_dir:  Option<PathBuf>,
+}
+
+/// Simple resolver methods.
+impl DebugResolver {
+    /// Creates a resolver searching the default debug directory and the
+    /// default `debuginfod` cache.
+    ///
+    /// The cache is `$DEBUGINFOD_CACHE_PATH`, or `debuginfod_client`
+    /// under `$XDG_CACHE_HOME` or `~/.cache`.
+    pub fn new() -> Self {
+        let env = |name| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+        let cache_dir = env("DEBUGINFOD_CACHE_PATH")
+            .or_else(|| env("XDG_CACHE_HOME").map(|d| d.join("debuginfod_client")))
+            .or_else(|| env("HOME").map(|d| d.join(".cache/debuginfod_client")));
+        Self {
+            debug_dirs: vec![PathBuf::from(DEBUG_DIR)],
+            cache_dir,
+        }
+    }
+
+    /// Creates a resolver searching nowhere yet.
+    pub fn empty() -> Self {
+        Self {
+            debug_dirs: vec![],
+            cache_dir:  None,
+        }
+    }
+
+    /// Adds a debug directory, searched after the previous ones.
+    pub fn add_debug_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.debug_dirs.push(dir.as_ref().to_path_buf());
+    }
+
+    /// Sets the `debuginfod` client cache directory.
+    pub fn cache_dir<P: AsRef<Path>>(&mut self, dir: Option<P>) {
+        self.cache_dir = dir.map(|d| d.as_ref().to_path_buf());
+    }
+
+    /// Get the path of the debug file of an object, if found.
+    ///
+    /// The path of the object itself is needed to follow its
+    /// `.gnu_debuglink`.
+    pub fn find(&self, obj: &Object, path: Option<&Path>) -> Option<PathBuf> {
+        self.find_by_build_id(obj)
+            .or_else(|| self.find_by_debuglink(obj, path?))
+            .or_else(|| self.find_in_cache(obj))
+    }
+
+    /// Get the debug file of an object, if found.
+    pub fn resolve(&self, obj: &Object, path: Option<&Path>) -> Option<Object> {
+        let found = self.find(obj, path)?;
+        read_object(&found)
+    }
+}
+
+impl Default for DebugResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lookup methods.
+mod lookup {
+    use super::*;
+
+    impl DebugResolver {
+        /// Looks into `.build-id` under the debug directories.
+        pub(crate) fn find_by_build_id(&self, obj: &Object) -> Option<PathBuf> {
+            let id = obj.build_id().filter(|id| id.len() >= 2)?;
+            let hex = to_hex(&id);
+            let name = format!(".build-id/{}/{}.debug", &hex[..2], &hex[2..]);
+            self.debug_dirs.iter()
+                .map(|dir| dir.join(&name))
+                .find(|path| has_build_id(path, &id))
+        }
+
+        /// Looks for the `.gnu_debuglink` file, checking its CRC-32.
+        pub(crate) fn find_by_debuglink(&self, obj: &Object, path: &Path) -> Option<PathBuf> {
+            let (name, crc) = obj.debuglink()?;
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            let dir = path.parent()?;
+
+            let mut candidates = vec![dir.join(&name), dir.join(".debug").join(&name)];
+            let relative = dir.strip_prefix("/").unwrap_or(dir);
+            for debug_dir in &self.debug_dirs {
+                candidates.push(debug_dir.join(relative).join(&name));
+            }
+            candidates.into_iter()
+                .filter(|c| *c != path)
+                .find(|c| fs::read(c).is_ok_and(|data| util::crc32(&data) == crc))
+        }
+
+        /// Looks into the `debuginfod` client cache.
+        pub(crate) fn find_in_cache(&self, obj: &Object) -> Option<PathBuf> {
+            let id = obj.build_id()?;
+            let path = self.cache_dir.as_ref()?.join(to_hex(&id)).join("debuginfo");
+            Some(path).filter(|p| has_build_id(p, &id))
+        }
+    }
+
+    /// Check if a file is an object with the given build ID.
+    fn has_build_id(path: &Path, id: &[u8]) -> bool {
+        read_object(path).and_then(|o| o.build_id()).is_some_and(|i| i == id)
+    }
+
+    /// Get bytes as lower case hexadecimal.
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Reads an object from a file, unless it is not a readable ELF file.
+pub(crate) fn read_object(path: &Path) -> Option<Object> {
+    let data = fs::read(path).ok()?;
+    if !Object::is_readable(&data) {
+        return None;
+    }
+    Some(Object::from_bytes(&data))
+}
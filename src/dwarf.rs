@@ -0,0 +1,442 @@
+//! Regarding DWARF debugging information, the parts shared by its
+//! sections.
+//!
+//! The sections are gathered into a `Dwarf`, decompressed if needed.
+//! In relocatable objects, their relocations are applied first, so
+//! string offsets are right and addresses are relative to the section
+//! they refer to.
+//!
+//! Every section is a sequence of units starting with their length,
+//! which also tells whether offsets in the unit are 32 or 64-bits. The
+//! values of attributes are encoded in one of many forms, read into a
+//! `Value`.
+
+use std::borrow::Cow;
+
+use crate::object::Object;
+use crate::rel::x86_64::*;
+use crate::util;
+
+pub const DW_FORM_ADDR: u64 = 0x01;
+pub const DW_FORM_BLOCK2: u64 = 0x03;
+pub const DW_FORM_BLOCK4: u64 = 0x04;
+pub const DW_FORM_DATA2: u64 = 0x05;
+pub const DW_FORM_DATA4: u64 = 0x06;
+pub const DW_FORM_DATA8: u64 = 0x07;
+pub const DW_FORM_STRING: u64 = 0x08;
+pub const DW_FORM_BLOCK: u64 = 0x09;
+pub const DW_FORM_BLOCK1: u64 = 0x0a;
+pub const DW_FORM_DATA1: u64 = 0x0b;
+pub const DW_FORM_FLAG: u64 = 0x0c;
+pub const DW_FORM_SDATA: u64 = 0x0d;
+pub const DW_FORM_STRP: u64 = 0x0e;
+pub const DW_FORM_UDATA: u64 = 0x0f;
+pub const DW_FORM_REF_ADDR: u64 = 0x10;
+pub const DW_FORM_REF1: u64 = 0x11;
+pub const DW_FORM_REF2: u64 = 0x12;
+pub const DW_FORM_REF4: u64 = 0x13;
+pub const DW_FORM_REF8: u64 = 0x14;
+pub const DW_FORM_REF_UDATA: u64 = 0x15;
+pub const DW_FORM_INDIRECT: u64 = 0x16;
+pub const DW_FORM_SEC_OFFSET: u64 = 0x17;
+pub const DW_FORM_EXPRLOC: u64 = 0x18;
+pub const DW_FORM_FLAG_PRESENT: u64 = 0x19;
+pub const DW_FORM_STRX: u64 = 0x1a;
+pub const DW_FORM_ADDRX: u64 = 0x1b;
+pub const DW_FORM_REF_SUP4: u64 = 0x1c;
+pub const DW_FORM_STRP_SUP: u64 = 0x1d;
+pub const DW_FORM_DATA16: u64 = 0x1e;
+pub const DW_FORM_LINE_STRP: u64 = 0x1f;
+pub const DW_FORM_REF_SIG8: u64 = 0x20;
+pub const DW_FORM_IMPLICIT_CONST: u64 = 0x21;
+pub const DW_FORM_LOCLISTX: u64 = 0x22;
+pub const DW_FORM_RNGLISTX: u64 = 0x23;
+pub const DW_FORM_REF_SUP8: u64 = 0x24;
+pub const DW_FORM_STRX1: u64 = 0x25;
+pub const DW_FORM_STRX2: u64 = 0x26;
+pub const DW_FORM_STRX3: u64 = 0x27;
+pub const DW_FORM_STRX4: u64 = 0x28;
+pub const DW_FORM_ADDRX1: u64 = 0x29;
+pub const DW_FORM_ADDRX2: u64 = 0x2a;
+pub const DW_FORM_ADDRX3: u64 = 0x2b;
+pub const DW_FORM_ADDRX4: u64 = 0x2c;
+pub const DW_FORM_GNU_ADDR_INDEX: u64 = 0x1f01;
+pub const DW_FORM_GNU_STR_INDEX: u64 = 0x1f02;
+pub const DW_FORM_GNU_REF_ALT: u64 = 0x1f20;
+pub const DW_FORM_GNU_STRP_ALT: u64 = 0x1f21;
+
+/// Reads values out of a DWARF section.
+///
+/// Reading past the end gets `None`, like other malformed input.
+#[derive(Clone)]
+pub struct Reader<'a> {
+    /// Section contents.
+    data:   &'a [u8],
+    /// Current offset.
+    pub pos: usize,
+}
+
+/// How the values of a unit are encoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding {
+    /// DWARF version of the unit.
+    pub version:        u16,
+    /// Wether offsets are 64-bits.
+    pub is64:           bool,
+    /// Size in bytes of addresses.
+    pub address_size:   u8,
+}
+
+/// The value of an attribute, as encoded by its form.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// An address.
+    Addr(u64),
+    /// An index into the unit addresses (`.debug_addr`).
+    AddrIndex(u64),
+    /// Unsigned constant.
+    Data(u64),
+    /// Signed constant.
+    Sdata(i64),
+    /// Uninterpreted bytes.
+    Block(Vec<u8>),
+    /// A DWARF expression.
+    Exprloc(Vec<u8>),
+    /// A flag.
+    Flag(bool),
+    /// An offset into the same unit.
+    Ref(u64),
+    /// An offset into `.debug_info`.
+    RefAddr(u64),
+    /// The signature of a type unit.
+    RefSig8(u64),
+    /// An offset into another section.
+    SecOffset(u64),
+    /// An inline string.
+    Str(String),
+    /// An offset into `.debug_str`.
+    Strp(u64),
+    /// An offset into `.debug_line_str`.
+    LineStrp(u64),
+    /// An index into the unit string offsets (`.debug_str_offsets`).
+    StrIndex(u64),
+    /// An index into the unit location lists.
+    LoclistIndex(u64),
+    /// An index into the unit range lists.
+    RnglistIndex(u64),
+    /// A reference into a supplementary file, not followed.
+    Sup(u64),
+}
+
+/// Simple reader methods.
+impl<'a> Reader<'a> {
+    /// Creates a reader at the start of the given contents.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Creates a reader at the given offset of the contents.
+    pub fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    /// Check if everything was read.
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Get the contents being read.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Read bytes.
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Read one byte.
+    pub fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    /// Read two bytes.
+    pub fn u16(&mut self) -> Option<u16> {
+        Some(util::get_u16(self.bytes(2)?, 0))
+    }
+
+    /// Read four bytes.
+    pub fn u32(&mut self) -> Option<u32> {
+        Some(util::get_u32(self.bytes(4)?, 0))
+    }
+
+    /// Read eight bytes.
+    pub fn u64(&mut self) -> Option<u64> {
+        Some(util::get_u64(self.bytes(8)?, 0))
+    }
+
+    /// Read an unsigned value of the given size in bytes, at most eight.
+    pub fn sized(&mut self, size: usize) -> Option<u64> {
+        if size > 8 {
+            return None;
+        }
+        Some(self.bytes(size)?.iter().rev().fold(0, |v, &b| v << 8 | b as u64))
+    }
+
+    /// Read an unsigned LEB128 value.
+    pub fn uleb(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                value |= ((b & 0x7f) as u64) << shift;
+                shift += 7;
+            }
+            if b & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Read a signed LEB128 value.
+    pub fn sleb(&mut self) -> Option<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                value |= ((b & 0x7f) as i64) << shift;
+                shift += 7;
+            }
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    value |= -1i64 << shift;
+                }
+                return Some(value);
+            }
+        }
+    }
+
+    /// Read a null-terminated string.
+    pub fn cstr(&mut self) -> Option<String> {
+        let rest = self.data.get(self.pos..)?;
+        let len = rest.iter().position(|&c| c == 0)?;
+        self.pos += len + 1;
+        Some(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    /// Read a unit length, and get it along with wether the unit uses
+    /// 64-bits offsets.
+    pub fn initial_length(&mut self) -> Option<(u64, bool)> {
+        match self.u32()? {
+            0xffff_ffff => Some((self.u64()?, true)),
+            len         => Some((len as u64, false)),
+        }
+    }
+
+    /// Read a unit length, and get the end of the unit, clamped to the
+    /// contents, along with wether the unit uses 64-bits offsets.
+    pub fn unit_end(&mut self) -> Option<(usize, bool)> {
+        let (len, is64) = self.initial_length()?;
+        let size = self.data.len() as u64;
+        let end = (self.pos as u64).checked_add(len).map_or(size, |end| end.min(size));
+        Some((end as usize, is64))
+    }
+
+    /// Read a section offset.
+    pub fn offset(&mut self, is64: bool) -> Option<u64> {
+        if is64 { self.u64() } else { Some(self.u32()? as u64) }
+    }
+
+    /// Read an address of the given size in bytes.
+    pub fn address(&mut self, size: u8) -> Option<u64> {
+        self.sized(size as usize)
+    }
+
+    /// Read a value of the given form.
+    ///
+    /// `DW_FORM_implicit_const` values come from the abbreviation, and
+    /// are given here. Unknown forms, like vendor ones, get `None`, as
+    /// their size is unknown too, and so do truncated values.
+    pub fn value(&mut self, form: u64, enc: &Encoding, implicit: i64) -> Option<Value> {
+        let value = match form {
+            DW_FORM_ADDR            => Value::Addr(self.address(enc.address_size)?),
+            DW_FORM_BLOCK1          => { let n = self.u8()? as usize; Value::Block(self.bytes(n)?.to_vec()) }
+            DW_FORM_BLOCK2          => { let n = self.u16()? as usize; Value::Block(self.bytes(n)?.to_vec()) }
+            DW_FORM_BLOCK4          => { let n = self.u32()? as usize; Value::Block(self.bytes(n)?.to_vec()) }
+            DW_FORM_BLOCK           => { let n = self.uleb()? as usize; Value::Block(self.bytes(n)?.to_vec()) }
+            DW_FORM_DATA16          => Value::Block(self.bytes(16)?.to_vec()),
+            DW_FORM_EXPRLOC         => { let n = self.uleb()? as usize; Value::Exprloc(self.bytes(n)?.to_vec()) }
+            DW_FORM_DATA1           => Value::Data(self.u8()? as u64),
+            DW_FORM_DATA2           => Value::Data(self.u16()? as u64),
+            DW_FORM_DATA4           => Value::Data(self.u32()? as u64),
+            DW_FORM_DATA8           => Value::Data(self.u64()?),
+            DW_FORM_UDATA           => Value::Data(self.uleb()?),
+            DW_FORM_SDATA           => Value::Sdata(self.sleb()?),
+            DW_FORM_IMPLICIT_CONST  => Value::Sdata(implicit),
+            DW_FORM_FLAG            => Value::Flag(self.u8()? != 0),
+            DW_FORM_FLAG_PRESENT    => Value::Flag(true),
+            DW_FORM_REF1            => Value::Ref(self.u8()? as u64),
+            DW_FORM_REF2            => Value::Ref(self.u16()? as u64),
+            DW_FORM_REF4            => Value::Ref(self.u32()? as u64),
+            DW_FORM_REF8            => Value::Ref(self.u64()?),
+            DW_FORM_REF_UDATA       => Value::Ref(self.uleb()?),
+            DW_FORM_REF_ADDR if enc.version == 2 => Value::RefAddr(self.address(enc.address_size)?),
+            DW_FORM_REF_ADDR        => Value::RefAddr(self.offset(enc.is64)?),
+            DW_FORM_REF_SIG8        => Value::RefSig8(self.u64()?),
+            DW_FORM_SEC_OFFSET      => Value::SecOffset(self.offset(enc.is64)?),
+            DW_FORM_STRING          => Value::Str(self.cstr()?),
+            DW_FORM_STRP            => Value::Strp(self.offset(enc.is64)?),
+            DW_FORM_LINE_STRP       => Value::LineStrp(self.offset(enc.is64)?),
+            DW_FORM_STRX | DW_FORM_GNU_STR_INDEX => Value::StrIndex(self.uleb()?),
+            DW_FORM_STRX1           => Value::StrIndex(self.sized(1)?),
+            DW_FORM_STRX2           => Value::StrIndex(self.sized(2)?),
+            DW_FORM_STRX3           => Value::StrIndex(self.sized(3)?),
+            DW_FORM_STRX4           => Value::StrIndex(self.sized(4)?),
+            DW_FORM_ADDRX | DW_FORM_GNU_ADDR_INDEX => Value::AddrIndex(self.uleb()?),
+            DW_FORM_ADDRX1          => Value::AddrIndex(self.sized(1)?),
+            DW_FORM_ADDRX2          => Value::AddrIndex(self.sized(2)?),
+            DW_FORM_ADDRX3          => Value::AddrIndex(self.sized(3)?),
+            DW_FORM_ADDRX4          => Value::AddrIndex(self.sized(4)?),
+            DW_FORM_LOCLISTX        => Value::LoclistIndex(self.uleb()?),
+            DW_FORM_RNGLISTX        => Value::RnglistIndex(self.uleb()?),
+            DW_FORM_REF_SUP4        => Value::Sup(self.u32()? as u64),
+            DW_FORM_REF_SUP8        => Value::Sup(self.u64()?),
+            DW_FORM_STRP_SUP | DW_FORM_GNU_REF_ALT | DW_FORM_GNU_STRP_ALT => {
+                Value::Sup(self.offset(enc.is64)?)
+            }
+            DW_FORM_INDIRECT        => {
+                let form = self.uleb()?;
+                return self.value(form, enc, implicit);
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// Simple value methods.
+impl Value {
+    /// Get the value as an unsigned constant, if it is one.
+    pub fn udata(&self) -> Option<u64> {
+        match self {
+            Value::Data(v) | Value::SecOffset(v) => Some(*v),
+            Value::Sdata(v) => Some(*v as u64),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a signed constant, if it is one.
+    pub fn sdata(&self) -> Option<i64> {
+        match self {
+            Value::Sdata(v) => Some(*v),
+            Value::Data(v)  => Some(*v as i64),
+            _ => None,
+        }
+    }
+}
+
+/// The DWARF sections of an object.
+///
+/// Missing sections are empty.
+pub struct Dwarf<'a> {
+    /// `.debug_info`.
+    pub info:           Cow<'a, [u8]>,
+    /// `.debug_abbrev`.
+    pub abbrev:         Cow<'a, [u8]>,
+    /// `.debug_str`.
+    pub str:            Cow<'a, [u8]>,
+    /// `.debug_line_str`.
+    pub line_str:       Cow<'a, [u8]>,
+    /// `.debug_line`.
+    pub line:           Cow<'a, [u8]>,
+    /// `.debug_str_offsets`.
+    pub str_offsets:    Cow<'a, [u8]>,
+    /// `.debug_addr`.
+    pub addr:           Cow<'a, [u8]>,
+    /// `.debug_rnglists`.
+    pub rnglists:       Cow<'a, [u8]>,
+    /// `.debug_ranges`.
+    pub ranges:         Cow<'a, [u8]>,
+}
+
+/// Simple dwarf methods.
+impl<'a> Dwarf<'a> {
+    /// Gathers the DWARF sections of an object.
+    pub fn new(obj: &'a Object) -> Self {
+        let section = |name| obj.dwarf_section(name).unwrap_or(Cow::Borrowed(&[]));
+        Self {
+            info:        section(".debug_info"),
+            abbrev:      section(".debug_abbrev"),
+            str:         section(".debug_str"),
+            line_str:    section(".debug_line_str"),
+            line:        section(".debug_line"),
+            str_offsets: section(".debug_str_offsets"),
+            addr:        section(".debug_addr"),
+            rnglists:    section(".debug_rnglists"),
+            ranges:      section(".debug_ranges"),
+        }
+    }
+
+    /// Get a string from `.debug_str`.
+    pub fn string(&self, off: u64) -> String {
+        util::get_str(&self.str, off as usize)
+    }
+
+    /// Get a string from `.debug_line_str`.
+    pub fn line_string(&self, off: u64) -> String {
+        util::get_str(&self.line_str, off as usize)
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Get the contents of a DWARF section, like `.debug_info`.
+        ///
+        /// Compressed sections are decompressed, `.zdebug_*` ones
+        /// included, and relocations of relocatable objects applied.
+        pub fn dwarf_section(&self, name: &str) -> Option<Cow<'_, [u8]>> {
+            let ndx = self.section_index(name)
+                .or_else(|| self.section_index(&format!(".z{}", name.trim_start_matches('.'))))?;
+            let data = self.section_data(ndx).ok()?;
+            if !self.header.is_rel() {
+                return Some(data);
+            }
+
+            /* relocations of relocatable objects */
+            let relas: Vec<usize> = (0..self.sections.len())
+                .filter(|&i| self.sections[i].is_rela() && self.sections[i].info as usize == ndx)
+                .collect();
+            if relas.is_empty() {
+                return Some(data);
+            }
+            let mut data = data.into_owned();
+            for rela in relas.into_iter().flat_map(|i| self.relocations(i)) {
+                let sym = self.symbols.get(rela.sym).map_or(0, |s| s.value);
+                let value = sym.wrapping_add(rela.addend as u64);
+                let at = rela.offset as usize;
+                let bytes = match rela.rtype {
+                    R_X86_64_64                 => value.to_le_bytes().to_vec(),
+                    R_X86_64_32 | R_X86_64_32S  => (value as u32).to_le_bytes().to_vec(),
+                    _ => continue,
+                };
+                /* relocations out of the section are left out */
+                if let Some(target) = data.get_mut(at..at.saturating_add(bytes.len())) {
+                    target.copy_from_slice(&bytes);
+                }
+            }
+            Some(Cow::Owned(data))
+        }
+
+        /// Gathers the DWARF sections.
+        pub fn dwarf(&self) -> Dwarf<'_> {
+            Dwarf::new(self)
+        }
+    }
+}
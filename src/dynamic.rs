@@ -0,0 +1,182 @@
+//! Regarding the dynamic section entries (`.dynamic`).
+//!
+//! Each entry is a tag with a value, which is either an integer, an
+//! address, or an offset into the dynamic string table. The table ends
+//! with a `DT_NULL` entry, possibly followed by spare ones.
+
+pub const DT_NULL: i64 = 0;
+pub const DT_NEEDED: i64 = 1;
+pub const DT_PLTRELSZ: i64 = 2;
+pub const DT_PLTGOT: i64 = 3;
+pub const DT_HASH: i64 = 4;
+pub const DT_STRTAB: i64 = 5;
+pub const DT_SYMTAB: i64 = 6;
+pub const DT_RELA: i64 = 7;
+pub const DT_RELASZ: i64 = 8;
+pub const DT_RELAENT: i64 = 9;
+pub const DT_STRSZ: i64 = 10;
+pub const DT_SYMENT: i64 = 11;
+pub const DT_INIT: i64 = 12;
+pub const DT_FINI: i64 = 13;
+pub const DT_SONAME: i64 = 14;
+pub const DT_RPATH: i64 = 15;
+pub const DT_SYMBOLIC: i64 = 16;
+pub const DT_DEBUG: i64 = 21;
+pub const DT_TEXTREL: i64 = 22;
+pub const DT_JMPREL: i64 = 23;
+pub const DT_BIND_NOW: i64 = 24;
+pub const DT_RUNPATH: i64 = 29;
+pub const DT_FLAGS: i64 = 30;
+pub const DT_GNU_HASH: i64 = 0x6fff_fef5;
+pub const DT_VERSYM: i64 = 0x6fff_fff0;
+pub const DT_FLAGS_1: i64 = 0x6fff_fffb;
+pub const DT_VERDEF: i64 = 0x6fff_fffc;
+pub const DT_VERNEED: i64 = 0x6fff_fffe;
+
+/// Size in bytes of a dynamic entry.
+pub const DYN_SIZE: usize = 16;
+
+/// Represents a dynamic section entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dyn {
+    /// Entry tag.
+    pub tag:    i64,        // 64-bits
+    /// Integer, address or string offset.
+    pub val:    u64,        // 64-bits
+}
+
+/// Simple dyn methods.
+impl Dyn {
+    /// Creates a dynamic entry.
+    pub fn new(tag: i64, val: u64) -> Self {
+        Self { tag, val }
+    }
+
+    /// Check if the value is an offset into the dynamic string table.
+    pub fn is_string(&self) -> bool {
+        matches!(self.tag, DT_NEEDED | DT_SONAME | DT_RPATH | DT_RUNPATH)
+    }
+
+    /// Get string slice for the tag.
+    pub fn tag_str(&self) -> &'static str {
+        match self.tag {
+            DT_NULL     => "NULL",
+            DT_NEEDED   => "NEEDED",
+            DT_PLTRELSZ => "PLTRELSZ",
+            DT_PLTGOT   => "PLTGOT",
+            DT_HASH     => "HASH",
+            DT_STRTAB   => "STRTAB",
+            DT_SYMTAB   => "SYMTAB",
+            DT_RELA     => "RELA",
+            DT_RELASZ   => "RELASZ",
+            DT_RELAENT  => "RELAENT",
+            DT_STRSZ    => "STRSZ",
+            DT_SYMENT   => "SYMENT",
+            DT_INIT     => "INIT",
+            DT_FINI     => "FINI",
+            DT_SONAME   => "SONAME",
+            DT_RPATH    => "RPATH",
+            DT_SYMBOLIC => "SYMBOLIC",
+            DT_DEBUG    => "DEBUG",
+            DT_TEXTREL  => "TEXTREL",
+            DT_JMPREL   => "JMPREL",
+            DT_BIND_NOW => "BIND_NOW",
+            DT_RUNPATH  => "RUNPATH",
+            DT_FLAGS    => "FLAGS",
+            DT_GNU_HASH => "GNU_HASH",
+            DT_VERSYM   => "VERSYM",
+            DT_FLAGS_1  => "FLAGS_1",
+            DT_VERDEF   => "VERDEF",
+            DT_VERNEED  => "VERNEED",
+            _           => "Unknown",
+        }
+    }
+}
+
+/// Buffer IO methods.
+pub mod io {
+    use super::*;
+    use std::io::{self, Write};
+    use crate::util;
+
+    impl Dyn {
+        /// Extract every entry from the contents of a dynamic section,
+        /// spare ones after the first `DT_NULL` included.
+        pub fn extract_all(data: &[u8]) -> Vec<Self> {
+            data.chunks_exact(DYN_SIZE)
+                .map(|e| Self {
+                    tag: util::get_u64(e, 0) as i64,
+                    val: util::get_u64(e, 8),
+                })
+                .collect()
+        }
+
+        /// Write a dynamic entry into a file **at current offset**.
+        pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            util::write_u64(file, self.tag as u64)?;
+            util::write_u64(file, self.val)
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use crate::object::Object;
+
+    impl Object {
+        /// Get the index of the dynamic section, if any.
+        pub fn dynamic_index(&self) -> Option<usize> {
+            self.sections.iter().position(|s| s.is_dynamic())
+        }
+
+        /// Get the dynamic entries, up to and excluding the first
+        /// `DT_NULL`.
+        pub fn dynamic(&self) -> Vec<Dyn> {
+            let ndx = match self.dynamic_index() {
+                Some(ndx) => ndx,
+                None      => return vec![],
+            };
+            Dyn::extract_all(&self.sections[ndx].data)
+                .into_iter()
+                .take_while(|d| d.tag != DT_NULL)
+                .collect()
+        }
+
+        /// Get the value of the first dynamic entry with the given tag.
+        pub fn dynamic_value(&self, tag: i64) -> Option<u64> {
+            self.dynamic().iter().find(|d| d.tag == tag).map(|d| d.val)
+        }
+
+        /// Get the string a dynamic entry refers to, from the dynamic
+        /// string table, if there is one.
+        pub fn dynamic_str(&self, entry: &Dyn) -> Option<String> {
+            let ndx = self.dynamic_index()?;
+            let strtab = self.sections.get(self.sections[ndx].link)?;
+            Some(crate::util::get_str(&strtab.data, entry.val as usize))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Object;
+    use crate::tests::sample;
+
+    #[test]
+    fn sample_entries() {
+        let obj = Object::from_bytes(&sample("main"));
+        let entries = obj.dynamic();
+        assert_eq!(entries.len(), 21);
+        assert!(entries.iter().all(|d| d.tag != DT_NULL));
+        assert_eq!(obj.dynamic_str(&entries[0]).as_deref(), Some("libc.so.6"));
+        assert_eq!(obj.dynamic_value(DT_RELAENT), Some(24));
+
+        let mut data = vec![];
+        for entry in &entries {
+            entry.write(&mut data).unwrap();
+        }
+        assert_eq!(Dyn::extract_all(&data), entries);
+    }
+}
@@ -0,0 +1,468 @@
+//! Regarding running x86-64 executables in the current process, without
+//! `execve`.
+//!
+//! Only executables that need no interpreter are handled: static ones
+//! and static PIE ones. Running one is done in two steps:
+//! - `Program::map` follows the load plan, mapping every region with
+//!   its protections, `.bss` included, applies the `R_X86_64_RELATIVE`
+//!   relocations of PIE ones, found through `DT_RELA` so section
+//!   headers are not needed, and reserves a stack.
+//! - `Program::run` fills the stack with the arguments, environment and
+//!   auxiliary vector the kernel would provide, and jumps to the entry
+//!   point. It never returns: the program exits the whole process.
+//!
+//! `R_X86_64_IRELATIVE` relocations are left to the program itself, as
+//! the C library startup code already applies them.
+
+use std::arch::asm;
+use std::fmt;
+use std::io::{self, Cursor, Read};
+
+use crate::dynamic::{Dyn, DT_RELA, DT_RELASZ};
+use crate::header::EM_X86_64;
+use crate::object::Object;
+use crate::plan::{self, Region};
+use crate::rel::Rela;
+use crate::rel::x86_64::*;
+use crate::segment::Type as SegmentType;
+use crate::sys::*;
+use crate::util;
+
+/// Page size used for mappings.
+const PAGE: u64 = 0x1000;
+
+/// Size of the stack given to the program.
+const STACK_SIZE: usize = 8 << 20;
+
+/// Size in bytes of a program header.
+const PHDR_SIZE: u64 = 56;
+
+/// Auxiliary vector entry types.
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_FLAGS: u64 = 8;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_PLATFORM: u64 = 15;
+const AT_HWCAP: u64 = 16;
+const AT_CLKTCK: u64 = 17;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+const AT_HWCAP2: u64 = 26;
+const AT_EXECFN: u64 = 31;
+const AT_SYSINFO_EHDR: u64 = 33;
+
+/// Errors found while mapping.
+#[derive(Debug)]
+pub enum Error {
+    /// The file is not an x86-64 executable with loadable segments.
+    NotExecutable,
+    /// The executable needs an interpreter (dynamic loader).
+    Interpreter,
+    /// The load plan is invalid.
+    Plan(plan::Error),
+    /// A relocation type is not supported.
+    Unsupported(u32),
+    /// A segment, or a relocation, lies outside of the file or mapping.
+    Truncated,
+    /// Mapping or protecting memory failed.
+    Map(io::Error),
+}
+
+/// An executable mapped into the current process, ready to run.
+pub struct Program {
+    /// Start of the segments mapping.
+    base:       *mut u8,
+    /// Size of the segments mapping.
+    len:        usize,
+    /// Start of the stack mapping.
+    stack:      *mut u8,
+    /// Difference between run time and link time addresses.
+    bias:       u64,
+    /// Run time address of the entry point.
+    entry:      u64,
+    /// Run time address of the program headers, if mapped.
+    phdr:       Option<u64>,
+    /// Raw program headers.
+    phdrs:      Vec<u8>,
+}
+
+/// Simple program methods.
+impl Program {
+    /// Get the difference between run time and link time addresses,
+    /// zero for fixed address executables.
+    pub fn bias(&self) -> u64 {
+        self.bias
+    }
+
+    /// Get the run time address of the entry point.
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.base, self.len);
+            munmap(self.stack, STACK_SIZE);
+        }
+    }
+}
+
+/// Map an anonymous region, at `addr` unless null.
+fn map(addr: u64, len: usize, prot: i32) -> Result<*mut u8, Error> {
+    let mut flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    if addr != 0 {
+        flags |= MAP_FIXED_NOREPLACE;
+    }
+    let ptr = unsafe { mmap(addr as *mut u8, len, prot, flags, -1, 0) };
+    if ptr == MAP_FAILED {
+        return Err(Error::Map(io::Error::last_os_error()));
+    }
+    /* older kernels take the address as a mere hint */
+    if addr != 0 && ptr as u64 != addr {
+        unsafe { munmap(ptr, len) };
+        return Err(Error::Map(io::Error::from(io::ErrorKind::AddrInUse)));
+    }
+    Ok(ptr)
+}
+
+/// Get the byte range of `size` bytes at `off`, if it does not overflow.
+fn range(off: u64, size: u64) -> Option<std::ops::Range<usize>> {
+    let end = off.checked_add(size)?;
+    Some(off as usize..end as usize)
+}
+
+/// Get the memory protections of a region.
+fn prot(region: &Region) -> i32 {
+    let mut prot = PROT_NONE;
+    if region.is_readable() {
+        prot |= PROT_READ;
+    }
+    if region.is_writable() {
+        prot |= PROT_WRITE;
+    }
+    if region.is_executable() {
+        prot |= PROT_EXEC;
+    }
+    prot
+}
+
+/// Mapping methods.
+mod mapping {
+    use super::*;
+
+    impl Program {
+        /// Maps an executable into the current process.
+        ///
+        /// Fixed address executables must not overlap anything already
+        /// mapped, PIE ones are placed wherever the kernel sees fit.
+        pub fn map(obj: &Object) -> Result<Self, Error> {
+            let header = obj.header();
+            if header.machine() != EM_X86_64 || !(header.is_exec() || header.is_dyn()) {
+                return Err(Error::NotExecutable);
+            }
+            let segments = obj.segments();
+            if segments.iter().any(|s| matches!(s.etype, SegmentType::Interp)) {
+                return Err(Error::Interpreter);
+            }
+            let loads = obj.load_plan().map_err(Error::Plan)?;
+            if loads.is_empty() {
+                return Err(Error::NotExecutable);
+            }
+
+            /* reserve the whole span at once, writable until relocated */
+            let start = loads[0].vaddr / PAGE * PAGE;
+            let end = loads.iter().map(|r| util::align(r.vaddr + r.memsz(), PAGE)).max().unwrap();
+            let len = (end - start) as usize;
+            let hint = if header.is_exec() { start } else { 0 };
+            let base = map(hint, len, PROT_READ | PROT_WRITE)?;
+            let bias = (base as u64).wrapping_sub(start);
+            let stack = match map(0, STACK_SIZE, PROT_READ | PROT_WRITE) {
+                Ok(stack) => stack,
+                Err(err) => {
+                    unsafe { munmap(base, len) };
+                    return Err(err);
+                }
+            };
+
+            let mut phdrs = vec![];
+            for seg in segments {
+                seg.write(&mut phdrs).unwrap();
+            }
+            let program = Self {
+                base,
+                len,
+                stack,
+                bias,
+                entry:  header.entry.wrapping_add(bias),
+                phdr:   None,
+                phdrs,
+            };
+            program.load(obj, &loads, start)
+        }
+
+        /// Copies the segments in, relocates them, and gives them their
+        /// final protections.
+        fn load(mut self, obj: &Object, loads: &[Region], start: u64) -> Result<Self, Error> {
+            let mem = unsafe { std::slice::from_raw_parts_mut(self.base, self.len) };
+
+            /* the file contents, as written back */
+            let mut file = Cursor::new(vec![]);
+            obj.write_to(&mut file).map_err(Error::Map)?;
+            let file = file.into_inner();
+
+            /* anything past the file size, like .bss, stays zero */
+            for region in loads {
+                let src = range(region.offset, region.filesz).and_then(|r| file.get(r));
+                let dst = range(region.vaddr - start, region.filesz).and_then(|r| mem.get_mut(r));
+                match (src, dst) {
+                    (Some(src), Some(dst)) => dst.copy_from_slice(src),
+                    _                      => return Err(Error::Truncated),
+                }
+            }
+
+            /* program headers, as seen from the auxiliary vector */
+            let phoff = obj.header().phoff;
+            self.phdr = match obj.segments().iter().find(|s| matches!(s.etype, SegmentType::Phdr)) {
+                Some(seg) => Some(seg.vaddr.wrapping_add(self.bias)),
+                None => loads.iter()
+                    .find(|s| s.offset <= phoff && phoff + PHDR_SIZE <= s.offset + s.filesz)
+                    .map(|s| (s.vaddr + phoff - s.offset).wrapping_add(self.bias)),
+            };
+
+            /* the relocations, as the dynamic entries give them */
+            let at = |addr: u64, size: u64| range(addr.wrapping_sub(start), size);
+            let dynamic = obj.segments().iter()
+                .find(|s| matches!(s.etype, SegmentType::Dynamic))
+                .map(|s| at(s.vaddr, s.filesz).and_then(|r| mem.get(r)).ok_or(Error::Truncated))
+                .transpose()?
+                .map_or(vec![], Dyn::extract_all);
+            let value = |tag| dynamic.iter().find(|d| d.tag == tag).map(|d| d.val);
+            let relocs = match (value(DT_RELA), value(DT_RELASZ)) {
+                (Some(addr), Some(size)) => {
+                    let table = at(addr, size).and_then(|r| mem.get(r)).ok_or(Error::Truncated)?;
+                    Rela::extract_all(table)
+                }
+                _ => vec![],
+            };
+            for rela in relocs {
+                match rela.rtype {
+                    R_X86_64_NONE | R_X86_64_IRELATIVE => (),
+                    R_X86_64_RELATIVE => {
+                        let dst = at(rela.offset, 8).and_then(|r| mem.get_mut(r));
+                        let dst = dst.ok_or(Error::Truncated)?;
+                        let value = self.bias.wrapping_add(rela.addend as u64);
+                        dst.copy_from_slice(&value.to_le_bytes());
+                    }
+                    t => return Err(Error::Unsupported(t)),
+                }
+            }
+
+            /* pages shared by two regions get both protections */
+            let mut prots = vec![PROT_NONE; self.len / PAGE as usize];
+            for region in loads {
+                let first = (region.vaddr - start) / PAGE;
+                let last = (util::align(region.vaddr + region.memsz(), PAGE) - start) / PAGE;
+                for page in first..last {
+                    prots[page as usize] |= prot(region);
+                }
+            }
+            let mut page = 0;
+            while page < prots.len() {
+                let run = prots[page..].iter().take_while(|&&p| p == prots[page]).count();
+                let ret = unsafe {
+                    mprotect(self.base.add(page * PAGE as usize), run * PAGE as usize, prots[page])
+                };
+                if ret != 0 {
+                    return Err(Error::Map(io::Error::last_os_error()));
+                }
+                page += run;
+            }
+            Ok(self)
+        }
+    }
+}
+
+/// Running methods.
+mod running {
+    use super::*;
+
+    /// A stack being filled from the top.
+    struct Stack {
+        /// Backing memory.
+        mem:    &'static mut [u8],
+        /// Address of the memory.
+        base:   u64,
+        /// Offset of the lowest byte used.
+        top:    usize,
+    }
+
+    impl Stack {
+        /// Pushes bytes, and gets their address.
+        fn push(&mut self, bytes: &[u8]) -> u64 {
+            self.top -= bytes.len();
+            self.mem[self.top..self.top + bytes.len()].copy_from_slice(bytes);
+            self.base + self.top as u64
+        }
+
+        /// Pushes a string with its terminating null, and gets its
+        /// address.
+        fn push_str(&mut self, s: &str) -> u64 {
+            self.push(&[0]);
+            self.push(s.as_bytes())
+        }
+    }
+
+    impl Program {
+        /// Starts the program with the given arguments and environment,
+        /// passing `argv[0]` as its name.
+        ///
+        /// The stack is laid out as the kernel does: `argc`, `argv`,
+        /// `envp` and the auxiliary vector, with the strings above.
+        ///
+        /// # Safety
+        /// The program takes over the process: it shares its memory,
+        /// threads and file descriptors, and ends it when exiting.
+        pub unsafe fn run(&self, argv: &[&str], envp: &[&str]) -> ! {
+            let mut stack = Stack {
+                mem:    std::slice::from_raw_parts_mut(self.stack, STACK_SIZE),
+                base:   self.stack as u64,
+                top:    STACK_SIZE,
+            };
+
+            /* strings and blobs */
+            let execfn = stack.push_str(argv.first().copied().unwrap_or(""));
+            let platform = stack.push_str("x86_64");
+            let mut random = [0u8; 16];
+            if let Ok(mut file) = std::fs::File::open("/dev/urandom") {
+                let _ = file.read_exact(&mut random);
+            }
+            let random = stack.push(&random);
+            let phdr = match self.phdr {
+                Some(phdr) => phdr,
+                None       => stack.push(&self.phdrs),
+            };
+            let args: Vec<u64> = argv.iter().map(|s| stack.push_str(s)).collect();
+            let envs: Vec<u64> = envp.iter().map(|s| stack.push_str(s)).collect();
+
+            let auxv = [
+                (AT_PHDR,           phdr),
+                (AT_PHENT,          PHDR_SIZE),
+                (AT_PHNUM,          self.phdrs.len() as u64 / PHDR_SIZE),
+                (AT_PAGESZ,         PAGE),
+                (AT_BASE,           0),
+                (AT_FLAGS,          0),
+                (AT_ENTRY,          self.entry),
+                (AT_UID,            getauxval(AT_UID)),
+                (AT_EUID,           getauxval(AT_EUID)),
+                (AT_GID,            getauxval(AT_GID)),
+                (AT_EGID,           getauxval(AT_EGID)),
+                (AT_PLATFORM,       platform),
+                (AT_HWCAP,          getauxval(AT_HWCAP)),
+                (AT_CLKTCK,         getauxval(AT_CLKTCK)),
+                (AT_SECURE,         0),
+                (AT_RANDOM,         random),
+                (AT_HWCAP2,         getauxval(AT_HWCAP2)),
+                (AT_EXECFN,         execfn),
+                (AT_SYSINFO_EHDR,   getauxval(AT_SYSINFO_EHDR)),
+                (AT_NULL,           0),
+            ];
+
+            /* argc, argv, envp and auxv, 16 bytes aligned */
+            let mut words = vec![args.len() as u64];
+            words.extend(&args);
+            words.push(0);
+            words.extend(&envs);
+            words.push(0);
+            for (atype, value) in auxv.iter() {
+                words.push(*atype);
+                words.push(*value);
+            }
+            stack.top = (stack.top - words.len() * 8) / 16 * 16;
+            let sp = stack.base + stack.top as u64;
+            for (i, word) in words.iter().enumerate() {
+                let off = stack.top + i * 8;
+                stack.mem[off..off + 8].copy_from_slice(&word.to_le_bytes());
+            }
+
+            /* no exit handler for the C library to register */
+            asm!(
+                "mov rsp, rsi",
+                "xor edx, edx",
+                "xor ebp, ebp",
+                "jmp rdi",
+                in("rsi") sp,
+                in("rdi") self.entry,
+                options(noreturn),
+            );
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe a mapping error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::NotExecutable    => write!(f, "not an x86-64 executable"),
+                Error::Interpreter      => write!(f, "executable needs an interpreter"),
+                Error::Plan(err)        => write!(f, "invalid load plan: {}", err),
+                Error::Unsupported(t)   => write!(f, "unsupported relocation type {}", t),
+                Error::Truncated        => write!(f, "segment or relocation out of bounds"),
+                Error::Map(err)         => write!(f, "cannot map memory: {}", err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    #[test]
+    fn map_static_pie() {
+        let mut data = sample("static");
+        let obj = Object::from_bytes(&data);
+        let link = |name| obj.symbols().iter()
+            .find(|s| s.name.as_deref() == Some(name))
+            .unwrap()
+            .value;
+
+        /* with and without section headers */
+        let program = Program::map(&obj).unwrap();
+        data[0x28..0x30].fill(0);
+        data[0x3c..0x40].fill(0);
+        let bare = Object::from_bytes(&data);
+        assert!(bare.sections().len() <= 1);
+        let bare = Program::map(&bare).unwrap();
+
+        for program in &[program, bare] {
+            let addr = |name| link(name).wrapping_add(program.bias());
+            assert_ne!(program.bias(), 0);
+            assert_eq!(program.entry(), addr("_start"));
+            unsafe {
+                assert_eq!(*(addr("value") as *const i32), 42);
+                assert_eq!(*(addr("pointer") as *const u64), addr("value"));
+            }
+        }
+    }
+
+    #[test]
+    fn reject_interpreter() {
+        let obj = Object::from_bytes(&sample("main"));
+        assert!(matches!(Program::map(&obj), Err(Error::Interpreter)));
+    }
+}
@@ -0,0 +1,437 @@
+//! Regarding firmware images, like `objcopy -O binary`, `-O ihex` and
+//! `-O srec` produce them.
+//!
+//! The contents come from the allocated sections of each `PT_LOAD`
+//! segment, placed at their load address, which is the segment `paddr`
+//! plus the section offset into it. Then:
+//! - Raw binaries start at the lowest load address, gaps being filled.
+//! - Intel HEX files use extended linear address records past 64KiB,
+//!   and a start linear address record for the entry point.
+//! - Motorola S-record files use S1, S2 or S3 data records, depending
+//!   on the highest address, and the matching S9, S8 or S7 record for
+//!   the entry point.
+//!
+//! Contents overlapping at their load address, and addresses out of the
+//! range of the format, are errors.
+//!
+//! Images can be turned back into an executable, with one writable and
+//! executable `.secN` section and segment per contiguous block.
+
+use std::fmt;
+use std::fmt::Write as _;
+
+use crate::header::{Header, Type as HeaderType};
+use crate::object::Object;
+use crate::section::{self, Section, Type as SectionType};
+use crate::segment::{self, Segment, Type as SegmentType};
+
+/// Number of data bytes per record.
+const RECORD_LEN: usize = 16;
+
+/// Intel HEX record types.
+const IHEX_DATA: u8 = 0;
+const IHEX_EOF: u8 = 1;
+const IHEX_SEGMENT: u8 = 2;
+const IHEX_START_SEGMENT: u8 = 3;
+const IHEX_LINEAR: u8 = 4;
+const IHEX_START_LINEAR: u8 = 5;
+
+/// A contiguous block of memory contents.
+type Block = (u64, Vec<u8>);
+
+/// Errors found while writing or reading an image.
+#[derive(Debug)]
+pub enum Error {
+    /// A malformed record, at the given line.
+    Syntax(usize),
+    /// A record with a wrong checksum, at the given line.
+    Checksum(usize),
+    /// An unknown record type, at the given line.
+    Record(usize),
+    /// An address the format cannot hold.
+    Range(u64),
+    /// Contents loaded over others, at the given address.
+    Overlap(u64),
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use super::image::*;
+
+    impl Object {
+        /// Get the lowest load address and the raw memory image from
+        /// there, gaps being filled with the given byte.
+        pub fn to_binary(&self, fill: u8) -> Result<(u64, Vec<u8>), Error> {
+            let blocks = self.image_blocks(u64::MAX)?;
+            let base = match blocks.first() {
+                Some((addr, _)) => *addr,
+                None            => return Ok((0, vec![])),
+            };
+            let mut image = vec![];
+            for (addr, data) in blocks {
+                image.resize((addr - base) as usize, fill);
+                image.extend_from_slice(&data);
+            }
+            Ok((base, image))
+        }
+
+        /// Get the memory image as an Intel HEX file.
+        ///
+        /// Every address, the entry point included, must fit in 32 bits.
+        pub fn to_ihex(&self) -> Result<String, Error> {
+            let max = u32::MAX as u64;
+            let blocks = self.image_blocks(max)?;
+            let entry = self.header.entry;
+            if entry > max {
+                return Err(Error::Range(entry));
+            }
+
+            let mut out = String::new();
+            let mut upper = 0;
+            for (addr, data) in blocks {
+                let mut addr = addr;
+                let mut data = &data[..];
+                while !data.is_empty() {
+                    if addr >> 16 != upper {
+                        upper = addr >> 16;
+                        ihex_record(&mut out, IHEX_LINEAR, 0, &(upper as u16).to_be_bytes());
+                    }
+                    /* records do not cross 64KiB boundaries */
+                    let room = 0x10000 - (addr & 0xffff) as usize;
+                    let len = data.len().min(RECORD_LEN).min(room);
+                    ihex_record(&mut out, IHEX_DATA, addr as u16, &data[..len]);
+                    addr += len as u64;
+                    data = &data[len..];
+                }
+            }
+            if entry != 0 {
+                ihex_record(&mut out, IHEX_START_LINEAR, 0, &(entry as u32).to_be_bytes());
+            }
+            ihex_record(&mut out, IHEX_EOF, 0, &[]);
+            Ok(out)
+        }
+
+        /// Get the memory image as a Motorola S-record file.
+        ///
+        /// Every address, the entry point included, must fit in 32 bits.
+        pub fn to_srec(&self) -> Result<String, Error> {
+            let max = u32::MAX as u64;
+            let blocks = self.image_blocks(max)?;
+            let entry = self.header.entry;
+            if entry > max {
+                return Err(Error::Range(entry));
+            }
+            let last = blocks.iter()
+                .map(|(addr, data)| addr + data.len() as u64 - 1)
+                .chain(Some(entry))
+                .max()
+                .unwrap_or(0);
+            let width = match last {
+                0..=0xffff           => 2,
+                0x1_0000..=0xff_ffff => 3,
+                _                    => 4,
+            };
+
+            let mut out = String::new();
+            srec_record(&mut out, 0, 2, 0, &[]);
+            for (addr, data) in blocks {
+                for (i, chunk) in data.chunks(RECORD_LEN).enumerate() {
+                    let addr = addr + (i * RECORD_LEN) as u64;
+                    srec_record(&mut out, width - 1, width, addr, chunk);
+                }
+            }
+            srec_record(&mut out, 11 - width, width, entry, &[]);
+            Ok(out)
+        }
+
+        /// Creates an executable from a raw memory image loaded at the
+        /// given address.
+        pub fn from_binary(data: &[u8], addr: u64, machine: u16) -> Object {
+            Object::from_blocks(vec![(addr, data.to_vec())], addr, machine)
+        }
+
+        /// Creates an executable from an Intel HEX file.
+        pub fn from_ihex(text: &str, machine: u16) -> Result<Object, Error> {
+            let mut blocks = vec![];
+            let mut base = 0;
+            let mut entry = 0;
+            for (i, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let bytes = line.strip_prefix(':')
+                    .and_then(decode_hex)
+                    .filter(|b| b.len() >= 5 && b.len() == b[0] as usize + 5)
+                    .ok_or(Error::Syntax(i + 1))?;
+                if bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b)) != 0 {
+                    return Err(Error::Checksum(i + 1));
+                }
+                let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+                let data = &bytes[4..bytes.len() - 1];
+                match (bytes[3], data.len()) {
+                    (IHEX_DATA, _)              => blocks.push((base + offset, data.to_vec())),
+                    (IHEX_EOF, _)               => break,
+                    (IHEX_SEGMENT, 2)           => base = (be(data) as u64) << 4,
+                    (IHEX_LINEAR, 2)            => base = (be(data) as u64) << 16,
+                    (IHEX_START_SEGMENT, 4)     => {
+                        entry = ((be(&data[..2]) as u64) << 4) + be(&data[2..]) as u64;
+                    }
+                    (IHEX_START_LINEAR, 4)      => entry = be(data) as u64,
+                    (IHEX_SEGMENT..=IHEX_START_LINEAR, _) => return Err(Error::Syntax(i + 1)),
+                    _ => return Err(Error::Record(i + 1)),
+                }
+            }
+            Ok(Object::from_blocks(blocks, entry, machine))
+        }
+
+        /// Creates an executable from a Motorola S-record file.
+        pub fn from_srec(text: &str, machine: u16) -> Result<Object, Error> {
+            let mut blocks = vec![];
+            let mut entry = 0;
+            for (i, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let kind = line.strip_prefix('S')
+                    .and_then(|l| l.chars().next())
+                    .and_then(|c| c.to_digit(10))
+                    .ok_or(Error::Syntax(i + 1))?;
+                let bytes = decode_hex(&line[2..])
+                    .filter(|b| !b.is_empty() && b.len() == b[0] as usize + 1)
+                    .ok_or(Error::Syntax(i + 1))?;
+                if bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b)) != 0xff {
+                    return Err(Error::Checksum(i + 1));
+                }
+                let width = match kind {
+                    0 | 1 | 5 | 9   => 2,
+                    2 | 6 | 8       => 3,
+                    3 | 7           => 4,
+                    _               => return Err(Error::Record(i + 1)),
+                };
+                if bytes.len() < width + 2 {
+                    return Err(Error::Syntax(i + 1));
+                }
+                let addr = be(&bytes[1..1 + width]) as u64;
+                let data = &bytes[1 + width..bytes.len() - 1];
+                match kind {
+                    1..=3 => blocks.push((addr, data.to_vec())),
+                    7..=9 => entry = addr,
+                    _ => (),
+                }
+            }
+            Ok(Object::from_blocks(blocks, entry, machine))
+        }
+    }
+}
+
+/// Image methods.
+mod image {
+    use super::*;
+
+    impl Object {
+        /// Get the contents of the loadable sections at their load
+        /// address, sorted by it.
+        pub(crate) fn load_blocks(&self) -> Vec<Block> {
+            let mut blocks: Vec<Block> = vec![];
+            for seg in self.segments.iter().filter(|s| s.is_load() && s.filesz != 0) {
+                for section in &self.sections {
+                    let inside = section.offset >= seg.offset
+                        && section.offset + section.size <= seg.offset + seg.filesz;
+                    if !section.is_alloc() || section.is_nobits() || section.size == 0 || !inside {
+                        continue;
+                    }
+                    let addr = seg.paddr + section.offset - seg.offset;
+                    blocks.push((addr, section.data.clone()));
+                }
+            }
+            blocks.sort_by_key(|(addr, _)| *addr);
+            blocks
+        }
+
+        /// Get the contents of the loadable sections at their load
+        /// address, checking none overlaps another and every byte is at
+        /// most at `max`.
+        pub(crate) fn image_blocks(&self, max: u64) -> Result<Vec<Block>, Error> {
+            let blocks = self.load_blocks();
+            let mut end = 0;
+            for (addr, data) in blocks.iter().filter(|(_, d)| !d.is_empty()) {
+                if *addr < end {
+                    return Err(Error::Overlap(*addr));
+                }
+                let last = addr.checked_add(data.len() as u64 - 1)
+                    .filter(|&last| last <= max)
+                    .ok_or(Error::Range(*addr))?;
+                end = last.saturating_add(1);
+            }
+            Ok(blocks)
+        }
+
+        /// Creates an executable holding the given blocks.
+        pub(crate) fn from_blocks(blocks: Vec<Block>, entry: u64, machine: u16) -> Object {
+            let blocks = merge_blocks(blocks);
+            let mut obj = Object::empty();
+            obj.header = Header::new(HeaderType::Exec, machine);
+            obj.header.entry = entry;
+            obj.header.phoff = 64;
+            obj.header.phentsize = 56;
+            obj.sections.push(Section::new("", SectionType::Null, 0));
+
+            let flags = section::SHF_ALLOC | section::SHF_WRITE | section::SHF_EXECINSTR;
+            let mut offset = 64 + 56 * blocks.len() as u64;
+            for (i, (addr, data)) in blocks.into_iter().enumerate() {
+                let size = data.len() as u64;
+                let mut section = Section::new(&format!(".sec{}", i + 1), SectionType::Progbits, flags);
+                section.addr = addr;
+                section.offset = offset;
+                section.size = size;
+                section.addralign = 1;
+                section.data = data;
+                obj.sections.push(section);
+
+                let mut seg = Segment::new(SegmentType::Load,
+                    segment::PF_R | segment::PF_W | segment::PF_X);
+                seg.offset = offset;
+                seg.vaddr = addr;
+                seg.paddr = addr;
+                seg.filesz = size;
+                seg.memsz = size;
+                seg.align = 1;
+                obj.segments.push(seg);
+                offset += size;
+            }
+
+            let mut shstrtab = Section::new(".shstrtab", SectionType::Strtab, 0);
+            shstrtab.addralign = 1;
+            obj.header.shstrndx = obj.sections.len();
+            obj.sections.push(shstrtab);
+            obj.layout();
+            obj
+        }
+    }
+
+    /// Sorts blocks by address and merges the adjacent ones.
+    ///
+    /// Overlapping blocks overwrite the earlier ones.
+    pub(crate) fn merge_blocks(mut blocks: Vec<Block>) -> Vec<Block> {
+        blocks.sort_by_key(|(addr, _)| *addr);
+        let mut merged: Vec<Block> = vec![];
+        for (addr, data) in blocks {
+            if let Some((last, last_data)) = merged.last_mut() {
+                let end = *last + last_data.len() as u64;
+                if addr <= end {
+                    let at = (addr - *last) as usize;
+                    let len = last_data.len().max(at + data.len());
+                    last_data.resize(len, 0);
+                    last_data[at..at + data.len()].copy_from_slice(&data);
+                    continue;
+                }
+            }
+            merged.push((addr, data));
+        }
+        merged
+    }
+
+    /// Appends an Intel HEX record.
+    pub(crate) fn ihex_record(out: &mut String, kind: u8, addr: u16, data: &[u8]) {
+        let mut bytes = vec![data.len() as u8];
+        bytes.extend_from_slice(&addr.to_be_bytes());
+        bytes.push(kind);
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+        bytes.push(sum.wrapping_neg());
+        out.push(':');
+        encode_hex(out, &bytes);
+    }
+
+    /// Appends a Motorola S-record, with an address of the given width
+    /// in bytes.
+    pub(crate) fn srec_record(out: &mut String, kind: usize, width: usize, addr: u64, data: &[u8]) {
+        let mut bytes = vec![(width + data.len() + 1) as u8];
+        bytes.extend_from_slice(&addr.to_be_bytes()[8 - width..]);
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+        bytes.push(!sum);
+        write!(out, "S{}", kind).unwrap();
+        encode_hex(out, &bytes);
+    }
+
+    /// Appends bytes as upper case hexadecimal, then a line feed.
+    fn encode_hex(out: &mut String, bytes: &[u8]) {
+        for b in bytes {
+            write!(out, "{:02X}", b).unwrap();
+        }
+        out.push('\n');
+    }
+
+    /// Decodes hexadecimal digit pairs.
+    pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+        if !s.len().is_multiple_of(2) || !s.is_ascii() {
+            return None;
+        }
+        (0..s.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+            .collect()
+    }
+
+    /// Get a big endian value from up to four bytes.
+    pub(crate) fn be(bytes: &[u8]) -> u32 {
+        bytes.iter().fold(0, |v, &b| v << 8 | b as u32)
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe an image reading error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Syntax(line)     => write!(f, "malformed record at line {}", line),
+                Error::Checksum(line)   => write!(f, "wrong checksum at line {}", line),
+                Error::Record(line)     => write!(f, "unknown record type at line {}", line),
+                Error::Range(addr)      => write!(f, "address {:#x} out of range", addr),
+                Error::Overlap(addr)    => write!(f, "contents overlap at address {:#x}", addr),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::EM_X86_64;
+
+    #[test]
+    fn round_trip() {
+        let obj = Object::from_binary(&[1, 2, 3, 4], 0x12_3456, EM_X86_64);
+        let ihex = Object::from_ihex(&obj.to_ihex().unwrap(), EM_X86_64).unwrap();
+        let srec = Object::from_srec(&obj.to_srec().unwrap(), EM_X86_64).unwrap();
+        for read in &[ihex, srec] {
+            assert_eq!(read.to_binary(0).unwrap(), (0x12_3456, vec![1, 2, 3, 4]));
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let obj = Object::from_binary(&[1, 2], 0x1_0000_0000, EM_X86_64);
+        assert!(matches!(obj.to_ihex(), Err(Error::Range(_))));
+        assert!(matches!(obj.to_srec(), Err(Error::Range(_))));
+
+        let mut obj = Object::from_binary(&[1, 2], 0x1000, EM_X86_64);
+        obj.header.entry = u64::MAX;
+        assert!(matches!(obj.to_ihex(), Err(Error::Range(u64::MAX))));
+        assert!(matches!(obj.to_srec(), Err(Error::Range(u64::MAX))));
+    }
+
+    #[test]
+    fn overlap() {
+        let mut obj = Object::from_blocks(vec![(0x1000, vec![1; 8]), (0x2000, vec![2; 8])],
+            0x1000, EM_X86_64);
+        obj.segments[1].paddr = 0x1004;
+        assert!(matches!(obj.to_binary(0), Err(Error::Overlap(0x1004))));
+    }
+}
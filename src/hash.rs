@@ -0,0 +1,398 @@
+//! Regarding the dynamic symbol hash tables.
+//!
+//! Two flavours exist:
+//! - `.hash` (`DT_HASH`): the original System V table, a set of buckets
+//!   each holding a linked chain of symbol indices.
+//! - `.gnu.hash` (`DT_GNU_HASH`): the GNU table, with a bloom filter in
+//!   front of buckets pointing into a sorted run of chain values.
+//!
+//! Both allow looking up a dynamic symbol by name the same way the
+//! dynamic loader does.
+
+/// A System V hash table.
+pub struct SysvHash {
+    /// First symbol index of each chain.
+    pub buckets:    Vec<u32>,
+    /// Next symbol index for each symbol.
+    pub chains:     Vec<u32>,
+}
+
+/// A GNU hash table.
+pub struct GnuHash {
+    /// Index of the first symbol reachable through the table.
+    pub symoffset:  u32,
+    /// Shift used for the second bloom filter bit.
+    pub shift:      u32,
+    /// Bloom filter words.
+    pub bloom:      Vec<u64>,
+    /// First symbol index of each bucket.
+    pub buckets:    Vec<u32>,
+    /// Hash value for each symbol from `symoffset` on, with the low bit
+    /// marking the end of a bucket.
+    pub chains:     Vec<u32>,
+}
+
+/// Bucket chain length distribution of a hash table.
+pub struct Stats {
+    /// Amount of buckets in the table.
+    pub buckets:    usize,
+    /// Amount of symbols reachable through the table.
+    pub symbols:    usize,
+    /// Amount of buckets for each chain length.
+    pub histogram:  Vec<usize>,
+}
+
+/// Compute the System V hash of a name.
+pub fn elf_hash(name: &[u8]) -> u32 {
+    let mut h = 0u32;
+    for &c in name {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
+/// Compute the GNU hash of a name.
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    let mut h = 5381u32;
+    for &c in name {
+        h = h.wrapping_mul(33).wrapping_add(c as u32);
+    }
+    h
+}
+
+/// Simple System V hash methods.
+impl SysvHash {
+    /// Find the index of a symbol, given a predicate checking wether
+    /// a candidate index matches.
+    pub fn find<F>(&self, name: &str, mut matches: F) -> Option<usize>
+        where F: FnMut(usize) -> bool
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let h = elf_hash(name.as_bytes()) as usize;
+        let start = self.buckets[h % self.buckets.len()];
+        self.chain(start).find(|&i| matches(i))
+    }
+
+    /// Get the chain length distribution.
+    pub fn stats(&self) -> Stats {
+        let lengths: Vec<usize> = self.buckets.iter()
+            .map(|&b| self.chain(b).count())
+            .collect();
+        Stats::new(&lengths)
+    }
+
+    /// Get the symbol indices of the chain starting at the given one.
+    ///
+    /// The walk stops at indices past the chains, and after as many
+    /// steps as there are chains, so cyclic chains end too.
+    fn chain(&self, start: u32) -> impl Iterator<Item = usize> + '_ {
+        let mut i = start as usize;
+        (0..self.chains.len()).map_while(move |_| {
+            if i == 0 || i >= self.chains.len() {
+                return None;
+            }
+            let curr = i;
+            i = self.chains[i] as usize;
+            Some(curr)
+        })
+    }
+}
+
+/// Simple GNU hash methods.
+impl GnuHash {
+    /// Find the index of a symbol, given a predicate checking wether
+    /// a candidate index matches.
+    ///
+    /// The bloom filter is checked first, so most missing names are
+    /// rejected without touching the buckets.
+    pub fn find<F>(&self, name: &str, mut matches: F) -> Option<usize>
+        where F: FnMut(usize) -> bool
+    {
+        if self.buckets.is_empty() || self.bloom.is_empty() {
+            return None;
+        }
+        let h1 = gnu_hash(name.as_bytes());
+
+        /* bloom filter: both bits must be set, a bogus shift matching
+         * nothing
+         */
+        let h2 = h1.checked_shr(self.shift)?;
+        let word = self.bloom[(h1 as usize / 64) % self.bloom.len()];
+        let mask = (1u64 << (h1 % 64)) | (1u64 << (h2 % 64));
+        if word & mask != mask {
+            return None;
+        }
+
+        /* walk the bucket's run of hash values */
+        let start = self.buckets[h1 as usize % self.buckets.len()];
+        self.chain(start)
+            .find(|&(i, h2)| (h1 | 1) == (h2 | 1) && matches(i))
+            .map(|(i, _)| i)
+    }
+
+    /// Get the chain length distribution.
+    pub fn stats(&self) -> Stats {
+        let lengths: Vec<usize> = self.buckets.iter()
+            .map(|&b| self.chain(b).count())
+            .collect();
+        Stats::new(&lengths)
+    }
+
+    /// Get the symbol indices and hash values of the run starting at
+    /// the given index.
+    ///
+    /// The walk stops at the value marking the end of the run, or at the
+    /// end of the chains for truncated tables.
+    fn chain(&self, start: u32) -> impl Iterator<Item = (usize, u32)> + '_ {
+        let symoffset = self.symoffset as usize;
+        let first = (start as usize).max(symoffset);
+        let mut done = (start as usize) < symoffset;
+        (first..symoffset + self.chains.len()).map_while(move |i| {
+            if done {
+                return None;
+            }
+            let h = self.chains[i - symoffset];
+            done = h & 1 != 0;
+            Some((i, h))
+        })
+    }
+}
+
+/// Simple stats methods.
+impl Stats {
+    /// Build stats from the chain length of each bucket.
+    fn new(lengths: &[usize]) -> Self {
+        let max = lengths.iter().copied().max().unwrap_or(0);
+        let mut histogram = vec![0; max + 1];
+        for &len in lengths {
+            histogram[len] += 1;
+        }
+        Self {
+            buckets: lengths.len(),
+            symbols: lengths.iter().sum(),
+            histogram,
+        }
+    }
+
+    /// Length of the longest chain.
+    pub fn max_chain(&self) -> usize {
+        self.histogram.len() - 1
+    }
+
+    /// Average amount of names compared for a successful lookup.
+    pub fn avg_probes(&self) -> f64 {
+        if self.symbols == 0 {
+            return 0.0;
+        }
+        /* a chain of length n costs 1 + 2 + ... + n over its symbols */
+        let total: usize = self.histogram.iter()
+            .enumerate()
+            .map(|(len, n)| n * len * (len + 1) / 2)
+            .sum();
+        total as f64 / self.symbols as f64
+    }
+}
+
+/// Format methods.
+mod format {
+    use std::fmt;
+    use super::*;
+
+    impl fmt::Display for Stats {
+        /// Print the histogram the same way `readelf -I` does.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "  {0: >6}  {1: <10} {2: <11} {3: <8}",
+                "length", "number", "% of total", "coverage")?;
+            let mut covered = 0;
+            for (len, &n) in self.histogram.iter().enumerate() {
+                let pct = 100.0 * n as f64 / self.buckets.max(1) as f64;
+                if len == 0 {
+                    writeln!(f, "  {0: >6}  {1: <10} ({2: >5.1}%)", len, n, pct)?;
+                    continue;
+                }
+                covered += len * n;
+                let cov = 100.0 * covered as f64 / self.symbols.max(1) as f64;
+                writeln!(f, "  {0: >6}  {1: <10} ({2: >5.1}%)    {3: >5.1}%",
+                    len, n, pct, cov)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Buffer IO methods.
+pub mod io {
+    use super::*;
+    use crate::util;
+
+    impl SysvHash {
+        /// Extract a System V hash table from the contents of a `.hash`
+        /// section.
+        ///
+        /// Truncated tables keep the words present.
+        pub fn extract(data: &[u8]) -> Self {
+            let words = |off: usize, n: usize| -> Vec<u32> {
+                let n = n.min(data.len().saturating_sub(off) / 4);
+                (0..n).map(|i| util::get_u32(data, off + i * 4)).collect()
+            };
+            let header = words(0, 2);
+            let (nbucket, nchain) = match header[..] {
+                [nbucket, nchain] => (nbucket as usize, nchain as usize),
+                _                 => (0, 0),
+            };
+            let buckets = words(8, nbucket);
+            let chains  = words(8 + buckets.len() * 4, nchain);
+            Self { buckets, chains }
+        }
+    }
+
+    impl GnuHash {
+        /// Extract a GNU hash table from the contents of a `.gnu.hash`
+        /// section, for a dynamic symbol table with `nsyms` entries.
+        ///
+        /// Truncated tables keep the words present.
+        pub fn extract(data: &[u8], nsyms: usize) -> Self {
+            if data.len() < 16 {
+                return Self {
+                    symoffset:  0,
+                    shift:      0,
+                    bloom:      vec![],
+                    buckets:    vec![],
+                    chains:     vec![],
+                };
+            }
+            let nbucket   = util::get_u32(data, 0) as usize;
+            let symoffset = util::get_u32(data, 4);
+            let nbloom    = util::get_u32(data, 8) as usize;
+            let shift     = util::get_u32(data, 12);
+
+            let nbloom = nbloom.min((data.len() - 16) / 8);
+            let bloom = (0..nbloom)
+                .map(|i| util::get_u64(data, 16 + i * 8))
+                .collect();
+            let off = 16 + nbloom * 8;
+            let nbucket = nbucket.min((data.len() - off) / 4);
+            let buckets = (0..nbucket)
+                .map(|i| util::get_u32(data, off + i * 4))
+                .collect();
+            let off = off + nbucket * 4;
+            /* tables without defined symbols may omit the chains */
+            let nchain = nsyms.saturating_sub(symoffset as usize)
+                .min((data.len() - off) / 4);
+            let chains = (0..nchain)
+                .map(|i| util::get_u32(data, off + i * 4))
+                .collect();
+
+            Self { symoffset, shift, bloom, buckets, chains }
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use crate::object::Object;
+    use crate::sym::Sym;
+
+    impl Object {
+        /// Get the System V hash table of the object, if any.
+        pub fn sysv_hash(&self) -> Option<&SysvHash> {
+            self.sysv_hash.as_ref()
+        }
+
+        /// Get the GNU hash table of the object, if any.
+        pub fn gnu_hash(&self) -> Option<&GnuHash> {
+            self.gnu_hash.as_ref()
+        }
+
+        /// Look up a defined dynamic symbol by name through the hash
+        /// tables, preferring the GNU one when both are present.
+        ///
+        /// When `version` is given only that version matches, otherwise
+        /// hidden versions are skipped, just like the dynamic loader
+        /// does for unversioned references.
+        pub fn lookup(&self, name: &str, version: Option<&str>) -> Option<&Sym> {
+            let matches = |i: usize| {
+                let sym = match self.dynsyms.get(i) {
+                    Some(sym) => sym,
+                    None      => return false,
+                };
+                if sym.is_undefined() || sym.name.as_deref() != Some(name) {
+                    return false;
+                }
+                match version {
+                    Some(v) => sym.version.as_deref() == Some(v),
+                    None    => !sym.hidden,
+                }
+            };
+            let ndx = match (&self.gnu_hash, &self.sysv_hash) {
+                (Some(h), _)    => h.find(name, matches),
+                (None, Some(h)) => h.find(name, matches),
+                (None, None)    => None,
+            };
+            ndx.map(|i| &self.dynsyms[i])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Object;
+    use crate::tests::sample;
+
+    #[test]
+    fn sysv_cyclic_chain_ends() {
+        let hash = SysvHash { buckets: vec![1], chains: vec![0, 2, 1] };
+        assert_eq!(hash.find("x", |_| false), None);
+        assert_eq!(hash.stats().max_chain(), 3);
+    }
+
+    #[test]
+    fn gnu_truncated_chains_end() {
+        let hash = GnuHash {
+            symoffset:  1,
+            shift:      0,
+            bloom:      vec![u64::MAX],
+            buckets:    vec![1],
+            chains:     vec![0, 0],
+        };
+        assert_eq!(hash.find("x", |_| true), None);
+        assert_eq!(hash.stats().max_chain(), 2);
+    }
+
+    #[test]
+    fn gnu_bogus_shift() {
+        let hash = GnuHash {
+            symoffset:  1,
+            shift:      40,
+            bloom:      vec![u64::MAX],
+            buckets:    vec![1],
+            chains:     vec![1],
+        };
+        assert_eq!(hash.find("x", |_| true), None);
+    }
+
+    #[test]
+    fn sample_lookups() {
+        let obj = Object::from_bytes(&sample("libsample.so"));
+        let (gnu, sysv) = (obj.gnu_hash().unwrap(), obj.sysv_hash().unwrap());
+        assert_eq!((gnu.stats().max_chain(), sysv.stats().max_chain()), (2, 3));
+
+        for name in &["lib_add", "lib_counter", "lib_next", "lib_mul"] {
+            let sym = obj.lookup(name, None).unwrap();
+            assert_eq!(sym.name.as_deref(), Some(*name));
+            let named = |i: usize| obj.dynsyms()[i].name.as_deref() == Some(*name);
+            assert_eq!(gnu.find(name, named), sysv.find(name, named));
+        }
+        assert!(obj.lookup("lib_sub", None).is_none());
+        assert!(obj.lookup("__gmon_start__", None).is_none());
+    }
+}
@@ -3,14 +3,21 @@
 //! **TODO:
 //! better documentation of header layout.**
 
+/// Machine value for x86-64.
+pub const EM_X86_64: u16 = 62;
+
 /// ELF header identification.
+#[derive(Clone)]
 struct Ident {
     /// Wether the header is a valid ELF header.
     valid: bool,
+    /// Raw identification bytes.
+    bytes: [u8; 16],
 }
 
 /// ELF file type.
-enum Type {
+#[derive(Clone, Copy)]
+pub enum Type {
     /// An unknown type.
     Null,
     /// A relocatable file.
@@ -21,24 +28,33 @@ enum Type {
     Dyn,
     /// A core file.
     Core,
+    /// An OS or processor specific type.
+    Unhandled(u16),
 }
 
 /// ELF machine.
-/// **TODO:**
+/// **TODO: named machines.**
+#[derive(Clone)]
 struct Machine {
+    value: u16,
 }
 
 /// ELF version.
 /// **TODO:**
+#[derive(Clone)]
 struct Version {
+    value: u32,
 }
 
 /// ELF flags.
-/// **TODO:**
+/// **TODO: processor specific flags.**
+#[derive(Clone)]
 struct Flags {
+    value: u32,
 }
 
 /// ELF header struct.
+#[derive(Clone)]
 pub struct Header {
     ident:          Ident,      // 16-bits
     etype:          Type,       // 16-bits
@@ -62,6 +78,7 @@ impl Ident {
     pub fn empty() -> Self {
         Self {
             valid: false,
+            bytes: [0u8; 16],
         }
     }
 
@@ -73,6 +90,7 @@ impl Ident {
                         ident[3] == b'F';
         Self {
             valid,
+            bytes: ident,
         }
     }
 }
@@ -92,7 +110,19 @@ impl Type {
             2 => Self::Exec,
             3 => Self::Dyn,
             4 => Self::Core,
-            _ => Self::Null,
+            _ => Self::Unhandled(etype),
+        }
+    }
+
+    /// Get real value of the type.
+    pub fn value(&self) -> u16 {
+        match self {
+            Self::Null         => 0,
+            Self::Rel          => 1,
+            Self::Exec         => 2,
+            Self::Dyn          => 3,
+            Self::Core         => 4,
+            Self::Unhandled(v) => *v,
         }
     }
 
@@ -104,6 +134,7 @@ impl Type {
                 Self::Exec => "executable",
                 Self::Dyn  => "shared object",
                 Self::Core => "core",
+                Self::Unhandled(_) => "unknown",
             }
     }
 }
@@ -113,12 +144,14 @@ impl Machine {
     /// Default Machine object.
     pub fn empty() -> Self {
         Self {
+            value: 0,
         }
     }
 
     /// Generate Machine object from machine value.
-    pub fn new(_machine: u16) -> Self {
+    pub fn new(machine: u16) -> Self {
         Self {
+            value: machine,
         }
     }
 }
@@ -128,12 +161,14 @@ impl Version {
     /// Default Version object.
     pub fn empty() -> Self{
         Self {
+            value: 0,
         }
     }
 
     /// Generate Version object from version value.
-    pub fn new(_version: u32) -> Self {
+    pub fn new(version: u32) -> Self {
         Self {
+            value: version,
         }
     }
 }
@@ -143,12 +178,14 @@ impl Flags {
     /// Default Flags object.
     pub fn empty() -> Self{
         Self {
+            value: 0,
         }
     }
 
     /// Generate Flags object from flags value.
-    pub fn new(_flags: u32) -> Self {
+    pub fn new(flags: u32) -> Self {
         Self {
+            value: flags,
         }
     }
 }
@@ -175,11 +212,51 @@ impl Header {
         }
     }
 
+    /// Creates a header for a 64-bit little endian file of the given
+    /// type and machine, without any sections or segments yet.
+    pub fn new(etype: Type, machine: u16) -> Self {
+        let mut ident = [0u8; 16];
+        ident[..4].copy_from_slice(b"\x7fELF");
+        ident[4] = 2;   // 64-bits
+        ident[5] = 1;   // little endian
+        ident[6] = 1;   // current version
+        Self {
+            ident:      Ident::new(ident),
+            etype,
+            machine:    Machine::new(machine),
+            version:    Version::new(1),
+            ehsize:     64,
+            shentsize:  64,
+            ..Self::empty()
+        }
+    }
+
     /// Check if an ELF header is valid.
     pub fn valid(&self) -> bool {
         self.ident.valid
     }
 
+    /// Get the machine value.
+    pub fn machine(&self) -> u16 {
+        self.machine.value
+    }
+
+    /// Check if the file is a relocatable object.
+    pub fn is_rel(&self) -> bool {
+        matches!(self.etype, Type::Rel)
+    }
+
+    /// Check if the file is a fixed address executable.
+    pub fn is_exec(&self) -> bool {
+        matches!(self.etype, Type::Exec)
+    }
+
+    /// Check if the file is a shared object or position independent
+    /// executable.
+    pub fn is_dyn(&self) -> bool {
+        matches!(self.etype, Type::Dyn)
+    }
+
     /// Get string slice for header type.
     pub fn type_str(&self) -> &'static str {
         self.etype.as_str()
@@ -203,14 +280,13 @@ pub mod format {
 
 /// File IO methods.
 pub mod io {
-    use std::fs::File;
-    use std::io::{Seek,Read,SeekFrom};
+    use std::io::{self,Seek,Read,Write,SeekFrom};
     use super::*;
     use super::super::util;
 
     impl Header {
         /// Extract ELF header from file.
-        pub fn extract(file: &mut File) -> Self {
+        pub fn extract<R: Read + Seek>(file: &mut R) -> Self {
             let mut new = Self::empty();
 
             // go to start of file
@@ -218,24 +294,42 @@ pub mod io {
             
             // ident
             let mut ident = [0u8; 16];
-            file.read(&mut ident).unwrap();
+            file.read_exact(&mut ident).unwrap();
             new.ident     = Ident::new(ident);
             new.etype     = Type::new(util::read_u16(file));
             new.machine   = Machine::new(util::read_u16(file));
             new.version   = Version::new(util::read_u32(file));
-            new.entry     = util::read_u64(file) as u64;
-            new.phoff     = util::read_u64(file) as u64;
-            new.shoff     = util::read_u64(file) as u64;
+            new.entry     = util::read_u64(file);
+            new.phoff     = util::read_u64(file);
+            new.shoff     = util::read_u64(file);
             new.flags     = Flags::new(util::read_u32(file));
-            new.ehsize    = util::read_u16(file) as u16;
-            new.phentsize = util::read_u16(file) as u16;
-            new.phnum     = util::read_u16(file) as u16;
-            new.shentsize = util::read_u16(file) as u16;
-            new.shnum     = util::read_u16(file) as u16;
+            new.ehsize    = util::read_u16(file);
+            new.phentsize = util::read_u16(file);
+            new.phnum     = util::read_u16(file);
+            new.shentsize = util::read_u16(file);
+            new.shnum     = util::read_u16(file);
             new.shstrndx  = util::read_u16(file) as usize;
 
             new
         }
+
+        /// Write ELF header into file **at current offset**.
+        pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            file.write_all(&self.ident.bytes)?;
+            util::write_u16(file, self.etype.value())?;
+            util::write_u16(file, self.machine.value)?;
+            util::write_u32(file, self.version.value)?;
+            util::write_u64(file, self.entry)?;
+            util::write_u64(file, self.phoff)?;
+            util::write_u64(file, self.shoff)?;
+            util::write_u32(file, self.flags.value)?;
+            util::write_u16(file, self.ehsize)?;
+            util::write_u16(file, self.phentsize)?;
+            util::write_u16(file, self.phnum)?;
+            util::write_u16(file, self.shentsize)?;
+            util::write_u16(file, self.shnum)?;
+            util::write_u16(file, self.shstrndx as u16)
+        }
     }
 }
 
@@ -0,0 +1,769 @@
+//! Regarding `.debug_info`, the tree of debugging information entries.
+//!
+//! The section is a sequence of units, compilation units mostly, each a
+//! tree of entries (DIEs). An entry has a tag telling what it describes
+//! and attributes, whose names and forms come from its abbreviation in
+//! `.debug_abbrev`.
+//!
+//! DWARF 5 units may keep their strings and addresses in tables shared
+//! by the unit, `.debug_str_offsets` and `.debug_addr`, found through the
+//! bases given by the unit entry. Address ranges are either a low and
+//! high address, or a list in `.debug_rnglists` (`.debug_ranges` before
+//! version 5).
+//!
+//! A function inlined into another is an inlined subroutine entry
+//! nested in the entry of its caller, naming it through its abstract
+//! origin and telling where it was called from. A `DebugInfo` keeps the
+//! units around for many lookups.
+
+use std::collections::HashMap;
+
+use crate::dwarf::{Dwarf, Encoding, Reader, Value, DW_FORM_IMPLICIT_CONST};
+use crate::line::{LineProgram, LineTable, Location};
+use crate::object::Object;
+
+pub const DW_TAG_ARRAY_TYPE: u64 = 0x01;
+pub const DW_TAG_CLASS_TYPE: u64 = 0x02;
+pub const DW_TAG_ENUMERATION_TYPE: u64 = 0x04;
+pub const DW_TAG_FORMAL_PARAMETER: u64 = 0x05;
+pub const DW_TAG_LEXICAL_BLOCK: u64 = 0x0b;
+pub const DW_TAG_MEMBER: u64 = 0x0d;
+pub const DW_TAG_POINTER_TYPE: u64 = 0x0f;
+pub const DW_TAG_REFERENCE_TYPE: u64 = 0x10;
+pub const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+pub const DW_TAG_STRUCTURE_TYPE: u64 = 0x13;
+pub const DW_TAG_SUBROUTINE_TYPE: u64 = 0x15;
+pub const DW_TAG_TYPEDEF: u64 = 0x16;
+pub const DW_TAG_UNION_TYPE: u64 = 0x17;
+pub const DW_TAG_INHERITANCE: u64 = 0x1c;
+pub const DW_TAG_INLINED_SUBROUTINE: u64 = 0x1d;
+pub const DW_TAG_SUBRANGE_TYPE: u64 = 0x21;
+pub const DW_TAG_BASE_TYPE: u64 = 0x24;
+pub const DW_TAG_CONST_TYPE: u64 = 0x26;
+pub const DW_TAG_ENUMERATOR: u64 = 0x28;
+pub const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+pub const DW_TAG_VARIABLE: u64 = 0x34;
+pub const DW_TAG_VOLATILE_TYPE: u64 = 0x35;
+pub const DW_TAG_RESTRICT_TYPE: u64 = 0x37;
+pub const DW_TAG_NAMESPACE: u64 = 0x39;
+pub const DW_TAG_PARTIAL_UNIT: u64 = 0x3c;
+pub const DW_TAG_TYPE_UNIT: u64 = 0x41;
+pub const DW_TAG_RVALUE_REFERENCE_TYPE: u64 = 0x42;
+pub const DW_TAG_ATOMIC_TYPE: u64 = 0x47;
+pub const DW_TAG_SKELETON_UNIT: u64 = 0x4a;
+
+pub const DW_AT_SIBLING: u64 = 0x01;
+pub const DW_AT_LOCATION: u64 = 0x02;
+pub const DW_AT_NAME: u64 = 0x03;
+pub const DW_AT_BYTE_SIZE: u64 = 0x0b;
+pub const DW_AT_BIT_OFFSET: u64 = 0x0c;
+pub const DW_AT_BIT_SIZE: u64 = 0x0d;
+pub const DW_AT_STMT_LIST: u64 = 0x10;
+pub const DW_AT_LOW_PC: u64 = 0x11;
+pub const DW_AT_HIGH_PC: u64 = 0x12;
+pub const DW_AT_LANGUAGE: u64 = 0x13;
+pub const DW_AT_COMP_DIR: u64 = 0x1b;
+pub const DW_AT_CONST_VALUE: u64 = 0x1c;
+pub const DW_AT_INLINE: u64 = 0x20;
+pub const DW_AT_PRODUCER: u64 = 0x25;
+pub const DW_AT_UPPER_BOUND: u64 = 0x2f;
+pub const DW_AT_ABSTRACT_ORIGIN: u64 = 0x31;
+pub const DW_AT_COUNT: u64 = 0x37;
+pub const DW_AT_DATA_MEMBER_LOCATION: u64 = 0x38;
+pub const DW_AT_DECL_FILE: u64 = 0x3a;
+pub const DW_AT_DECL_LINE: u64 = 0x3b;
+pub const DW_AT_DECLARATION: u64 = 0x3c;
+pub const DW_AT_ENCODING: u64 = 0x3e;
+pub const DW_AT_EXTERNAL: u64 = 0x3f;
+pub const DW_AT_SPECIFICATION: u64 = 0x47;
+pub const DW_AT_TYPE: u64 = 0x49;
+pub const DW_AT_RANGES: u64 = 0x55;
+pub const DW_AT_CALL_COLUMN: u64 = 0x57;
+pub const DW_AT_CALL_FILE: u64 = 0x58;
+pub const DW_AT_CALL_LINE: u64 = 0x59;
+pub const DW_AT_DATA_BIT_OFFSET: u64 = 0x6b;
+pub const DW_AT_LINKAGE_NAME: u64 = 0x6e;
+pub const DW_AT_STR_OFFSETS_BASE: u64 = 0x72;
+pub const DW_AT_ADDR_BASE: u64 = 0x73;
+pub const DW_AT_RNGLISTS_BASE: u64 = 0x74;
+pub const DW_AT_DWO_NAME: u64 = 0x76;
+pub const DW_AT_ALIGNMENT: u64 = 0x88;
+pub const DW_AT_LOCLISTS_BASE: u64 = 0x8c;
+pub const DW_AT_MIPS_LINKAGE_NAME: u64 = 0x2007;
+pub const DW_AT_GNU_DWO_NAME: u64 = 0x2130;
+pub const DW_AT_GNU_DWO_ID: u64 = 0x2131;
+pub const DW_AT_GNU_RANGES_BASE: u64 = 0x2132;
+pub const DW_AT_GNU_ADDR_BASE: u64 = 0x2133;
+
+pub const DW_UT_COMPILE: u8 = 0x01;
+pub const DW_UT_TYPE: u8 = 0x02;
+pub const DW_UT_PARTIAL: u8 = 0x03;
+pub const DW_UT_SKELETON: u8 = 0x04;
+pub const DW_UT_SPLIT_COMPILE: u8 = 0x05;
+pub const DW_UT_SPLIT_TYPE: u8 = 0x06;
+
+pub const DW_RLE_END_OF_LIST: u8 = 0x00;
+pub const DW_RLE_BASE_ADDRESSX: u8 = 0x01;
+pub const DW_RLE_STARTX_ENDX: u8 = 0x02;
+pub const DW_RLE_STARTX_LENGTH: u8 = 0x03;
+pub const DW_RLE_OFFSET_PAIR: u8 = 0x04;
+pub const DW_RLE_BASE_ADDRESS: u8 = 0x05;
+pub const DW_RLE_START_END: u8 = 0x06;
+pub const DW_RLE_START_LENGTH: u8 = 0x07;
+
+/// An abbreviation, the shape of entries using it.
+#[derive(Clone, Debug)]
+pub struct Abbrev {
+    /// Tag of the entries.
+    pub tag:            u64,
+    /// Wether the entries have children.
+    pub has_children:   bool,
+    /// Name, form and implicit constant of every attribute.
+    pub attrs:          Vec<(u64, u64, i64)>,
+}
+
+/// A unit of `.debug_info`, along with what its unit entry tells.
+#[derive(Clone, Debug)]
+pub struct Unit {
+    /// Offset of the unit in `.debug_info`.
+    pub offset:             u64,
+    /// How values are encoded.
+    pub encoding:           Encoding,
+    /// Unit type, as in DWARF 5 (`DW_UT_*`).
+    pub unit_type:          u8,
+    /// Identifier linking skeleton and split units.
+    pub dwo_id:             Option<u64>,
+    /// Offset of the unit entry in `.debug_info`.
+    pub entries:            u64,
+    /// Offset of the end of the unit in `.debug_info`.
+    pub end:                u64,
+    /// Abbreviations, by code.
+    pub abbrevs:            HashMap<u64, Abbrev>,
+    /// Name of the primary source file.
+    pub name:               Option<String>,
+    /// Compilation directory.
+    pub comp_dir:           Option<String>,
+    /// Name of the split unit file, for skeleton units.
+    pub dwo_name:           Option<String>,
+    /// Offset of the line program in `.debug_line`.
+    pub stmt_list:          Option<u64>,
+    /// Base address of the unit.
+    pub low_pc:             u64,
+    /// Base of the string offsets in `.debug_str_offsets`.
+    pub str_offsets_base:   u64,
+    /// Base of the addresses in `.debug_addr`.
+    pub addr_base:          u64,
+    /// Base of the range lists in `.debug_rnglists` (`.debug_ranges`
+    /// for GNU split units).
+    pub rnglists_base:      u64,
+}
+
+/// A debugging information entry.
+#[derive(Clone, Debug)]
+pub struct Die {
+    /// Offset of the entry in `.debug_info`.
+    pub offset:         u64,
+    /// Tag of the entry (`DW_TAG_*`).
+    pub tag:            u64,
+    /// Depth in the tree, zero for the unit entry.
+    pub depth:          usize,
+    /// Wether the entry has children.
+    pub has_children:   bool,
+    /// Attributes, by name (`DW_AT_*`).
+    pub attrs:          Vec<(u64, Value)>,
+}
+
+/// A frame of an address, either a function or a function inlined in
+/// it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Function name, if known.
+    pub function:   Option<String>,
+    /// Source location, where the next inner frame was inlined for
+    /// outer frames.
+    pub location:   Option<Location>,
+    /// Wether the function was inlined into the next outer frame.
+    pub inlined:    bool,
+}
+
+/// Finds the functions and inlined functions addresses belong to.
+pub struct DebugInfo<'a> {
+    /// The DWARF sections.
+    dwarf:      Dwarf<'a>,
+    /// The units, sorted by offset.
+    pub(crate) units: Vec<Unit>,
+    /// Split units loaded for skeleton units.
+    pub(crate) splits: Vec<SplitUnit<'a>>,
+}
+
+/// A split unit, from a `.dwo` file or a `.dwp` package.
+pub struct SplitUnit<'a> {
+    /// Index of its skeleton unit.
+    pub skeleton:   usize,
+    /// Its DWARF sections and the unit alone.
+    pub info:       DebugInfo<'a>,
+}
+
+/// Simple entry methods.
+impl Die {
+    /// Get the value of an attribute, if present.
+    pub fn attr(&self, name: u64) -> Option<&Value> {
+        self.attrs.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+}
+
+/// Simple debug info methods.
+impl<'a> DebugInfo<'a> {
+    /// Creates the debug info of DWARF sections.
+    pub fn new(dwarf: Dwarf<'a>) -> Self {
+        let units = dwarf.units();
+        Self { dwarf, units, splits: vec![] }
+    }
+
+    /// Get the DWARF sections.
+    pub fn dwarf(&self) -> &Dwarf<'a> {
+        &self.dwarf
+    }
+
+    /// Get the units.
+    pub fn units(&self) -> &[Unit] {
+        &self.units
+    }
+
+    /// Get the split units loaded.
+    pub fn split_units(&self) -> &[SplitUnit<'a>] {
+        &self.splits
+    }
+
+    /// Get the unit holding an offset of `.debug_info`.
+    pub fn unit_at(&self, offset: u64) -> Option<&Unit> {
+        let at = self.units.partition_point(|u| u.offset <= offset);
+        self.units[..at].last().filter(|u| offset < u.end)
+    }
+
+    /// Get the entry an attribute refers to, along with its unit.
+    pub fn reference<'u>(&'u self, unit: &'u Unit, value: &Value) -> Option<(&'u Unit, Die)> {
+        let (unit, offset) = match *value {
+            Value::Ref(off)     => (unit, unit.offset + off),
+            Value::RefAddr(off) => (self.unit_at(off)?, off),
+            _ => return None,
+        };
+        Some((unit, self.dwarf.entry(unit, offset)?))
+    }
+
+    /// Get the name of an entry, following its abstract origin or
+    /// specification.
+    ///
+    /// Linkage names are preferred, like `addr2line` does.
+    pub fn name(&self, unit: &Unit, die: &Die) -> Option<String> {
+        let mut current = (unit, die.clone());
+        /* a few hops at most, in case of cycles */
+        for _ in 0..8 {
+            let (unit, die) = &current;
+            for name in [DW_AT_LINKAGE_NAME, DW_AT_MIPS_LINKAGE_NAME, DW_AT_NAME] {
+                if let Some(value) = die.attr(name) {
+                    return self.dwarf.unit_string(unit, value);
+                }
+            }
+            let origin = die.attr(DW_AT_ABSTRACT_ORIGIN).or_else(|| die.attr(DW_AT_SPECIFICATION))?;
+            current = self.reference(unit, origin)?;
+        }
+        None
+    }
+
+    /// Get the line program of a unit.
+    ///
+    /// Programs before version 5 get the compilation directory of the
+    /// unit, so their paths are absolute.
+    pub fn line_program(&self, unit: &Unit) -> Option<LineProgram> {
+        let offset = unit.stmt_list.filter(|&off| (off as usize) < self.dwarf.line.len())?;
+        let mut program = LineProgram::parse(&self.dwarf, offset)?;
+        if program.version < 5 {
+            program.directories[0] = unit.comp_dir.clone().unwrap_or_default();
+        }
+        Some(program)
+    }
+
+    /// Get the frames of an address, the innermost first, like
+    /// `addr2line -i`.
+    ///
+    /// The outermost frame is the function holding the address, the
+    /// other ones the functions inlined in it. The location of the
+    /// innermost frame is the one of the address, the location of the
+    /// others where the next inner one was inlined. Addresses in no
+    /// function have no frames.
+    ///
+    /// The entries of skeleton units are searched in their split unit,
+    /// if loaded.
+    pub fn inline_frames(&self, addr: u64) -> Vec<Frame> {
+        for (i, unit) in self.units.iter().enumerate() {
+            if unit.unit_type == DW_UT_TYPE || unit.unit_type == DW_UT_SPLIT_TYPE {
+                continue;
+            }
+            /* units without ranges are searched anyway */
+            if let Some(root) = self.dwarf.entry(unit, unit.entries) {
+                let ranges = self.dwarf.ranges(unit, &root);
+                if !ranges.is_empty() && !covers(&ranges, addr) {
+                    continue;
+                }
+            }
+            let frames = match self.splits.iter().find(|s| s.skeleton == i) {
+                Some(split) => split.info.units.first()
+                    .and_then(|u| split.info.unit_frames(u, addr, self.line_program(unit))),
+                None => self.unit_frames(unit, addr, None),
+            };
+            if let Some(frames) = frames {
+                return frames;
+            }
+        }
+        vec![]
+    }
+
+    /// Get the frames of an address in a unit, if a function of the unit
+    /// holds it.
+    ///
+    /// Addresses are looked up in the given line program, split units
+    /// having theirs in the skeleton, or else in the one of the unit.
+    /// Call sites are looked up in the one of the unit, if any.
+    fn unit_frames(&self, unit: &Unit, addr: u64, lines: Option<LineProgram>) -> Option<Vec<Frame>> {
+        let dies = self.dwarf.entries(unit);
+        let function = dies.iter().position(|d| {
+            d.tag == DW_TAG_SUBPROGRAM && covers(&self.dwarf.ranges(unit, d), addr)
+        })?;
+        let depth = dies[function].depth;
+        let mut chain = vec![&dies[function]];
+        chain.extend(dies[function + 1..].iter()
+            .take_while(|d| d.depth > depth)
+            .filter(|d| d.tag == DW_TAG_INLINED_SUBROUTINE)
+            .filter(|d| covers(&self.dwarf.ranges(unit, d), addr)));
+
+        /* split units refer to the files of their skeleton */
+        let program = self.line_program(unit).or_else(|| lines.clone());
+        let table = LineTable::new(lines.or_else(|| program.clone()).into_iter().collect());
+        let mut location = table.lookup(addr);
+        let mut frames = vec![];
+        for die in chain.into_iter().rev() {
+            frames.push(Frame {
+                function:   self.name(unit, die),
+                location:   location.take(),
+                inlined:    die.tag == DW_TAG_INLINED_SUBROUTINE,
+            });
+            let call_file = die.attr(DW_AT_CALL_FILE).and_then(Value::udata);
+            location = program.as_ref()
+                .zip(call_file)
+                .and_then(|(program, file)| program.file_path(file))
+                .map(|file| Location {
+                    file,
+                    line:   die.attr(DW_AT_CALL_LINE).and_then(Value::udata).unwrap_or(0),
+                    column: die.attr(DW_AT_CALL_COLUMN).and_then(Value::udata).unwrap_or(0),
+                });
+        }
+        Some(frames)
+    }
+}
+
+/// Check if address ranges cover an address.
+fn covers(ranges: &[(u64, u64)], addr: u64) -> bool {
+    ranges.iter().any(|&(start, end)| start <= addr && addr < end)
+}
+
+/// Parsing methods.
+mod parsing {
+    use super::*;
+
+    impl Dwarf<'_> {
+        /// Parses the abbreviations at the given offset of
+        /// `.debug_abbrev`.
+        pub fn abbrevs(&self, offset: u64) -> HashMap<u64, Abbrev> {
+            let mut r = Reader::at(&self.abbrev, offset as usize);
+            let mut abbrevs = HashMap::new();
+            while let Some((code, abbrev)) = read_abbrev(&mut r) {
+                abbrevs.insert(code, abbrev);
+            }
+            abbrevs
+        }
+
+        /// Parses the unit headers of `.debug_info`, and their unit
+        /// entries.
+        pub fn units(&self) -> Vec<Unit> {
+            let mut units = vec![];
+            let mut offset = 0;
+            while offset < self.info.len() {
+                let mut r = Reader::at(&self.info, offset);
+                let end = match r.unit_end() {
+                    Some((end, _)) if end > r.pos => end,
+                    _ => break,
+                };
+                if let Some(unit) = self.unit(offset) {
+                    units.push(unit);
+                }
+                offset = end;
+            }
+            units
+        }
+
+        /// Parses the unit header at the given offset of `.debug_info`,
+        /// and its unit entry, unless the header is truncated.
+        fn unit(&self, offset: usize) -> Option<Unit> {
+            let mut r = Reader::at(&self.info, offset);
+            let (end, is64) = r.unit_end()?;
+            let version = r.u16()?;
+            let (unit_type, address_size, abbrev_offset) = if version >= 5 {
+                let unit_type = r.u8()?;
+                let address_size = r.u8()?;
+                (unit_type, address_size, r.offset(is64)?)
+            } else {
+                let abbrev_offset = r.offset(is64)?;
+                (DW_UT_COMPILE, r.u8()?, abbrev_offset)
+            };
+            let mut dwo_id = None;
+            match unit_type {
+                DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => dwo_id = Some(r.u64()?),
+                DW_UT_TYPE | DW_UT_SPLIT_TYPE => {
+                    let _signature = r.u64()?;
+                    let _type_offset = r.offset(is64)?;
+                }
+                _ => (),
+            }
+            if r.pos > end {
+                return None;
+            }
+
+            let mut unit = Unit {
+                offset:             offset as u64,
+                encoding:           Encoding { version, is64, address_size },
+                unit_type,
+                dwo_id,
+                entries:            r.pos as u64,
+                end:                end as u64,
+                abbrevs:            self.abbrevs(abbrev_offset),
+                name:               None,
+                comp_dir:           None,
+                dwo_name:           None,
+                stmt_list:          None,
+                low_pc:             0,
+                str_offsets_base:   0,
+                addr_base:          0,
+                rnglists_base:      0,
+            };
+            /* split units start past the header of their tables */
+            if unit_type == DW_UT_SPLIT_COMPILE || unit_type == DW_UT_SPLIT_TYPE {
+                unit.str_offsets_base = if is64 { 16 } else { 8 };
+                unit.rnglists_base = if is64 { 20 } else { 12 };
+            }
+            if let Some(root) = self.entry(&unit, unit.entries) {
+                self.read_unit_entry(&mut unit, &root);
+            }
+            Some(unit)
+        }
+
+        /// Fills a unit with what its unit entry tells.
+        ///
+        /// The bases come first, as the other attributes may need them.
+        pub(crate) fn read_unit_entry(&self, unit: &mut Unit, root: &Die) {
+            let base = |names: &[u64]| names.iter().find_map(|&n| root.attr(n)?.udata());
+            if let Some(base) = base(&[DW_AT_STR_OFFSETS_BASE]) {
+                unit.str_offsets_base = base;
+            }
+            if let Some(base) = base(&[DW_AT_ADDR_BASE, DW_AT_GNU_ADDR_BASE]) {
+                unit.addr_base = base;
+            }
+            if let Some(base) = base(&[DW_AT_RNGLISTS_BASE, DW_AT_GNU_RANGES_BASE]) {
+                unit.rnglists_base = base;
+            }
+            if let Some(Value::Data(id)) = root.attr(DW_AT_GNU_DWO_ID) {
+                unit.dwo_id = Some(*id);
+            }
+            let string = |unit: &Unit, name| root.attr(name).and_then(|v| self.unit_string(unit, v));
+            unit.name = string(unit, DW_AT_NAME);
+            unit.comp_dir = string(unit, DW_AT_COMP_DIR);
+            unit.dwo_name = string(unit, DW_AT_DWO_NAME).or_else(|| string(unit, DW_AT_GNU_DWO_NAME));
+            unit.stmt_list = root.attr(DW_AT_STMT_LIST).and_then(Value::udata);
+            unit.low_pc = root.attr(DW_AT_LOW_PC).and_then(|v| self.unit_address(unit, v)).unwrap_or(0);
+        }
+
+        /// Reads the entry at the given offset of `.debug_info`, unless
+        /// it is a null entry ending siblings.
+        pub fn entry(&self, unit: &Unit, offset: u64) -> Option<Die> {
+            let mut r = Reader::at(&self.info[..unit.end as usize], offset as usize);
+            read_entry(&mut r, unit, 0)
+        }
+
+        /// Reads every entry of a unit, in order.
+        pub fn entries(&self, unit: &Unit) -> Vec<Die> {
+            let mut r = Reader::at(&self.info[..unit.end as usize], unit.entries as usize);
+            let mut dies = vec![];
+            let mut depth = 0;
+            while !r.is_empty() {
+                match read_entry(&mut r, unit, depth) {
+                    Some(die) => {
+                        if die.has_children {
+                            depth += 1;
+                        }
+                        dies.push(die);
+                    }
+                    None if depth <= 1 => break,
+                    None => depth -= 1,
+                }
+            }
+            dies
+        }
+
+        /// Get a string attribute of a unit entry.
+        pub fn unit_string(&self, unit: &Unit, value: &Value) -> Option<String> {
+            match *value {
+                Value::Str(ref s)       => Some(s.clone()),
+                Value::Strp(off)        => Some(self.string(off)),
+                Value::LineStrp(off)    => Some(self.line_string(off)),
+                Value::StrIndex(index)  => {
+                    let size = if unit.encoding.is64 { 8 } else { 4 };
+                    let at = index.checked_mul(size)?.checked_add(unit.str_offsets_base)?;
+                    Some(self.string(Reader::at(&self.str_offsets, at as usize).offset(unit.encoding.is64)?))
+                }
+                _ => None,
+            }
+        }
+
+        /// Get an address attribute of a unit entry.
+        pub fn unit_address(&self, unit: &Unit, value: &Value) -> Option<u64> {
+            match *value {
+                Value::Addr(addr)       => Some(addr),
+                Value::AddrIndex(index) => self.indexed_address(unit, index),
+                _ => None,
+            }
+        }
+
+        /// Get an address of `.debug_addr` by its index.
+        fn indexed_address(&self, unit: &Unit, index: u64) -> Option<u64> {
+            let size = unit.encoding.address_size as u64;
+            let at = index.checked_mul(size)?.checked_add(unit.addr_base)?;
+            Reader::at(&self.addr, at as usize).address(unit.encoding.address_size)
+        }
+
+        /// Get the address ranges of an entry, empty if it has none.
+        ///
+        /// A high address given as a constant is relative to the low
+        /// one. Empty ranges are left out.
+        pub fn ranges(&self, unit: &Unit, die: &Die) -> Vec<(u64, u64)> {
+            let low = die.attr(DW_AT_LOW_PC).and_then(|v| self.unit_address(unit, v));
+            let mut ranges = match (low, die.attr(DW_AT_HIGH_PC), die.attr(DW_AT_RANGES)) {
+                (Some(low), Some(high), _) => {
+                    let high = match high {
+                        Value::Data(_) | Value::Sdata(_) => high.udata().map(|len| low.wrapping_add(len)),
+                        _ => self.unit_address(unit, high),
+                    };
+                    high.map_or(vec![], |high| vec![(low, high)])
+                }
+                (_, _, Some(list)) if unit.encoding.version >= 5 => self.rnglist(unit, list),
+                (_, _, Some(list)) => match list.udata() {
+                    Some(off) => self.range_list(unit, unit.rnglists_base + off),
+                    None      => vec![],
+                },
+                _ => vec![],
+            };
+            ranges.retain(|&(start, end)| start < end);
+            ranges
+        }
+
+        /// Reads a range list of `.debug_ranges`.
+        fn range_list(&self, unit: &Unit, offset: u64) -> Vec<(u64, u64)> {
+            let size = unit.encoding.address_size;
+            let largest = u64::MAX.checked_shr(64u32.saturating_sub(size as u32 * 8)).unwrap_or(0);
+            let mut r = Reader::at(&self.ranges, offset as usize);
+            let mut base = unit.low_pc;
+            let mut ranges = vec![];
+            while let (Some(start), Some(end)) = (r.address(size), r.address(size)) {
+                match (start, end) {
+                    (0, 0)                      => break,
+                    (s, base_address) if s == largest => base = base_address,
+                    (start, end)                => {
+                        ranges.push((base.wrapping_add(start), base.wrapping_add(end)));
+                    }
+                }
+            }
+            ranges
+        }
+
+        /// Reads a range list of `.debug_rnglists`, given by offset or
+        /// by index.
+        ///
+        /// A truncated list, or one with an entry of an unknown kind,
+        /// keeps the ranges read so far.
+        fn rnglist(&self, unit: &Unit, value: &Value) -> Vec<(u64, u64)> {
+            let (size, is64) = (unit.encoding.address_size, unit.encoding.is64);
+            let address = |index| self.indexed_address(unit, index).unwrap_or(0);
+            let mut base = unit.low_pc;
+            let mut ranges = vec![];
+            let mut read = || -> Option<()> {
+                let offset = match *value {
+                    Value::SecOffset(off)       => off,
+                    Value::RnglistIndex(index)  => {
+                        let at = index.checked_mul(if is64 { 8 } else { 4 })?.checked_add(unit.rnglists_base)?;
+                        unit.rnglists_base.checked_add(Reader::at(&self.rnglists, at as usize).offset(is64)?)?
+                    }
+                    _ => return None,
+                };
+                let mut r = Reader::at(&self.rnglists, offset as usize);
+                loop {
+                    match r.u8()? {
+                        DW_RLE_END_OF_LIST      => return Some(()),
+                        DW_RLE_BASE_ADDRESSX    => base = address(r.uleb()?),
+                        DW_RLE_STARTX_ENDX      => {
+                            let start = address(r.uleb()?);
+                            ranges.push((start, address(r.uleb()?)));
+                        }
+                        DW_RLE_STARTX_LENGTH    => {
+                            let start = address(r.uleb()?);
+                            ranges.push((start, start.wrapping_add(r.uleb()?)));
+                        }
+                        DW_RLE_OFFSET_PAIR      => {
+                            let start = base.wrapping_add(r.uleb()?);
+                            ranges.push((start, base.wrapping_add(r.uleb()?)));
+                        }
+                        DW_RLE_BASE_ADDRESS     => base = r.address(size)?,
+                        DW_RLE_START_END        => {
+                            let start = r.address(size)?;
+                            ranges.push((start, r.address(size)?));
+                        }
+                        DW_RLE_START_LENGTH     => {
+                            let start = r.address(size)?;
+                            ranges.push((start, start.wrapping_add(r.uleb()?)));
+                        }
+                        _ => return None,
+                    }
+                }
+            };
+            read();
+            ranges
+        }
+    }
+
+    /// Reads an abbreviation, unless it is the null one ending them or
+    /// it is truncated.
+    fn read_abbrev(r: &mut Reader) -> Option<(u64, Abbrev)> {
+        let code = r.uleb().filter(|&code| code != 0)?;
+        let tag = r.uleb()?;
+        let has_children = r.u8()? != 0;
+        let mut attrs = vec![];
+        loop {
+            let (name, form) = (r.uleb()?, r.uleb()?);
+            if name == 0 && form == 0 {
+                break;
+            }
+            let implicit = if form == DW_FORM_IMPLICIT_CONST { r.sleb()? } else { 0 };
+            attrs.push((name, form, implicit));
+        }
+        Some((code, Abbrev { tag, has_children, attrs }))
+    }
+
+    /// Reads an entry, unless it is a null entry.
+    ///
+    /// An unknown abbreviation or form, or a truncated entry, leaves the
+    /// rest of the unit unreadable, so the reader then skips to its end.
+    fn read_entry(r: &mut Reader, unit: &Unit, depth: usize) -> Option<Die> {
+        let offset = r.pos as u64;
+        let code = r.uleb();
+        if code == Some(0) {
+            return None;
+        }
+        let abbrev = code.and_then(|code| unit.abbrevs.get(&code));
+        let attrs = abbrev.and_then(|abbrev| abbrev.attrs.iter()
+            .map(|&(name, form, implicit)| Some((name, r.value(form, &unit.encoding, implicit)?)))
+            .collect::<Option<Vec<_>>>());
+        let (abbrev, attrs) = match (abbrev, attrs) {
+            (Some(abbrev), Some(attrs)) => (abbrev, attrs),
+            _ => {
+                r.pos = r.data().len();
+                return None;
+            }
+        };
+        Some(Die {
+            offset,
+            tag:            abbrev.tag,
+            depth,
+            has_children:   abbrev.has_children,
+            attrs,
+        })
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Parses the units of `.debug_info`.
+        pub fn debug_info(&self) -> DebugInfo<'_> {
+            DebugInfo::new(self.dwarf())
+        }
+
+        /// Get the frames of an address, the innermost first, like
+        /// `addr2line -i`.
+        ///
+        /// Split units are only loaded from the `.dwo` files their
+        /// skeletons name; use `split_debug_info` with the path of the
+        /// object for packages. Use a `DebugInfo` for many lookups.
+        pub fn inline_frames(&self, addr: u64) -> Vec<Frame> {
+            self.split_debug_info(None).inline_frames(addr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::tests::sample;
+
+    #[test]
+    fn inline_frames() {
+        for name in &["inline", "inline4"] {
+            let obj = Object::from_bytes(&sample(name));
+            let frames = obj.inline_frames(0x1048);
+            let names: Vec<_> = frames.iter().map(|f| f.function.as_deref().unwrap()).collect();
+            assert_eq!(names, ["offset", "main"], "{}", name);
+            assert!(frames[0].inlined && !frames[1].inlined);
+            assert_eq!(frames[1].location.as_ref().unwrap().line, 13);
+
+            let frames = obj.inline_frames(0x1044);
+            assert_eq!(frames.len(), 1);
+            assert!(obj.inline_frames(0x104d).is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let obj = Object::from_bytes(&sample("inline"));
+        let full = obj.dwarf();
+        for len in 0..full.info.len() {
+            let mut dwarf = obj.dwarf();
+            dwarf.info = Cow::Borrowed(&full.info[..len]);
+            DebugInfo::new(dwarf).inline_frames(0x1048);
+        }
+        for len in 0..full.abbrev.len() {
+            let mut dwarf = obj.dwarf();
+            dwarf.abbrev = Cow::Borrowed(&full.abbrev[..len]);
+            DebugInfo::new(dwarf).inline_frames(0x1048);
+        }
+    }
+
+    #[test]
+    fn unknown_range_kind() {
+        let mut obj = Object::from_bytes(&sample("inline"));
+        let ndx = obj.section_index(".debug_rnglists").unwrap();
+        let ranges = |obj: &Object| {
+            let dwarf = obj.dwarf();
+            let unit = &dwarf.units()[0];
+            dwarf.ranges(unit, &dwarf.entry(unit, unit.entries).unwrap())
+        };
+        assert_eq!(obj.sections[ndx].data[0xc], DW_RLE_START_LENGTH);
+        assert_eq!(ranges(&obj), [(0x1040, 0x104d)]);
+
+        /* in place of the end of the list, then of its only entry */
+        obj.sections[ndx].data[0x16] = 0x7f;
+        assert_eq!(ranges(&obj), [(0x1040, 0x104d)]);
+        obj.sections[ndx].data[0xc] = 0x7f;
+        assert!(ranges(&obj).is_empty());
+    }
+}
@@ -0,0 +1,495 @@
+//! Regarding loading x86-64 relocatable objects into memory and running
+//! them in process.
+//!
+//! The object is loaded into a single anonymous mapping split in three
+//! page aligned regions:
+//! - Code: executable sections, followed by one stub per external
+//!   symbol that may be called, jumping through its GOT slot.
+//! - Read only: read only sections, followed by the GOT.
+//! - Data: writable sections, `.bss` and common symbols.
+//!
+//! Every region is written and relocated before being given its final
+//! protections. External symbols are resolved through a callback, so
+//! they can live anywhere in the address space: calls go through the
+//! stubs, and GOT references through the GOT. Undefined symbols carry
+//! no type, so `PLT32` relocations always go through the stub, and
+//! `PC32` ones only when the target is out of range. Direct references
+//! to external data still need it within 2GiB, so such objects should
+//! be built with `-fPIC`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::mem;
+
+use crate::object::Object;
+use crate::rel::x86_64::*;
+use crate::section::{self, Section};
+use crate::sym::{Sym, Bind, Type as SymType, SHN_ABS, SHN_COMMON};
+use crate::sys::*;
+use crate::util;
+
+/// Page size used for region alignment.
+const PAGE: u64 = 0x1000;
+
+/// Size in bytes of a call stub (`jmp *slot(%rip)`, padded).
+const STUB_SIZE: u64 = 8;
+
+/// Name of the symbol defined at the start of the GOT.
+const GOT_SYMBOL: &str = "_GLOBAL_OFFSET_TABLE_";
+
+/// Errors found while loading.
+#[derive(Debug)]
+pub enum Error {
+    /// A symbol is referenced but the callback does not know it.
+    Undefined(String),
+    /// A relocation type is not supported.
+    Unsupported(u32),
+    /// A relocated value does not fit its field.
+    Overflow(String),
+    /// Mapping or protecting memory failed.
+    Map(io::Error),
+}
+
+/// Memory regions, in address order.
+#[derive(Clone, Copy, PartialEq)]
+enum Region {
+    Code,
+    Rodata,
+    Data,
+}
+
+/// Every memory region.
+const REGIONS: [Region; 3] = [Region::Code, Region::Rodata, Region::Data];
+
+/// An object loaded in memory.
+///
+/// The memory is unmapped when dropped, so function pointers obtained
+/// from it must not outlive it.
+pub struct Image {
+    /// Start of the mapping.
+    base:       *mut u8,
+    /// Size of the mapping.
+    len:        usize,
+    /// Address of every defined symbol.
+    symbols:    HashMap<String, u64>,
+}
+
+/// Simple region methods.
+impl Region {
+    /// Get the region of an input section, if it is loaded.
+    fn of(section: &Section) -> Option<Self> {
+        let name = section.name.as_deref().unwrap_or("");
+        if !section.is_alloc() || name.starts_with(".eh_frame") || name.starts_with(".note") {
+            return None;
+        }
+        let region = if section.flags & section::SHF_EXECINSTR != 0 {
+            Self::Code
+        } else if section.flags & section::SHF_WRITE != 0 || section.is_nobits() {
+            Self::Data
+        } else {
+            Self::Rodata
+        };
+        Some(region)
+    }
+
+    /// Get the final memory protections.
+    fn prot(&self) -> i32 {
+        match self {
+            Self::Code   => PROT_READ | PROT_EXEC,
+            Self::Rodata => PROT_READ,
+            Self::Data   => PROT_READ | PROT_WRITE,
+        }
+    }
+}
+
+/// Simple image methods.
+impl Image {
+    /// Get the address of a defined symbol.
+    ///
+    /// Global symbols are preferred over local ones of the same name.
+    pub fn symbol(&self, name: &str) -> Option<*const u8> {
+        self.symbols.get(name).map(|&addr| addr as *const u8)
+    }
+
+    /// Get a defined symbol as a function pointer of type `F`, like
+    /// `extern "C" fn(i32) -> i32`.
+    ///
+    /// # Safety
+    /// `F` must be a function pointer type matching the actual code, and
+    /// must not be called once the image is dropped.
+    pub unsafe fn function<F: Copy>(&self, name: &str) -> Option<F> {
+        assert_eq!(mem::size_of::<F>(), mem::size_of::<usize>());
+        self.symbols.get(name).map(|&addr| mem::transmute_copy(&(addr as usize)))
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.base, self.len);
+        }
+    }
+}
+
+/// Loading methods.
+mod load {
+    use super::*;
+
+    /// Layout of an image being loaded.
+    struct Layout<'a> {
+        /// Loaded object.
+        obj:        &'a Object,
+        /// Region and offset into it of every input section.
+        place:      Vec<Option<(Region, u64)>>,
+        /// Size of every region.
+        sizes:      [u64; 3],
+        /// Offset into the mapping of every region.
+        starts:     [u64; 3],
+        /// Offset into the data region of every common symbol.
+        commons:    HashMap<usize, u64>,
+        /// Offset into the read only region of the GOT slot of every
+        /// symbol referenced through it.
+        got:        HashMap<usize, u64>,
+        /// Offset into the code region of the stub of every external
+        /// symbol that may be called.
+        stubs:      HashMap<usize, u64>,
+        /// Offset into the read only region of the start of the GOT.
+        got_start:  u64,
+        /// Address of every external symbol.
+        externals:  HashMap<usize, u64>,
+    }
+
+    impl Image {
+        /// Loads a relocatable object into executable memory.
+        ///
+        /// Undefined symbols are resolved by name through `resolve`,
+        /// which gives their address. Unresolved weak symbols are null.
+        pub fn load<F>(obj: &Object, mut resolve: F) -> Result<Self, Error>
+            where F: FnMut(&str) -> Option<u64>
+        {
+            let mut layout = Layout {
+                obj,
+                place:      vec![],
+                sizes:      [0; 3],
+                starts:     [0; 3],
+                commons:    HashMap::new(),
+                got:        HashMap::new(),
+                stubs:      HashMap::new(),
+                got_start:  0,
+                externals:  HashMap::new(),
+            };
+            layout.resolve_externals(&mut resolve)?;
+            layout.place_sections();
+            layout.place_indirections();
+
+            /* map everything writable until relocated */
+            let mut len = 0;
+            for region in REGIONS.iter() {
+                layout.starts[*region as usize] = len;
+                len = util::align(len + layout.sizes[*region as usize], PAGE);
+            }
+            let len = len.max(PAGE) as usize;
+            let base = unsafe {
+                mmap(std::ptr::null_mut(), len, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0)
+            };
+            if base == MAP_FAILED {
+                return Err(Error::Map(io::Error::last_os_error()));
+            }
+            let image = Self {
+                base,
+                len,
+                symbols: HashMap::new(),
+            };
+            let mem = unsafe { std::slice::from_raw_parts_mut(base, len) };
+            let image = layout.fill(image, mem)?;
+
+            /* final protections */
+            for region in REGIONS.iter() {
+                let size = util::align(layout.sizes[*region as usize], PAGE) as usize;
+                if size == 0 {
+                    continue;
+                }
+                let start = layout.starts[*region as usize] as usize;
+                let ret = unsafe { mprotect(base.add(start), size, region.prot()) };
+                if ret != 0 {
+                    return Err(Error::Map(io::Error::last_os_error()));
+                }
+            }
+            Ok(image)
+        }
+    }
+
+    impl<'a> Layout<'a> {
+        /// Resolves every undefined symbol through the callback.
+        fn resolve_externals<F>(&mut self, resolve: &mut F) -> Result<(), Error>
+            where F: FnMut(&str) -> Option<u64>
+        {
+            for (i, sym) in self.obj.symbols().iter().enumerate().skip(1) {
+                if !sym.is_undefined() {
+                    continue;
+                }
+                let name = sym.name.as_deref().unwrap_or("");
+                if name == GOT_SYMBOL {
+                    continue;
+                }
+                let addr = match resolve(name) {
+                    Some(addr) => addr,
+                    None if sym.bind == Bind::Weak => 0,
+                    None => return Err(Error::Undefined(name.to_string())),
+                };
+                self.externals.insert(i, addr);
+            }
+            Ok(())
+        }
+
+        /// Reserves room in a region, and gets its offset.
+        fn reserve(&mut self, region: Region, size: u64, align: u64) -> u64 {
+            let off = util::align(self.sizes[region as usize], align.max(1));
+            self.sizes[region as usize] = off + size;
+            off
+        }
+
+        /// Gives every loaded section and common symbol its offset.
+        fn place_sections(&mut self) {
+            for section in self.obj.sections() {
+                let place = Region::of(section).map(|region| {
+                    (region, self.reserve(region, section.size, section.addralign as u64))
+                });
+                self.place.push(place);
+            }
+            for (i, sym) in self.obj.symbols().iter().enumerate() {
+                if sym.shndx == SHN_COMMON {
+                    /* for commons, the value holds the alignment */
+                    let off = self.reserve(Region::Data, sym.size, sym.value);
+                    self.commons.insert(i, off);
+                }
+            }
+        }
+
+        /// Allocates GOT slots and call stubs.
+        ///
+        /// Every external symbol referenced through the GOT gets a slot.
+        /// Those referenced by `PLT32` or `PC32`, unless known to be data,
+        /// also get a slot and a stub, as the distance to them is only
+        /// known once mapped.
+        fn place_indirections(&mut self) {
+            self.got_start = self.reserve(Region::Rodata, 0, 8);
+            for (_, rela) in self.relocations() {
+                let external = self.externals.contains_key(&rela.sym);
+                let data = self.obj.symbols()[rela.sym].etype == SymType::Object;
+                let stub = external && !data
+                    && matches!(rela.rtype, R_X86_64_PLT32 | R_X86_64_PC32);
+                let got = stub || matches!(rela.rtype,
+                    R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX);
+
+                if got && !self.got.contains_key(&rela.sym) {
+                    let off = self.reserve(Region::Rodata, 8, 8);
+                    self.got.insert(rela.sym, off);
+                }
+                if stub && !self.stubs.contains_key(&rela.sym) {
+                    let off = self.reserve(Region::Code, STUB_SIZE, STUB_SIZE);
+                    self.stubs.insert(rela.sym, off);
+                }
+            }
+        }
+
+        /// Get every relocation applying to a loaded section, along with
+        /// the index of that section.
+        fn relocations(&self) -> Vec<(usize, crate::rel::Rela)> {
+            let mut relocs = vec![];
+            for (i, section) in self.obj.sections().iter().enumerate() {
+                let target = section.info as usize;
+                if !section.is_rela() || Region::of(&self.obj.sections()[target]).is_none() {
+                    continue;
+                }
+                for rela in self.obj.relocations(i) {
+                    relocs.push((target, rela));
+                }
+            }
+            relocs
+        }
+
+        /// Get the address of a region offset.
+        fn addr(&self, image: &Image, region: Region, off: u64) -> u64 {
+            image.base as u64 + self.starts[region as usize] + off
+        }
+
+        /// Get the address of the symbol with index `i`.
+        fn sym_addr(&self, image: &Image, i: usize) -> u64 {
+            let sym = &self.obj.symbols()[i];
+            if let Some(&addr) = self.externals.get(&i) {
+                return addr;
+            }
+            if sym.is_undefined() && sym.name.as_deref() == Some(GOT_SYMBOL) {
+                return self.addr(image, Region::Rodata, self.got_start);
+            }
+            match sym.shndx {
+                SHN_ABS    => sym.value,
+                SHN_COMMON => self.addr(image, Region::Data, self.commons[&i]),
+                ndx => match self.place.get(ndx).copied().flatten() {
+                    Some((region, off)) => self.addr(image, region, off + sym.value),
+                    None => 0,
+                },
+            }
+        }
+
+        /// Copies the sections in, fills the GOT and stubs, applies the
+        /// relocations and records the symbol addresses.
+        fn fill(&self, mut image: Image, mem: &mut [u8]) -> Result<Image, Error> {
+            for (section, place) in self.obj.sections().iter().zip(&self.place) {
+                if let (Some((region, off)), false) = (place, section.is_nobits()) {
+                    let start = (self.starts[*region as usize] + off) as usize;
+                    mem[start..start + section.data.len()].copy_from_slice(&section.data);
+                }
+            }
+
+            for (&i, &off) in &self.got {
+                let start = (self.starts[Region::Rodata as usize] + off) as usize;
+                let value = self.sym_addr(&image, i);
+                mem[start..start + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            for (&i, &off) in &self.stubs {
+                /* jmp *slot(%rip) */
+                let p = self.addr(&image, Region::Code, off + 6);
+                let g = self.addr(&image, Region::Rodata, self.got[&i]);
+                let start = (self.starts[Region::Code as usize] + off) as usize;
+                mem[start..start + 2].copy_from_slice(&[0xff, 0x25]);
+                let rel = g.wrapping_sub(p) as u32;
+                mem[start + 2..start + 6].copy_from_slice(&rel.to_le_bytes());
+                mem[start + 6..start + 8].copy_from_slice(&[0xcc, 0xcc]);
+            }
+
+            for (target, rela) in self.relocations() {
+                let (region, off) = self.place[target].unwrap();
+                let off = off + rela.offset;
+                let p = self.addr(&image, region, off);
+                let a = rela.addend;
+                let s = self.sym_addr(&image, rela.sym);
+                let stub = match rela.rtype {
+                    R_X86_64_PLT32 => self.stubs.get(&rela.sym),
+                    /* direct references only need the stub when too far */
+                    R_X86_64_PC32 => self.stubs.get(&rela.sym).filter(|_| {
+                        let value = s.wrapping_add(a as u64).wrapping_sub(p);
+                        value as i64 != value as i32 as i64
+                    }),
+                    _ => None,
+                };
+                let s = match stub {
+                    Some(&stub) => self.addr(&image, Region::Code, stub),
+                    None        => s,
+                };
+
+                let (value, size, signed) = match rela.rtype {
+                    R_X86_64_NONE => continue,
+                    R_X86_64_64 => (s.wrapping_add(a as u64), 8, false),
+                    R_X86_64_PC64 => (s.wrapping_add(a as u64).wrapping_sub(p), 8, true),
+                    R_X86_64_PC32 | R_X86_64_PLT32 => {
+                        (s.wrapping_add(a as u64).wrapping_sub(p), 4, true)
+                    }
+                    R_X86_64_32 => (s.wrapping_add(a as u64), 4, false),
+                    R_X86_64_32S => (s.wrapping_add(a as u64), 4, true),
+                    R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                        let g = self.addr(&image, Region::Rodata, self.got[&rela.sym]);
+                        (g.wrapping_add(a as u64).wrapping_sub(p), 4, true)
+                    }
+                    t => return Err(Error::Unsupported(t)),
+                };
+
+                /* check the value fits in its field */
+                let fits = match (size, signed) {
+                    (8, _)     => true,
+                    (_, true)  => value as i64 == value as i32 as i64,
+                    (_, false) => value >> 32 == 0,
+                };
+                if !fits {
+                    let sym = &self.obj.symbols()[rela.sym];
+                    return Err(Error::Overflow(sym.name.clone().unwrap_or_default()));
+                }
+
+                let start = (self.starts[region as usize] + off) as usize;
+                mem[start..start + size].copy_from_slice(&value.to_le_bytes()[..size]);
+            }
+
+            /* globals override locals of the same name */
+            let mut syms: Vec<(usize, &Sym)> = self.obj.symbols().iter().enumerate()
+                .filter(|(_, s)| !s.is_undefined() && !s.is_section())
+                .filter(|(_, s)| s.etype != SymType::File)
+                .collect();
+            syms.sort_by_key(|(_, s)| !s.is_local());
+            for (i, sym) in syms {
+                let name = sym.name.clone().unwrap_or_default();
+                image.symbols.insert(name, self.sym_addr(&image, i));
+            }
+            Ok(image)
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe a load error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Undefined(name)  => write!(f, "undefined symbol `{}`", name),
+                Error::Unsupported(t)   => write!(f, "unsupported relocation type {}", t),
+                Error::Overflow(name)   => write!(f, "relocation against `{}` overflows", name),
+                Error::Map(err)         => write!(f, "cannot map memory: {}", err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ObjectBuilder;
+    use crate::header::EM_X86_64;
+    use crate::tests::sample;
+
+    /// External function for the loaded code to call.
+    extern "C" fn answer() -> i32 {
+        42
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn call_sample_functions() {
+        let obj = Object::from_bytes(&sample("main.o"));
+        let image = Image::load(&obj, |_| None).unwrap();
+        unsafe {
+            let global_f: extern "C" fn(i32) -> i32 = image.function("global_f").unwrap();
+            assert_eq!(global_f(7), 42);
+            let main: extern "C" fn() -> i32 = image.function("main").unwrap();
+            assert_eq!(main(), 105);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn call_untyped_externals() {
+        for rtype in &[R_X86_64_PLT32, R_X86_64_PC32] {
+            let mut b = ObjectBuilder::new(EM_X86_64);
+            let text = b.add_section(".text", section::SHF_ALLOC | section::SHF_EXECINSTR,
+                vec![0xe8, 0, 0, 0, 0, 0xc3], 16);
+            b.define_symbol("f", Bind::Global, SymType::Func, text, 0, 6);
+            let answer_sym = b.declare_symbol("answer");
+            b.add_relocation(text, 1, answer_sym, *rtype, -4);
+            let obj = b.build();
+            assert!(obj.symbols().iter().any(|s| s.is_undefined() && s.etype == SymType::NoType));
+
+            let image = Image::load(&obj, |name| match name {
+                "answer" => Some(answer as *const () as u64),
+                _        => None,
+            }).unwrap();
+            unsafe {
+                let f: extern "C" fn() -> i32 = image.function("f").unwrap();
+                assert_eq!(f(), 42);
+            }
+        }
+    }
+}
@@ -1,25 +1,75 @@
 mod util;
-mod header;
-mod segment;
-mod section;
-mod sym;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod sys;
+mod digest;
+pub mod header;
+pub mod segment;
+pub mod section;
+pub mod sym;
+pub mod version;
+pub mod hash;
+pub mod rel;
+pub mod builder;
+pub mod link;
+pub mod partial;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod jit;
+pub mod plan;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod exec;
+pub mod archive;
+pub mod dynamic;
+pub mod patch;
+pub mod objcopy;
+pub mod strip;
+pub mod firmware;
+pub mod note;
+pub mod buildid;
+pub mod compress;
+pub mod minidebug;
+pub mod symbolize;
+pub mod debugfile;
+pub mod dwarf;
+pub mod line;
+pub mod info;
+pub mod types;
+pub mod cfi;
+pub mod split;
 
 pub mod object {
     use super::header::Header;
     use super::section::Section;
     use super::segment::Segment;
     use super::sym::Sym;
+    use super::version::{Verdef, Verneed};
+    use super::hash::{SysvHash, GnuHash};
 
     /// Represents a whole object file.
     pub struct Object {
         /// Main ELF header.
-        header: Header,
+        pub(crate) header: Header,
         /// Sections contained in the object file.
-        sections: Vec<Section>,
+        pub(crate) sections: Vec<Section>,
         /// Segments contained in the object file.
-        segments: Vec<Segment>,
+        pub(crate) segments: Vec<Segment>,
         /// Symbols contained in the object file.
-        symbols: Vec<Sym>,
+        pub(crate) symbols: Vec<Sym>,
+        /// Dynamic symbols contained in the object file.
+        pub(crate) dynsyms: Vec<Sym>,
+        /// Version definitions contained in the object file.
+        pub(crate) verdefs: Vec<Verdef>,
+        /// Version requirements contained in the object file.
+        pub(crate) verneeds: Vec<Verneed>,
+        /// System V hash table of the dynamic symbols.
+        pub(crate) sysv_hash: Option<SysvHash>,
+        /// GNU hash table of the dynamic symbols.
+        pub(crate) gnu_hash: Option<GnuHash>,
+        /// Bytes of the file no header or section owns, by offset, written
+        /// back unless the layout moved something over them.
+        pub(crate) unowned: Vec<(u64, Vec<u8>)>,
+        /// Bytes of the file past the last header or section, written
+        /// back after them.
+        pub(crate) trailing: Vec<u8>,
     }
 
     /// Simple object methods.
@@ -30,10 +80,54 @@ pub mod object {
                 header:     Header::empty(),
                 sections:   vec![],
                 symbols:    vec![],
+                dynsyms:    vec![],
                 segments:   vec![],
+                verdefs:    vec![],
+                verneeds:   vec![],
+                sysv_hash:  None,
+                gnu_hash:   None,
+                unowned:    vec![],
+                trailing:   vec![],
             }
         }
 
+        /// Get the main ELF header.
+        pub fn header(&self) -> &Header {
+            &self.header
+        }
+
+        /// Get the sections of the object file.
+        pub fn sections(&self) -> &[Section] {
+            &self.sections
+        }
+
+        /// Get the sections of the object file, for editing.
+        ///
+        /// Call `layout` after changing their contents.
+        pub fn sections_mut(&mut self) -> &mut Vec<Section> {
+            &mut self.sections
+        }
+
+        /// Get the segments of the object file.
+        pub fn segments(&self) -> &[Segment] {
+            &self.segments
+        }
+
+        /// Get the index of the section with the given name.
+        pub fn section_index(&self, name: &str) -> Option<usize> {
+            self.sections.iter()
+                .position(|s| s.name.as_deref() == Some(name))
+        }
+
+        /// Get the symbols of the object file.
+        pub fn symbols(&self) -> &[Sym] {
+            &self.symbols
+        }
+
+        /// Get the dynamic symbols of the object file.
+        pub fn dynsyms(&self) -> &[Sym] {
+            &self.dynsyms
+        }
     }
 
     /// Format methods.
@@ -95,6 +189,24 @@ pub mod object {
                     println!("  {0:#010x} {1: <10} {2: <10} {3: <30}",
                         val, bind, t, name);
                 }
+
+                if self.dynsyms.is_empty() {
+                    return;
+                }
+
+                println!("\n <> DYNAMIC SYMBOLS\n");
+                println!("  {0: <10} {1: <10} {2: <10} {3: <30}\n",
+                    "value", "bind", "type", "name");
+
+                for s in &self.dynsyms {
+                    let name = s.versioned_name();
+                    let val  = &s.value;
+                    let bind = s.bind_str();
+                    let t    = s.type_str();
+
+                    println!("  {0:#010x} {1: <10} {2: <10} {3: <30}",
+                        val, bind, t, name);
+                }
             }
         }
     }
@@ -102,9 +214,10 @@ pub mod object {
     /// File IO methods.
     mod io {
         use std::fs::File;
-        use std::io::{Seek,SeekFrom};
+        use std::io::{self,Read,Seek,SeekFrom,Write};
         use super::*;
         use super::super::util;
+        use super::super::version;
 
         impl Object {
             /// Generates a complete object file representation
@@ -114,15 +227,257 @@ pub mod object {
                 Self::extract(&mut file)
             }
 
+            /// Generates a complete object file representation
+            /// from the contents of a file, like an archive member.
+            pub fn from_bytes(data: &[u8]) -> Self {
+                Self::extract(&mut io::Cursor::new(data))
+            }
+
+            /// Check if contents can be given to `from_bytes`.
+            ///
+            /// They must be a 64-bit little endian ELF file, with its
+            /// header tables, section contents and section names within
+            /// the file, and sized symbol table entries.
+            pub fn is_readable(data: &[u8]) -> bool {
+                let len = data.len() as u64;
+                if len < 64 || !data.starts_with(b"\x7fELF") || data[4] != 2 || data[5] != 1 {
+                    return false;
+                }
+                /* program and section header tables */
+                let table = |off: usize, entsize: usize, count: usize, size: u64| {
+                    let num = util::get_u16(data, count) as u64;
+                    let end = util::get_u64(data, off).checked_add(num * size);
+                    num == 0 || (util::get_u16(data, entsize) as u64 == size
+                        && end.is_some_and(|e| e <= len))
+                };
+                if !table(0x20, 0x36, 0x38, 56) || !table(0x28, 0x3a, 0x3c, 64) {
+                    return false;
+                }
+
+                /* section contents and names */
+                let shoff = util::get_u64(data, 0x28) as usize;
+                let shnum = util::get_u16(data, 0x3c) as usize;
+                let shstrndx = util::get_u16(data, 0x3e) as usize;
+                let sections: Vec<Section> = (0..shnum)
+                    .map(|i| Section::extract(&mut &data[shoff + i * 64..]))
+                    .collect();
+                let within = |s: &Section| s.is_nobits()
+                    || s.offset.checked_add(s.size).is_some_and(|e| e <= len);
+                if !sections.iter().all(within) {
+                    return false;
+                }
+                if sections.iter().any(|s| (s.is_symtab() || s.is_dynsym()) && s.entsize < 24) {
+                    return false;
+                }
+                if shnum == 0 {
+                    return true;
+                }
+                let strtab = match sections.get(shstrndx) {
+                    Some(s) if !s.is_nobits() => &data[s.offset as usize..][..s.size as usize],
+                    _                          => return false,
+                };
+                sections.iter().all(|s| {
+                    let name = strtab.get(s.nameoff..).unwrap_or(&[]);
+                    name.iter().position(|&c| c == 0)
+                        .is_some_and(|end| std::str::from_utf8(&name[..end]).is_ok())
+                })
+            }
+
+            /// Writes the object into a file with the given name.
+            pub fn to_file(&self, filename: &str) -> io::Result<()> {
+                let mut file = File::create(filename)?;
+                self.write_to(&mut file)
+            }
+
+            /// Recomputes the file layout after the object was edited.
+            ///
+            /// - Section names are written into the section header
+            ///   string table, rebuilding it if they no longer match.
+            /// - Section sizes are taken from their contents.
+            /// - Allocated sections of executables and shared objects keep
+            ///   their offsets, since they are tied to their addresses.
+            /// - Every other section is packed after them, in file order,
+            ///   followed by the section headers.
+            /// - Bytes no section owns are dropped where sections are
+            ///   packed, and trailing bytes follow the section headers.
+            ///
+            /// An unmodified object keeps its original layout.
+            pub fn layout(&mut self) {
+                self.layout_names();
+
+                /* sizes */
+                for section in &mut self.sections {
+                    if !section.is_nobits() {
+                        section.size = section.data.len() as u64;
+                    }
+                }
+
+                /* fixed sections go first, the rest follow in file order */
+                let fixed = |s: &Section| s.is_alloc() && !self.header.is_rel();
+                let phsize = self.header.phentsize as u64 * self.segments.len() as u64;
+                let mut pos = (self.header.phoff + phsize).max(64);
+                let mut order = vec![];
+                for (i, section) in self.sections.iter().enumerate().skip(1) {
+                    if fixed(section) {
+                        let size = if section.is_nobits() { 0 } else { section.size };
+                        pos = pos.max(section.offset + size);
+                    } else {
+                        order.push(i);
+                    }
+                }
+                order.sort_by_key(|&i| self.sections[i].offset);
+                let packed = pos;
+                self.unowned.retain(|(off, _)| *off < packed);
+                for (off, data) in &mut self.unowned {
+                    data.truncate((packed - *off) as usize);
+                }
+                for i in order {
+                    let section = &mut self.sections[i];
+                    let align = section.addralign.max(1) as u64;
+                    section.offset = util::align(pos, align);
+                    if !section.is_nobits() {
+                        pos = section.offset + section.size;
+                    }
+                }
+
+                /* section headers */
+                self.header.shoff = util::align(pos, 8);
+                self.header.shnum = self.sections.len() as u16;
+                self.header.phnum = self.segments.len() as u16;
+            }
+
+            /// Writes every section name into the section header string
+            /// table, rebuilding it when a name does not match its offset.
+            fn layout_names(&mut self) {
+                let ndx = self.header.shstrndx;
+                if ndx == 0 || ndx >= self.sections.len() {
+                    return;
+                }
+                let strtab = &self.sections[ndx].data;
+                let stale = self.sections.iter().any(|s| {
+                    let name = s.name.as_deref().unwrap_or("");
+                    s.nameoff >= strtab.len() || util::get_str(strtab, s.nameoff) != name
+                });
+                if !stale {
+                    return;
+                }
+
+                /* a table shared with the symbols is rebuilt with them */
+                if self.sections.iter().any(|s| s.is_symtab() && s.link == ndx) {
+                    self.write_symbols();
+                    return;
+                }
+
+                /* rebuild the table */
+                let mut strtab = util::StrTab::new();
+                for section in &mut self.sections {
+                    section.nameoff = strtab.add(section.name.as_deref().unwrap_or(""));
+                }
+                self.sections[ndx].data = strtab.data;
+            }
+
+            /// Serializes the object into the given file.
+            ///
+            /// Everything is written at the offsets currently recorded in
+            /// the object:
+            /// - First the main ELF header.
+            /// - Then the program headers, at `phoff`.
+            /// - Then the contents of each section, at its `offset`.
+            /// - Then the section headers, at `shoff`.
+            /// - Then the trailing bytes of the original file, if any.
+            ///
+            /// Bytes of the original file no header or section owns are
+            /// written first, and any gap left is zero filled, so an
+            /// unmodified object is written back byte for byte.
+            pub fn write_to<W: Write + Seek>(&self, file: &mut W) -> io::Result<()> {
+                /* unowned bytes, under everything else */
+                for (off, data) in &self.unowned {
+                    file.seek(SeekFrom::Start(*off))?;
+                    file.write_all(data)?;
+                }
+
+                /* main header */
+                file.seek(SeekFrom::Start(0))?;
+                self.header.write(file)?;
+
+                /* program headers */
+                let sz = self.header.phentsize as u64;
+                for (i, segment) in self.segments.iter().enumerate() {
+                    file.seek(SeekFrom::Start(self.header.phoff + sz * i as u64))?;
+                    segment.write(file)?;
+                }
+
+                /* section contents */
+                for section in &self.sections {
+                    if section.is_nobits() || section.data.is_empty() {
+                        continue;
+                    }
+                    file.seek(SeekFrom::Start(section.offset))?;
+                    file.write_all(&section.data)?;
+                }
+
+                /* section headers */
+                let sz = self.header.shentsize as u64;
+                for (i, section) in self.sections.iter().enumerate() {
+                    file.seek(SeekFrom::Start(self.header.shoff + sz * i as u64))?;
+                    section.write(file)?;
+                }
+
+                /* trailing bytes */
+                if !self.trailing.is_empty() {
+                    file.seek(SeekFrom::Start(self.owned_ranges().last().map_or(0, |r| r.1)))?;
+                    file.write_all(&self.trailing)?;
+                }
+
+                file.flush()
+            }
+
+            /// Get the size of the file `write_to` writes.
+            pub(crate) fn file_size(&self) -> u64 {
+                let owned = self.owned_ranges().last().map_or(0, |r| r.1);
+                self.unowned.iter()
+                    .map(|(off, data)| off + data.len() as u64)
+                    .fold(owned + self.trailing.len() as u64, u64::max)
+            }
+
+            /// Get the file ranges headers and sections own, sorted and
+            /// merged.
+            fn owned_ranges(&self) -> Vec<(u64, u64)> {
+                let phsize = self.header.phentsize as u64 * self.segments.len() as u64;
+                let shsize = self.header.shentsize as u64 * self.sections.len() as u64;
+                let mut ranges = vec![
+                    (0, 64),
+                    (self.header.phoff, self.header.phoff + phsize),
+                    (self.header.shoff, self.header.shoff + shsize),
+                ];
+                for section in &self.sections {
+                    if !section.is_nobits() {
+                        ranges.push((section.offset, section.offset + section.size));
+                    }
+                }
+                ranges.retain(|r| r.0 < r.1);
+                ranges.sort_unstable();
+
+                let mut merged: Vec<(u64, u64)> = vec![];
+                for (start, end) in ranges {
+                    match merged.last_mut() {
+                        Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                        _ => merged.push((start, end)),
+                    }
+                }
+                merged
+            }
+
             /// Extracts a complete `Object` from given file.
             ///
             /// Will use the given file, to extract all the information it
             /// needs.
             /// - First the main ELF header.
-            /// - Then all sections.
-            /// - Then all symbols.
-            /// - Then all the names for these.
-            fn extract(file: &mut File) -> Self {
+            /// - Then all sections, their contents and names.
+            /// - Then all symbols and dynamic symbols.
+            /// - Then all segments.
+            /// - Then symbol versions and hash tables.
+            fn extract<R: Read + Seek>(file: &mut R) -> Self {
                 /* init default object */
                 let mut new = Self::empty();
 
@@ -130,17 +485,21 @@ pub mod object {
                 new.extract_header(file);
                 assert!(new.header.valid());
                 new.extract_sections(file);
+                new.extract_section_data(file);
+                new.extract_section_names(file);
                 new.extract_symbols(file);
+                new.extract_dynsyms(file);
                 new.extract_segments(file);
-                new.extract_section_names(file);
-                new.extract_symbol_names(file);
+                new.extract_versions();
+                new.extract_hash_tables();
+                new.extract_unowned(file);
 
                 new
             }
 
             /// Populates the object's ELf header with the info
             /// extracted from the given file.
-            fn extract_header(&mut self, file: &mut File) {
+            fn extract_header<R: Read + Seek>(&mut self, file: &mut R) {
                 /* go to beginning of file */
                 file.seek(SeekFrom::Start(0)).unwrap();
                 /* extract header */
@@ -154,7 +513,7 @@ pub mod object {
             /// `self.header`.
             ///
             /// - **Requires a valid ELF header to have been loaded first.**
-            fn extract_sections(&mut self, file: &mut File) {
+            fn extract_sections<R: Read + Seek>(&mut self, file: &mut R) {
                 let off = self.header.shoff;
                 let sz  = self.header.shentsize as u64;
                 let num = self.header.shnum as u64;
@@ -180,7 +539,7 @@ pub mod object {
             /// `self.header`.
             ///
             /// - **Requires a valid ELF header to have been loaded first.**
-            fn extract_segments(&mut self, file: &mut File) {
+            fn extract_segments<R: Read + Seek>(&mut self, file: &mut R) {
                 let off = self.header.phoff;
                 let sz  = self.header.phentsize as u64;
                 let num = self.header.phnum as u64;
@@ -199,85 +558,172 @@ pub mod object {
                 }
             }
 
+            /// Populates the contents of every section in `self.sections`
+            /// from the given file.
+            ///
+            /// - **Requires a valid sections vector to have been loaded first.**
+            fn extract_section_data<R: Read + Seek>(&mut self, file: &mut R) {
+                for section in &mut self.sections {
+                    if section.is_nobits() {
+                        continue;
+                    }
+                    file.seek(SeekFrom::Start(section.offset)).unwrap();
+                    section.data = util::read_bytes(file, section.size as usize);
+                }
+            }
+
             /// Populates the object's symbols vector with the info
             /// extracted from the given file.
             ///
-            /// Will extract symbols based on the values of the sections
-            /// vector `self.sections`.
+            /// Will extract symbols from the first symbol table found in
+            /// `self.sections`, if any.
             ///
             /// - **Requires a valid ELF header to have been loaded first.**
             /// - **Requires a valid sections vector to have been loaded first.**
-            fn extract_symbols(&mut self, file: &mut File) {
-                /* find symtab section */
-                let mut i = 0;
-                let count = self.sections.len();
-                let symtab = loop {
-                    let section = &self.sections[i];
-                    if section.is_symtab() {
-                        break section;
-                    }
-                    i += 1;
-                    if i >= count {
-                        panic!("symtab not found");
-                    }
-                };
+            fn extract_symbols<R: Read + Seek>(&mut self, file: &mut R) {
+                self.symbols.clear();
+                let ndx = self.sections.iter().position(|s| s.is_symtab());
+                if let Some(ndx) = ndx {
+                    self.symbols = self.extract_symbol_table(file, ndx);
+                }
+            }
 
+            /// Populates the object's dynamic symbols vector with the info
+            /// extracted from the given file.
+            ///
+            /// - **Requires a valid ELF header to have been loaded first.**
+            /// - **Requires a valid sections vector to have been loaded first.**
+            fn extract_dynsyms<R: Read + Seek>(&mut self, file: &mut R) {
+                self.dynsyms.clear();
+                let ndx = self.sections.iter().position(|s| s.is_dynsym());
+                if let Some(ndx) = ndx {
+                    self.dynsyms = self.extract_symbol_table(file, ndx);
+                }
+            }
+
+            /// Extracts every symbol, with its name, from the symbol table
+            /// section with the given index.
+            ///
+            /// - **Requires all sections to be loaded, with names.**
+            fn extract_symbol_table<R: Read + Seek>(&self, file: &mut R, ndx: usize) -> Vec<Sym> {
+                let symtab = &self.sections[ndx];
                 let off   = symtab.offset;      // offset into sym table
                 let entsz = symtab.entsize;     // bytes size of symbol entry
                 let num   = symtab.size/entsz;  // amount of symbols
 
+                /* symbol names come from the linked string table, if any */
+                let strtab = match self.sections.get(symtab.link) {
+                    Some(strtab) if strtab.is_strtab() => &strtab.data[..],
+                    _                                   => &[],
+                };
+
                 /* extract each symbol */
-                self.symbols.clear();
+                let mut syms = vec![];
                 let mut i = 0u64;
                 while i < num {
                     /* seek into next entry */
                     let curr = off + i * entsz;
                     file.seek(SeekFrom::Start(curr)).unwrap();
                     /* extract entry */
-                    let sym = Sym::extract(file);
-                    self.symbols.push(sym);
+                    let mut sym = Sym::extract(file);
+                    sym.name = Some(self.symbol_name(&sym, strtab));
+                    syms.push(sym);
                     i += 1;
                 }
+                syms
             }
 
-            /// Will update all the sections in `self.sections` by extracting
-            /// their name from the given file.
+            /// Populates the version info of the object, and of each of
+            /// its dynamic symbols.
             ///
-            /// - **Requires a valid ELF header to have been loaded first.**
-            /// - **Requires a valid sections vector to have been loaded first.**
-            fn extract_section_names(&mut self, file: &mut File) {
-                let num = self.sections.len();
-                let mut i = 0;
-                /* extract each name */
-                while i < num {
-                    let name = self.extract_section_name(file, i);
-                    self.sections[i].name = Some(name);
-                    i += 1;
+            /// - **Requires all sections to be loaded, with contents.**
+            /// - **Requires all dynamic symbols to be loaded.**
+            fn extract_versions(&mut self) {
+                for section in &self.sections {
+                    let strtab = self.sections.get(section.link).map_or(&[][..], |s| &s.data);
+                    if section.is_verdef() {
+                        self.verdefs = Verdef::extract_all(&section.data, strtab);
+                    } else if section.is_verneed() {
+                        self.verneeds = Verneed::extract_all(&section.data, strtab);
+                    }
+                }
+
+                /* one versym entry per dynamic symbol, the ones past the
+                 * end of a short table being left unversioned
+                 */
+                let versym = match self.sections.iter().find(|s| s.is_versym()) {
+                    Some(section) => &section.data,
+                    None          => return,
+                };
+                let mut versions = vec![];
+                for i in 0..self.dynsyms.len().min(versym.len() / 2) {
+                    let ndx = util::get_u16(versym, i * 2);
+                    let name = self.version_name(ndx).map(|(v, _)| v.to_string());
+                    versions.push((name, ndx & version::VERSYM_HIDDEN != 0));
+                }
+                for (sym, (name, hidden)) in self.dynsyms.iter_mut().zip(versions) {
+                    sym.version = name;
+                    sym.hidden  = hidden;
                 }
             }
 
-            /// Will update all the symbols in `self.symbols` by extracting
+            /// Keeps the bytes of the file no header or section owns, so
+            /// they are written back.
+            ///
+            /// - **Requires all headers and sections to be loaded.**
+            fn extract_unowned<R: Read + Seek>(&mut self, file: &mut R) {
+                let len = file.seek(SeekFrom::End(0)).unwrap();
+                let mut pos = 0;
+                for (start, end) in self.owned_ranges() {
+                    let start = start.min(len);
+                    if start > pos {
+                        file.seek(SeekFrom::Start(pos)).unwrap();
+                        let data = util::read_bytes(file, (start - pos) as usize);
+                        self.unowned.push((pos, data));
+                    }
+                    pos = pos.max(end);
+                }
+                if len > pos {
+                    file.seek(SeekFrom::Start(pos)).unwrap();
+                    self.trailing = util::read_bytes(file, (len - pos) as usize);
+                }
+            }
+
+            /// Populates the hash tables of the dynamic symbols.
+            ///
+            /// - **Requires all sections to be loaded, with contents.**
+            /// - **Requires all dynamic symbols to be loaded.**
+            fn extract_hash_tables(&mut self) {
+                for section in &self.sections {
+                    if section.is_hash() {
+                        self.sysv_hash = Some(SysvHash::extract(&section.data));
+                    } else if section.is_gnu_hash() {
+                        let nsyms = self.dynsyms.len();
+                        self.gnu_hash = Some(GnuHash::extract(&section.data, nsyms));
+                    }
+                }
+            }
+
+            /// Will update all the sections in `self.sections` by extracting
             /// their name from the given file.
             ///
             /// - **Requires a valid ELF header to have been loaded first.**
             /// - **Requires a valid sections vector to have been loaded first.**
-            /// - **Requires a valid symbols vector to have been loaded first.**
-            fn extract_symbol_names(&mut self, file: &mut File) {
-                let num = self.symbols.len();
+            fn extract_section_names<R: Read + Seek>(&mut self, file: &mut R) {
+                let num = self.sections.len();
                 let mut i = 0;
                 /* extract each name */
                 while i < num {
-                    let name = self.extract_symbol_name(file, i);
-                    self.symbols[i].name = Some(name);
+                    let name = self.extract_section_name(file, i);
+                    self.sections[i].name = Some(name);
                     i += 1;
                 }
             }
 
-
             /// Extracts the name of a section by the section index given.
             ///
             /// **Requires all sections to be loaded**
-            fn extract_section_name(&self, file: &mut File, ndx: usize) -> String {
+            fn extract_section_name<R: Read + Seek>(&self, file: &mut R, ndx: usize) -> String {
                 let section = &self.sections[ndx];        // the section we want
                 let nameoff = section.nameoff;            // offset into name
                 let tabndx  = self.header.shstrndx;       // index for str-table
@@ -301,13 +747,10 @@ pub mod object {
                 String::from_utf8(s).unwrap()
             }
 
-            /// Extracts the name of a symbol by the index given.
+            /// Gets the name of a symbol from the given string table.
             ///
-            /// **Requires all sections to be loaded**
-            /// **Requires all symbols to be loaded**
-            fn extract_symbol_name(&self, file: &mut File, ndx: usize) -> String {
-                let sym = &self.symbols[ndx];       // the symbol we want
-
+            /// **Requires all section names to be loaded**
+            fn symbol_name(&self, sym: &Sym, strtab: &[u8]) -> String {
                 /* section symbols get their name from the section
                  * they represent
                  */
@@ -316,42 +759,15 @@ pub mod object {
                      * to get the corresponding name
                      */
                     let ndx = sym.shndx;
-                    return self.sections[ndx].name.as_ref().unwrap().clone();
+                    return self.sections.get(ndx)
+                        .and_then(|s| s.name.clone())
+                        .unwrap_or_default();
                 }
 
-                /* otherwise the name comes from the file's symbol
+                /* otherwise the name comes from the symbol table's
                  * string table
                  */
-                let mut i = 0;
-                let tabndx = loop {
-                        let section = &self.sections[i];
-                        if section.is_strtab() {
-                            break i;
-                        }
-                        i += 1;
-                        if i >= self.sections.len() {
-                            panic!("no strtab found");
-                        }
-                };
-                let strtab = &self.sections[tabndx];
-
-                /* seek into string in file */
-                let nameoff = sym.nameoff;
-                let off = strtab.offset + nameoff as u64;
-                file.seek(SeekFrom::Start(off)).unwrap();
-
-                /* read string untill null-byte */
-                let mut s: Vec<u8> = vec![];
-                let mut c: u8;
-                loop {
-                    c = util::read_u8(file);
-                    if c == b'\0' {
-                        break;
-                    }
-                    s.push(c);
-                }
-
-                String::from_utf8(s).unwrap()
+                util::get_str(strtab, sym.nameoff)
             }
         }
     }
@@ -359,10 +775,64 @@ pub mod object {
 
 #[cfg(test)]
 mod tests {
-    use super::header::Header;
+    use std::io::Cursor;
     use super::object::Object;
-    
-    /*
-     * TODO:
-     */
+
+    /// Get the contents of a sample file.
+    pub(crate) fn sample(name: &str) -> Vec<u8> {
+        let path = format!("{}/samples/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read(path).unwrap()
+    }
+
+    /// Writes an object back into memory.
+    pub(crate) fn write(obj: &Object) -> Vec<u8> {
+        let mut out = Cursor::new(vec![]);
+        obj.write_to(&mut out).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn round_trip() {
+        for name in &["main", "main.o"] {
+            let data = sample(name);
+            assert!(write(&Object::from_bytes(&data)) == data, "{} differs", name);
+        }
+    }
+
+    #[test]
+    fn readable() {
+        for name in &["main", "main.o"] {
+            let data = sample(name);
+            assert!(Object::is_readable(&data), "{} unreadable", name);
+            assert!(!Object::is_readable(&data[..data.len() - 1]));
+            assert!(!Object::is_readable(&data[..32]));
+
+            let mut elf32 = data.clone();
+            elf32[4] = 1;
+            assert!(!Object::is_readable(&elf32));
+            let mut shstrndx = data.clone();
+            shstrndx[0x3e] = 0xff;
+            assert!(!Object::is_readable(&shstrndx));
+        }
+    }
+
+    #[test]
+    fn round_trip_trailing() {
+        let mut data = sample("main");
+        data.extend_from_slice(b"appended signature");
+        assert!(write(&Object::from_bytes(&data)) == data);
+    }
+
+    #[test]
+    fn round_trip_unowned() {
+        /* scribble over the first padding no section owns */
+        let mut data = sample("main.o");
+        let obj = Object::from_bytes(&data);
+        let (off, len) = obj.unowned.iter()
+            .map(|(off, d)| (*off as usize, d.len()))
+            .find(|(_, len)| *len > 0)
+            .expect("no unowned bytes");
+        data[off..off + len].iter_mut().for_each(|b| *b = 0xa5);
+        assert!(write(&Object::from_bytes(&data)) == data);
+    }
 }
@@ -0,0 +1,485 @@
+//! Regarding DWARF line number programs (`.debug_line`).
+//!
+//! Each program has a header, with the directories and files it refers
+//! to, followed by opcodes driving a state machine. Every row it emits
+//! maps an address to a file, line and column, and rows go by sequences
+//! of increasing addresses ending with an `end_sequence` row.
+//!
+//! Versions 2 to 5 are handled. Version 5 describes its directory and
+//! file entries with formats, and numbers files from zero, while older
+//! versions number them from one, directory zero being the compilation
+//! directory.
+
+use std::collections::HashMap;
+
+use crate::dwarf::*;
+use crate::object::Object;
+
+pub const DW_LNS_COPY: u8 = 1;
+pub const DW_LNS_ADVANCE_PC: u8 = 2;
+pub const DW_LNS_ADVANCE_LINE: u8 = 3;
+pub const DW_LNS_SET_FILE: u8 = 4;
+pub const DW_LNS_SET_COLUMN: u8 = 5;
+pub const DW_LNS_NEGATE_STMT: u8 = 6;
+pub const DW_LNS_SET_BASIC_BLOCK: u8 = 7;
+pub const DW_LNS_CONST_ADD_PC: u8 = 8;
+pub const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+pub const DW_LNS_SET_PROLOGUE_END: u8 = 10;
+pub const DW_LNS_SET_EPILOGUE_BEGIN: u8 = 11;
+pub const DW_LNS_SET_ISA: u8 = 12;
+
+pub const DW_LNE_END_SEQUENCE: u8 = 1;
+pub const DW_LNE_SET_ADDRESS: u8 = 2;
+pub const DW_LNE_DEFINE_FILE: u8 = 3;
+pub const DW_LNE_SET_DISCRIMINATOR: u8 = 4;
+
+pub const DW_LNCT_PATH: u64 = 1;
+pub const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+pub const DW_LNCT_TIMESTAMP: u64 = 3;
+pub const DW_LNCT_SIZE: u64 = 4;
+pub const DW_LNCT_MD5: u64 = 5;
+
+/// A file of a line number program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileEntry {
+    /// File name, possibly relative to its directory.
+    pub name:   String,
+    /// Index of its directory.
+    pub dir:    u64,
+}
+
+/// A row of the line number table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineRow {
+    /// Address of the instruction.
+    pub address:        u64,
+    /// Index of the file.
+    pub file:           u64,
+    /// Line number, starting at one, zero if unknown.
+    pub line:           u64,
+    /// Column number, starting at one, zero if unknown.
+    pub column:         u64,
+    /// Wether the instruction is a recommended breakpoint location.
+    pub is_stmt:        bool,
+    /// Wether the address is past the end of a sequence.
+    pub end_sequence:   bool,
+}
+
+/// A decoded line number program.
+#[derive(Clone, Debug)]
+pub struct LineProgram {
+    /// Offset of the program in `.debug_line`.
+    pub offset:         u64,
+    /// DWARF version.
+    pub version:        u16,
+    /// Directories, the compilation one first.
+    pub directories:    Vec<String>,
+    /// Files, the primary source file first.
+    pub files:          Vec<FileEntry>,
+    /// Rows, by sequence.
+    pub rows:           Vec<LineRow>,
+}
+
+/// A source location.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// Path of the source file.
+    pub file:   String,
+    /// Line number, zero if unknown.
+    pub line:   u64,
+    /// Column number, zero if unknown.
+    pub column: u64,
+}
+
+/// Maps addresses to source locations, across every line program.
+pub struct LineTable {
+    /// The programs.
+    programs:   Vec<LineProgram>,
+    /// Address range of every sequence, with its program and first
+    /// row, sorted by address.
+    sequences:  Vec<(u64, u64, usize, usize)>,
+}
+
+/// Simple line program methods.
+impl LineProgram {
+    /// Get the path of a file, joined to its directory.
+    ///
+    /// Relative directories are relative to the compilation one, which
+    /// older versions only record in their unit, so paths may stay
+    /// relative for programs parsed on their own.
+    pub fn file_path(&self, index: u64) -> Option<String> {
+        let file = if self.version >= 5 {
+            self.files.get(index as usize)?
+        } else {
+            self.files.get((index as usize).checked_sub(1)?)?
+        };
+        if file.name.starts_with('/') {
+            return Some(file.name.clone());
+        }
+        let mut dir = self.directories.get(file.dir as usize).cloned().unwrap_or_default();
+        if !dir.starts_with('/') && file.dir != 0 {
+            dir = join(self.directories.first().map_or("", |d| d), &dir);
+        }
+        Some(join(&dir, &file.name))
+    }
+}
+
+/// Simple line table methods.
+impl LineTable {
+    /// Creates a table from line programs.
+    pub fn new(programs: Vec<LineProgram>) -> Self {
+        let mut sequences = vec![];
+        for (p, program) in programs.iter().enumerate() {
+            let mut start = 0;
+            for (i, row) in program.rows.iter().enumerate() {
+                if row.end_sequence {
+                    let first = program.rows[start].address;
+                    if first < row.address {
+                        sequences.push((first, row.address, p, start));
+                    }
+                    start = i + 1;
+                }
+            }
+        }
+        sequences.sort_unstable();
+        Self { programs, sequences }
+    }
+
+    /// Get the line programs.
+    pub fn programs(&self) -> &[LineProgram] {
+        &self.programs
+    }
+
+    /// Get the row covering an address, along with its program.
+    pub fn row(&self, addr: u64) -> Option<(&LineProgram, &LineRow)> {
+        let at = self.sequences.partition_point(|s| s.0 <= addr);
+        let &(_, _, p, first) = self.sequences[..at].iter()
+            .rev()
+            .find(|s| addr < s.1)?;
+        let program = &self.programs[p];
+        let rows = &program.rows[first..];
+        let last = rows.iter().position(|r| r.end_sequence).unwrap_or(rows.len() - 1);
+        let i = rows[..=last].partition_point(|r| r.address <= addr);
+        Some((program, &rows[i.checked_sub(1)?]))
+    }
+
+    /// Get the source location of an address, if known.
+    pub fn lookup(&self, addr: u64) -> Option<Location> {
+        let (program, row) = self.row(addr)?;
+        Some(Location {
+            file:   program.file_path(row.file)?,
+            line:   row.line,
+            column: row.column,
+        })
+    }
+}
+
+/// Joins a directory and a path.
+fn join(dir: &str, path: &str) -> String {
+    if dir.is_empty() || path.starts_with('/') {
+        return path.to_string();
+    }
+    format!("{}/{}", dir.trim_end_matches('/'), path)
+}
+
+/// Parsing methods.
+mod parsing {
+    use super::*;
+
+    impl LineProgram {
+        /// Parses the line program at the given offset of `.debug_line`,
+        /// unless its header is truncated.
+        ///
+        /// A truncated program keeps the rows read so far.
+        pub fn parse(dwarf: &Dwarf, offset: u64) -> Option<Self> {
+            let mut r = Reader::at(&dwarf.line, offset as usize);
+            let (end, is64) = r.unit_end()?;
+            let mut r = Reader::at(&dwarf.line[..end], r.pos);
+            let version = r.u16()?;
+            let mut address_size = 8;
+            if version >= 5 {
+                address_size = r.u8()?;
+                let _segment_selector_size = r.u8()?;
+            }
+            let header_len = r.offset(is64)? as usize;
+            let program = r.pos.saturating_add(header_len);
+            let min_inst_len = r.u8()? as u64;
+            if version >= 4 {
+                let _max_ops_per_inst = r.u8()?;
+            }
+            let default_is_stmt = r.u8()? != 0;
+            let line_base = r.u8()? as i8 as i64;
+            let line_range = r.u8()? as u64;
+            let opcode_base = r.u8()?;
+            let opcode_lengths = r.bytes(opcode_base.saturating_sub(1) as usize)?.to_vec();
+            let enc = Encoding { version, is64, address_size };
+
+            let mut directories = vec![];
+            let mut files = vec![];
+            if version >= 5 {
+                /* an unknown form leaves the rest of the header unreadable */
+                if let Some(dirs) = entries(&mut r, dwarf, &enc) {
+                    directories = dirs.into_iter().map(|e| e.name).collect();
+                    files = entries(&mut r, dwarf, &enc).unwrap_or_default();
+                }
+            } else {
+                directories.push(String::new());
+                loop {
+                    let dir = r.cstr()?;
+                    if dir.is_empty() {
+                        break;
+                    }
+                    directories.push(dir);
+                }
+                loop {
+                    let name = r.cstr()?;
+                    if name.is_empty() {
+                        break;
+                    }
+                    let dir = r.uleb()?;
+                    let _mtime = r.uleb()?;
+                    let _size = r.uleb()?;
+                    files.push(FileEntry { name, dir });
+                }
+            }
+
+            /* the state machine */
+            r.pos = program;
+            let initial = LineRow {
+                address:        0,
+                file:           1,
+                line:           1,
+                column:         0,
+                is_stmt:        default_is_stmt,
+                end_sequence:   false,
+            };
+            let mut state = initial;
+            let mut rows = vec![];
+            let mut run = || -> Option<()> {
+                /* special opcodes cannot be decoded without a line range */
+                while !r.is_empty() && line_range != 0 {
+                    let opcode = r.u8()?;
+                    if opcode >= opcode_base {
+                        let adjusted = (opcode - opcode_base) as u64;
+                        let advance = line_base + (adjusted % line_range) as i64;
+                        state.address = state.address.wrapping_add(adjusted / line_range * min_inst_len);
+                        state.line = state.line.wrapping_add(advance as u64);
+                        rows.push(state);
+                        continue;
+                    }
+                    match opcode {
+                        0 => {
+                            let len = r.uleb()? as usize;
+                            let next = r.pos.saturating_add(len);
+                            if len == 0 {
+                                continue;
+                            }
+                            match r.u8()? {
+                                DW_LNE_END_SEQUENCE => {
+                                    state.end_sequence = true;
+                                    rows.push(state);
+                                    state = initial;
+                                }
+                                DW_LNE_SET_ADDRESS => state.address = r.sized(len - 1)?,
+                                DW_LNE_DEFINE_FILE => {
+                                    let name = r.cstr()?;
+                                    let dir = r.uleb()?;
+                                    files.push(FileEntry { name, dir });
+                                }
+                                _ => (),
+                            }
+                            r.pos = next;
+                        }
+                        DW_LNS_COPY => rows.push(state),
+                        DW_LNS_ADVANCE_PC => {
+                            let advance = r.uleb()?.wrapping_mul(min_inst_len);
+                            state.address = state.address.wrapping_add(advance);
+                        }
+                        DW_LNS_ADVANCE_LINE => state.line = state.line.wrapping_add(r.sleb()? as u64),
+                        DW_LNS_SET_FILE => state.file = r.uleb()?,
+                        DW_LNS_SET_COLUMN => state.column = r.uleb()?,
+                        DW_LNS_NEGATE_STMT => state.is_stmt = !state.is_stmt,
+                        DW_LNS_CONST_ADD_PC => {
+                            let advance = (255 - opcode_base) as u64 / line_range * min_inst_len;
+                            state.address = state.address.wrapping_add(advance);
+                        }
+                        DW_LNS_FIXED_ADVANCE_PC => {
+                            state.address = state.address.wrapping_add(r.u16()? as u64);
+                        }
+                        DW_LNS_SET_BASIC_BLOCK | DW_LNS_SET_PROLOGUE_END
+                            | DW_LNS_SET_EPILOGUE_BEGIN => (),
+                        _ => {
+                            /* unknown standard opcodes say how many arguments they have */
+                            for _ in 0..opcode_lengths[opcode as usize - 1] {
+                                r.uleb()?;
+                            }
+                        }
+                    }
+                }
+                Some(())
+            };
+            run();
+
+            Some(Self { offset, version, directories, files, rows })
+        }
+    }
+
+    /// Reads version 5 directory or file entries, as described by their
+    /// formats, unless one of them is unknown.
+    fn entries(r: &mut Reader, dwarf: &Dwarf, enc: &Encoding) -> Option<Vec<FileEntry>> {
+        let format_count = r.u8()?;
+        let formats = (0..format_count)
+            .map(|_| Some((r.uleb()?, r.uleb()?)))
+            .collect::<Option<Vec<(u64, u64)>>>()?;
+        /* entries take a byte at least, which bounds bogus counts */
+        let count = r.uleb()?.min(r.data().len().saturating_sub(r.pos) as u64);
+        let mut entries = vec![];
+        for _ in 0..count {
+            let mut entry = FileEntry { name: String::new(), dir: 0 };
+            for &(content, form) in &formats {
+                let value = r.value(form, enc, 0)?;
+                match content {
+                    DW_LNCT_PATH => {
+                        entry.name = match value {
+                            Value::Str(s)       => s,
+                            Value::Strp(off)    => dwarf.string(off),
+                            Value::LineStrp(off) => dwarf.line_string(off),
+                            _                   => String::new(),
+                        };
+                    }
+                    DW_LNCT_DIRECTORY_INDEX => entry.dir = value.udata().unwrap_or(0),
+                    _ => (),
+                }
+            }
+            entries.push(entry);
+        }
+        Some(entries)
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Dwarf<'_> {
+        /// Parses every line program of `.debug_line`, in order.
+        ///
+        /// Programs before version 5 get the compilation directory of
+        /// their unit, so their paths are absolute.
+        pub fn line_programs(&self) -> Vec<LineProgram> {
+            let comp_dirs: HashMap<u64, String> = self.units().into_iter()
+                .filter_map(|u| Some((u.stmt_list?, u.comp_dir?)))
+                .collect();
+            let mut programs = vec![];
+            let mut offset = 0;
+            while offset < self.line.len() {
+                let mut r = Reader::at(&self.line, offset);
+                let end = match r.unit_end() {
+                    Some((end, _)) if end > r.pos => end,
+                    _ => break,
+                };
+                if let Some(mut program) = LineProgram::parse(self, offset as u64) {
+                    if let (true, Some(dir)) = (program.version < 5, comp_dirs.get(&program.offset)) {
+                        program.directories[0] = dir.clone();
+                    }
+                    programs.push(program);
+                }
+                offset = end;
+            }
+            programs
+        }
+    }
+
+    impl Object {
+        /// Builds the line table of every line program.
+        pub fn line_table(&self) -> LineTable {
+            LineTable::new(self.dwarf().line_programs())
+        }
+
+        /// Get the source location of an address, like `addr2line`.
+        ///
+        /// Use a `LineTable` for many lookups.
+        pub fn addr2line(&self, addr: u64) -> Option<Location> {
+            self.line_table().lookup(addr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::tests::sample;
+
+    /// Samples built from `inline.c`, with DWARF 5 and 4.
+    const SAMPLES: [&str; 2] = ["inline", "inline4"];
+
+    #[test]
+    fn addr2line() {
+        for name in &SAMPLES {
+            let obj = Object::from_bytes(&sample(name));
+            for &(addr, line) in &[(0x1040, 11), (0x1044, 13), (0x1048, 6), (0x104c, 14)] {
+                let loc = obj.addr2line(addr).unwrap();
+                assert_eq!((loc.file.as_str(), loc.line), ("./inline.c", line), "{} {:#x}", name, addr);
+            }
+            assert!(obj.addr2line(0x104d).is_none());
+        }
+    }
+
+    #[test]
+    fn zero_line_range() {
+        for (name, at) in SAMPLES.iter().zip([16, 14]) {
+            let mut obj = Object::from_bytes(&sample(name));
+            let ndx = obj.section_index(".debug_line").unwrap();
+            assert_eq!(obj.sections[ndx].data[at], 14);
+            obj.sections[ndx].data[at] = 0;
+            assert!(obj.addr2line(0x1048).is_none());
+        }
+    }
+
+    #[test]
+    fn unknown_form() {
+        /* the producer of the compile unit gets a vendor form */
+        let mut obj = Object::from_bytes(&sample("inline"));
+        let ndx = obj.section_index(".debug_abbrev").unwrap();
+        assert_eq!(obj.sections[ndx].data[0x51..0x56], [0x06, 0x11, 0x01, 0x25, 0x0e]);
+        obj.sections[ndx].data[0x55] = 0x7f;
+        assert_eq!(obj.addr2line(0x1048).unwrap().line, 6);
+        assert!(obj.inline_frames(0x1048).is_empty());
+    }
+
+    #[test]
+    fn set_address() {
+        for (name, at) in SAMPLES.iter().zip([0x39, 0x2d]) {
+            let mut obj = Object::from_bytes(&sample(name));
+            let ndx = obj.section_index(".debug_line").unwrap();
+            let data = &mut obj.sections[ndx].data;
+            assert_eq!(data[at..at + 4], [0x09, DW_LNE_SET_ADDRESS, 0x40, 0x10]);
+
+            /* addresses wrap around past the end of the address space */
+            data[at + 2..at + 10].fill(0xff);
+            let rows = &obj.dwarf().line_programs()[0].rows;
+            assert_eq!(rows[0].address, u64::MAX);
+            assert!(rows.iter().any(|r| r.address < 0x100));
+
+            /* an empty extended opcode is skipped */
+            obj.sections[ndx].data[at] = 0;
+            assert!(!obj.dwarf().line_programs()[0].rows.is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        for name in &SAMPLES {
+            let obj = Object::from_bytes(&sample(name));
+            let full = obj.dwarf();
+            let rows = full.line_programs()[0].rows.len();
+            for len in 0..full.line.len() {
+                let mut dwarf = obj.dwarf();
+                dwarf.line = Cow::Borrowed(&full.line[..len]);
+                assert!(dwarf.line_programs().iter().all(|p| p.rows.len() < rows), "{} {}", name, len);
+            }
+        }
+    }
+}
@@ -0,0 +1,814 @@
+//! Regarding static linking of x86-64 relocatable objects.
+//!
+//! The linker is deliberately small:
+//! - Allocated input sections are merged into four output sections,
+//!   `.text`, `.rodata`, `.data` and `.bss`, picked by name and flags.
+//!   Everything else, `.eh_frame` included, is dropped.
+//! - Global symbols are resolved with the usual rules: a strong
+//!   definition wins over weak ones, two strong ones are an error, and
+//!   common symbols are allocated in `.bss`.
+//! - Relocations are applied directly. References through the GOT get
+//!   their slot at the end of `.data`.
+//! - The output is a non-PIE executable with one `PT_LOAD` segment per
+//!   output section (the text one also maps the headers).
+//! - Thread local storage is rejected, as there is no `PT_TLS`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::header::{Header, Type as HeaderType, EM_X86_64};
+use crate::object::Object;
+use crate::rel::x86_64::*;
+use crate::section::{self, Section, Type as SectionType};
+use crate::segment::{self, Segment, Type as SegmentType};
+use crate::sym::{Sym, Bind, Type as SymType, SHN_ABS, SHN_COMMON};
+use crate::util;
+
+/// Page size used for segment alignment.
+const PAGE: u64 = 0x1000;
+
+/// Name of the symbol the linker defines at the start of the GOT.
+const GOT_SYMBOL: &str = "_GLOBAL_OFFSET_TABLE_";
+
+/// Errors found while linking.
+#[derive(Debug)]
+pub enum Error {
+    /// A symbol is referenced but never defined.
+    Undefined(String),
+    /// A symbol is defined by more than one object.
+    Duplicate(String),
+    /// The entry point symbol is not defined.
+    NoEntry(String),
+    /// A relocation type is not supported.
+    Unsupported(u32),
+    /// A relocated value does not fit its field.
+    Overflow(String),
+    /// A section or symbol uses thread local storage.
+    Tls(String),
+    /// A relocation refers to a symbol that was dropped.
+    Discarded(String),
+    /// A relocation lies outside the section it applies to.
+    Misplaced(String),
+}
+
+/// Output section kinds, in address order.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Rodata,
+    Data,
+    Bss,
+}
+
+/// Every output section kind.
+const KINDS: [Kind; 4] = [Kind::Text, Kind::Rodata, Kind::Data, Kind::Bss];
+
+/// An output section being built.
+struct Out {
+    /// Merged contents (empty for `.bss`).
+    data:   Vec<u8>,
+    /// Size in memory.
+    size:   u64,
+    /// Largest alignment of its inputs.
+    align:  u64,
+    /// Final address.
+    addr:   u64,
+}
+
+/// Where a symbol is defined.
+#[derive(Clone, Copy)]
+struct Def {
+    /// Index of the defining object.
+    obj:    usize,
+    /// Index of the symbol in that object.
+    sym:    usize,
+}
+
+/// Links relocatable objects into a static executable.
+pub struct Linker {
+    /// Input objects.
+    pub(crate) objects: Vec<Object>,
+    /// Name of the entry point symbol.
+    entry:      String,
+    /// Address of the first segment.
+    base:       u64,
+}
+
+/// Get the precedence of a global definition: strong beats common,
+/// which beats weak.
+pub(crate) fn rank(sym: &Sym) -> u8 {
+    match (sym.bind, sym.shndx) {
+        (Bind::Weak, _) => 0,
+        (_, SHN_COMMON) => 1,
+        _               => 2,
+    }
+}
+
+/// Simple kind methods.
+impl Kind {
+    /// Get the output kind of an input section, if it is kept.
+    fn of(section: &Section) -> Option<Self> {
+        let name = section.name.as_deref().unwrap_or("");
+        if !section.is_alloc() || name.starts_with(".eh_frame") || name.starts_with(".note") {
+            return None;
+        }
+        let kind = if section.is_nobits() {
+            Self::Bss
+        } else if section.flags & section::SHF_EXECINSTR != 0 {
+            Self::Text
+        } else if section.flags & section::SHF_WRITE != 0 {
+            Self::Data
+        } else {
+            Self::Rodata
+        };
+        Some(kind)
+    }
+
+    /// Get the output section name.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Text   => ".text",
+            Self::Rodata => ".rodata",
+            Self::Data   => ".data",
+            Self::Bss    => ".bss",
+        }
+    }
+
+    /// Get the output section flags.
+    fn flags(&self) -> u64 {
+        match self {
+            Self::Text   => section::SHF_ALLOC | section::SHF_EXECINSTR,
+            Self::Rodata => section::SHF_ALLOC,
+            Self::Data   => section::SHF_ALLOC | section::SHF_WRITE,
+            Self::Bss    => section::SHF_ALLOC | section::SHF_WRITE,
+        }
+    }
+
+    /// Get the segment flags.
+    fn segment_flags(&self) -> u32 {
+        match self {
+            Self::Text   => segment::PF_R | segment::PF_X,
+            Self::Rodata => segment::PF_R,
+            Self::Data   => segment::PF_R | segment::PF_W,
+            Self::Bss    => segment::PF_R | segment::PF_W,
+        }
+    }
+}
+
+/// Simple out methods.
+impl Out {
+    /// Creates an empty output section.
+    fn empty() -> Self {
+        Self {
+            data:   vec![],
+            size:   0,
+            align:  1,
+            addr:   0,
+        }
+    }
+
+    /// Reserves room for an input of the given size and alignment,
+    /// and gets its offset.
+    fn reserve(&mut self, size: u64, align: u64) -> u64 {
+        let align = align.max(1);
+        let off = util::align(self.size, align);
+        self.size = off + size;
+        self.align = self.align.max(align);
+        off
+    }
+}
+
+/// Simple linker methods.
+impl Linker {
+    /// Creates a linker with no objects, entering at `_start` and
+    /// loading at `0x400000`.
+    pub fn new() -> Self {
+        Self {
+            objects:    vec![],
+            entry:      "_start".to_string(),
+            base:       0x400000,
+        }
+    }
+
+    /// Adds a relocatable object to link.
+    pub fn add_object(&mut self, obj: Object) {
+        self.objects.push(obj);
+    }
+
+    /// Sets the name of the entry point symbol.
+    pub fn entry(&mut self, name: &str) {
+        self.entry = name.to_string();
+    }
+
+    /// Sets the address of the first segment.
+    pub fn base(&mut self, addr: u64) {
+        self.base = addr;
+    }
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Linking methods.
+mod linking {
+    use super::*;
+
+    /// State of a link in progress.
+    struct State<'a> {
+        /// Input objects.
+        objects:    &'a [Object],
+        /// Output sections, one per kind.
+        outs:       Vec<Out>,
+        /// Output kind and offset of every input section.
+        place:      Vec<Vec<Option<(Kind, u64)>>>,
+        /// Definition of every global symbol.
+        globals:    HashMap<String, Def>,
+        /// Offset into `.bss` of every common symbol.
+        commons:    HashMap<String, u64>,
+        /// Offset into `.data` of the GOT slot of every symbol.
+        got:        HashMap<(usize, usize), u64>,
+        /// Offset into `.data` of the start of the GOT.
+        got_start:  u64,
+    }
+
+    impl Linker {
+        /// Links every object added into a static executable.
+        pub fn link(&self) -> Result<Object, Error> {
+            let mut state = State {
+                objects:    &self.objects,
+                outs:       KINDS.iter().map(|_| Out::empty()).collect(),
+                place:      vec![],
+                globals:    HashMap::new(),
+                commons:    HashMap::new(),
+                got:        HashMap::new(),
+                got_start:  0,
+            };
+
+            state.check_tls()?;
+            state.place_sections();
+            state.resolve_symbols()?;
+            state.place_commons();
+            state.place_got();
+            state.assign_addresses(self.base);
+            state.fill_got()?;
+            state.relocate()?;
+
+            let entry = state.globals.get(&self.entry)
+                .ok_or_else(|| Error::NoEntry(self.entry.clone()))?;
+            let entry = state.def_addr(*entry);
+            Ok(state.output(self.base, entry))
+        }
+    }
+
+    impl<'a> State<'a> {
+        /// Get the output section of a kind.
+        fn out(&mut self, kind: Kind) -> &mut Out {
+            &mut self.outs[kind as usize]
+        }
+
+        /// Rejects the thread local sections and symbols of the inputs.
+        fn check_tls(&self) -> Result<(), Error> {
+            for obj in self.objects {
+                let section = obj.sections().iter()
+                    .find(|s| s.is_alloc() && s.flags & section::SHF_TLS != 0);
+                if let Some(section) = section {
+                    return Err(Error::Tls(section.name.clone().unwrap_or_default()));
+                }
+                if let Some(sym) = obj.symbols().iter().find(|s| s.etype == SymType::TLS) {
+                    return Err(Error::Tls(sym.name.clone().unwrap_or_default()));
+                }
+            }
+            Ok(())
+        }
+
+        /// Merges every kept input section into its output section.
+        fn place_sections(&mut self) {
+            for obj in self.objects {
+                let mut place = vec![];
+                for section in obj.sections() {
+                    let kind = match Kind::of(section) {
+                        Some(kind) => kind,
+                        None => {
+                            place.push(None);
+                            continue;
+                        }
+                    };
+                    let out = &mut self.outs[kind as usize];
+                    let off = out.reserve(section.size, section.addralign as u64);
+                    if kind != Kind::Bss {
+                        out.data.resize(off as usize, 0);
+                        out.data.extend_from_slice(&section.data);
+                    }
+                    place.push(Some((kind, off)));
+                }
+                self.place.push(place);
+            }
+        }
+
+        /// Builds the global symbol table.
+        fn resolve_symbols(&mut self) -> Result<(), Error> {
+            for (o, obj) in self.objects.iter().enumerate() {
+                for (i, sym) in obj.symbols().iter().enumerate() {
+                    if sym.is_local() || sym.is_undefined() {
+                        continue;
+                    }
+                    let name = sym.name.clone().unwrap_or_default();
+                    let def = Def { obj: o, sym: i };
+                    let old = match self.globals.get(&name) {
+                        Some(old) => self.sym(*old),
+                        None => {
+                            self.globals.insert(name, def);
+                            continue;
+                        }
+                    };
+
+                    let (new_rank, old_rank) = (rank(sym), rank(old));
+                    if new_rank == 2 && old_rank == 2 {
+                        return Err(Error::Duplicate(name));
+                    }
+                    /* size only decides between two common symbols */
+                    let bigger = sym.size > old.size;
+                    if new_rank > old_rank || (new_rank == 1 && old_rank == 1 && bigger) {
+                        self.globals.insert(name, def);
+                    }
+                }
+            }
+
+            /* every strong reference needs a definition */
+            for obj in self.objects {
+                for sym in obj.symbols() {
+                    let name = sym.name.as_deref().unwrap_or("");
+                    if sym.is_undefined() && !sym.is_local() && sym.bind != Bind::Weak
+                        && !self.globals.contains_key(name) && name != GOT_SYMBOL {
+                        return Err(Error::Undefined(name.to_string()));
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Allocates every common symbol in `.bss`.
+        fn place_commons(&mut self) {
+            let mut names: Vec<&String> = self.globals.keys().collect();
+            names.sort();
+            let mut commons = vec![];
+            for name in names {
+                let sym = self.sym(self.globals[name]);
+                if sym.shndx == SHN_COMMON {
+                    /* for commons, the value holds the alignment */
+                    commons.push((name.clone(), sym.size, sym.value));
+                }
+            }
+            for (name, size, align) in commons {
+                let off = self.out(Kind::Bss).reserve(size, align);
+                self.commons.insert(name, off);
+            }
+        }
+
+        /// Allocates a GOT slot for every symbol referenced through it.
+        fn place_got(&mut self) {
+            self.got_start = self.out(Kind::Data).reserve(0, 8);
+            for (o, obj) in self.objects.iter().enumerate() {
+                for (_, rela) in self.relocations(o) {
+                    match rela.rtype {
+                        R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => (),
+                        _ => continue,
+                    }
+                    let key = self.key(o, rela.sym, obj);
+                    if !self.got.contains_key(&key) {
+                        let out = &mut self.outs[Kind::Data as usize];
+                        let off = out.reserve(8, 8);
+                        out.data.resize(off as usize + 8, 0);
+                        self.got.insert(key, off);
+                    }
+                }
+            }
+        }
+
+        /// Gives an address to every output section.
+        ///
+        /// Each one starts on its own page, with the file offset equal
+        /// to the address modulo the page size.
+        fn assign_addresses(&mut self, base: u64) {
+            let mut addr = base + PAGE;
+            for kind in KINDS.iter() {
+                let out = self.out(*kind);
+                if *kind == Kind::Bss {
+                    addr = util::align(addr, out.align);
+                } else {
+                    addr = util::align(addr, PAGE);
+                }
+                out.addr = addr;
+                addr += out.size;
+            }
+        }
+
+        /// Writes the address of every symbol into its GOT slot.
+        fn fill_got(&mut self) -> Result<(), Error> {
+            let mut slots: Vec<((usize, usize), u64)> =
+                self.got.iter().map(|(k, v)| (*k, *v)).collect();
+            slots.sort_unstable();
+            for ((o, i), off) in slots {
+                let value = self.sym_addr(o, i)?;
+                let data = &mut self.outs[Kind::Data as usize].data;
+                data[off as usize..off as usize + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            Ok(())
+        }
+
+        /// Applies every relocation against a kept section.
+        fn relocate(&mut self) -> Result<(), Error> {
+            for o in 0..self.objects.len() {
+                for (target, rela) in self.relocations(o) {
+                    let (kind, off) = self.place[o][target].unwrap();
+                    let off = off + rela.offset;
+                    let p = self.outs[kind as usize].addr + off;
+                    let s = self.sym_addr(o, rela.sym)?;
+                    let a = rela.addend;
+
+                    let (value, size, signed) = match rela.rtype {
+                        R_X86_64_NONE => continue,
+                        R_X86_64_64 => (s.wrapping_add(a as u64), 8, false),
+                        R_X86_64_PC64 => (s.wrapping_add(a as u64).wrapping_sub(p), 8, true),
+                        R_X86_64_PC32 | R_X86_64_PLT32 => {
+                            (s.wrapping_add(a as u64).wrapping_sub(p), 4, true)
+                        }
+                        R_X86_64_32 => (s.wrapping_add(a as u64), 4, false),
+                        R_X86_64_32S => (s.wrapping_add(a as u64), 4, true),
+                        R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                            let obj = &self.objects[o];
+                            let slot = self.got[&self.key(o, rela.sym, obj)];
+                            let g = self.outs[Kind::Data as usize].addr + slot;
+                            (g.wrapping_add(a as u64).wrapping_sub(p), 4, true)
+                        }
+                        t => return Err(Error::Unsupported(t)),
+                    };
+
+                    /* check the value fits in its field */
+                    let fits = match (size, signed) {
+                        (8, _)     => true,
+                        (_, true)  => value as i64 == value as i32 as i64,
+                        (_, false) => value >> 32 == 0,
+                    };
+                    if !fits {
+                        let sym = &self.objects[o].symbols()[rela.sym];
+                        return Err(Error::Overflow(sym.name.clone().unwrap_or_default()));
+                    }
+
+                    let data = &mut self.outs[kind as usize].data;
+                    let off = off as usize;
+                    data[off..off + size].copy_from_slice(&value.to_le_bytes()[..size]);
+                }
+            }
+            Ok(())
+        }
+
+        /// Get every relocation of an object that applies to a kept
+        /// section, along with the index of that section.
+        fn relocations(&self, o: usize) -> Vec<(usize, crate::rel::Rela)> {
+            let obj = &self.objects[o];
+            let mut relocs = vec![];
+            for (i, section) in obj.sections().iter().enumerate() {
+                let target = section.info as usize;
+                if !section.is_rela() || self.place[o].get(target).copied().flatten().is_none() {
+                    continue;
+                }
+                for rela in obj.relocations(i) {
+                    relocs.push((target, rela));
+                }
+            }
+            relocs
+        }
+
+        /// Get the symbol at a definition.
+        fn sym(&self, def: Def) -> &'a Sym {
+            &self.objects[def.obj].symbols()[def.sym]
+        }
+
+        /// Get a key identifying the symbol referenced by index `i` from
+        /// object `o`, once resolved.
+        fn key(&self, o: usize, i: usize, obj: &Object) -> (usize, usize) {
+            let sym = &obj.symbols()[i];
+            if sym.is_local() {
+                return (o, i);
+            }
+            let name = sym.name.as_deref().unwrap_or("");
+            match self.globals.get(name) {
+                Some(def) => (def.obj, def.sym),
+                None      => (o, i),
+            }
+        }
+
+        /// Get the final address of the symbol with index `i` in object
+        /// `o`, resolving globals.
+        fn sym_addr(&self, o: usize, i: usize) -> Result<u64, Error> {
+            let sym = &self.objects[o].symbols()[i];
+            if sym.is_local() {
+                return Ok(self.def_addr(Def { obj: o, sym: i }));
+            }
+            let name = sym.name.as_deref().unwrap_or("");
+            match self.globals.get(name) {
+                Some(def) => Ok(self.def_addr(*def)),
+                None if name == GOT_SYMBOL => {
+                    Ok(self.outs[Kind::Data as usize].addr + self.got_start)
+                }
+                None if sym.bind == Bind::Weak => Ok(0),
+                None => Err(Error::Undefined(name.to_string())),
+            }
+        }
+
+        /// Get the final address of a defined symbol.
+        fn def_addr(&self, def: Def) -> u64 {
+            let sym = self.sym(def);
+            match sym.shndx {
+                SHN_ABS => sym.value,
+                SHN_COMMON => {
+                    let name = sym.name.as_deref().unwrap_or("");
+                    self.outs[Kind::Bss as usize].addr + self.commons[name]
+                }
+                ndx => match self.place[def.obj].get(ndx).copied().flatten() {
+                    Some((kind, off)) => self.outs[kind as usize].addr + off + sym.value,
+                    None => 0,
+                },
+            }
+        }
+
+        /// Builds the output executable.
+        fn output(&self, base: u64, entry: u64) -> Object {
+            let mut obj = Object::empty();
+            obj.header = Header::new(HeaderType::Exec, EM_X86_64);
+            obj.header.entry = entry;
+            obj.header.phoff = 64;
+            obj.header.phentsize = 56;
+            obj.sections.push(Section::new("", SectionType::Null, 0));
+
+            /* one section and segment per non-empty output section */
+            let mut ndx = HashMap::new();
+            for kind in KINDS.iter() {
+                let out = &self.outs[*kind as usize];
+                if out.size == 0 {
+                    continue;
+                }
+                let etype = if *kind == Kind::Bss {
+                    SectionType::Nobits
+                } else {
+                    SectionType::Progbits
+                };
+                let mut section = Section::new(kind.name(), etype, kind.flags());
+                section.addr = out.addr;
+                section.offset = out.addr - base;
+                section.size = out.size;
+                section.addralign = out.align as usize;
+                section.data = out.data.clone();
+                ndx.insert(*kind as usize, obj.sections.len());
+                obj.sections.push(section);
+
+                if *kind == Kind::Bss && !obj.segments.is_empty() {
+                    let last = obj.segments.last_mut().unwrap();
+                    if last.flags() == kind.segment_flags() {
+                        last.memsz = out.addr + out.size - last.vaddr;
+                        continue;
+                    }
+                }
+                let mut seg = Segment::new(SegmentType::Load, kind.segment_flags());
+                seg.offset = out.addr - base;
+                seg.vaddr = out.addr;
+                seg.paddr = out.addr;
+                seg.filesz = if *kind == Kind::Bss { 0 } else { out.size };
+                seg.memsz = out.size;
+                seg.align = PAGE;
+                obj.segments.push(seg);
+            }
+
+            /* the first segment also maps the headers */
+            if let Some(first) = obj.segments.first_mut() {
+                first.filesz += first.offset;
+                first.memsz += first.offset;
+                first.offset = 0;
+                first.vaddr = base;
+                first.paddr = base;
+            }
+            obj.segments.push(Segment::new(SegmentType::GnuStack,
+                segment::PF_R | segment::PF_W));
+
+            /* symbol table with every defined global */
+            let mut syms = vec![Sym::empty()];
+            let mut names: Vec<&String> = self.globals.keys().collect();
+            names.sort();
+            for name in names {
+                let def = self.globals[name];
+                let src = self.sym(def);
+                let kind = match src.shndx {
+                    SHN_ABS    => None,
+                    SHN_COMMON => Some(Kind::Bss),
+                    n => self.place[def.obj].get(n).copied().flatten().map(|(k, _)| k),
+                };
+                let mut sym = src.clone();
+                sym.value = self.def_addr(def);
+                sym.shndx = match kind {
+                    Some(kind) => ndx[&(kind as usize)],
+                    None       => SHN_ABS,
+                };
+                syms.push(sym);
+            }
+            let (symdata, strdata) = Sym::encode_table(&mut syms);
+
+            let symtab_ndx = obj.sections.len();
+            let mut symtab = Section::new(".symtab", SectionType::Symtab, 0);
+            symtab.link = symtab_ndx + 1;
+            symtab.info = 1;
+            symtab.entsize = 24;
+            symtab.addralign = 8;
+            symtab.data = symdata;
+            obj.sections.push(symtab);
+
+            let mut strtab = Section::new(".strtab", SectionType::Strtab, 0);
+            strtab.addralign = 1;
+            strtab.data = strdata;
+            obj.sections.push(strtab);
+
+            let mut shstrtab = Section::new(".shstrtab", SectionType::Strtab, 0);
+            shstrtab.addralign = 1;
+            obj.header.shstrndx = obj.sections.len();
+            obj.sections.push(shstrtab);
+
+            obj.symbols = syms;
+            obj.layout();
+            obj
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe a link error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Undefined(name)  => write!(f, "undefined symbol `{}`", name),
+                Error::Duplicate(name)  => write!(f, "duplicate symbol `{}`", name),
+                Error::NoEntry(name)    => write!(f, "entry symbol `{}` not defined", name),
+                Error::Unsupported(t)   => write!(f, "unsupported relocation type {}", t),
+                Error::Overflow(name)   => write!(f, "relocation against `{}` overflows", name),
+                Error::Tls(name)        => write!(f, "thread local `{}` is not supported", name),
+                Error::Discarded(name)  => write!(f, "relocation against discarded `{}`", name),
+                Error::Misplaced(name)  => write!(f, "relocation outside of `{}`", name),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::builder::ObjectBuilder;
+    use crate::tests::sample;
+
+    /// Get an object defining `int x = 42;`.
+    pub(crate) fn strong_x() -> Object {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let data = b.add_section(".data", section::SHF_ALLOC | section::SHF_WRITE,
+            42u32.to_le_bytes().to_vec(), 4);
+        b.define_symbol("x", Bind::Global, SymType::Object, data, 0, 4);
+        b.build()
+    }
+
+    /// Get an object defining `int x[10];` as a common symbol.
+    pub(crate) fn common_x() -> Object {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let sym = Sym {
+            etype:  SymType::Object,
+            bind:   Bind::Global,
+            shndx:  SHN_COMMON,
+            value:  4,
+            size:   40,
+            name:   Some("x".to_string()),
+            ..Sym::empty()
+        };
+        b.add_symbol(sym, None);
+        b.build()
+    }
+
+    /// Get an object defining `_start`.
+    pub(crate) fn start() -> Object {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let text = b.add_section(".text", section::SHF_ALLOC | section::SHF_EXECINSTR,
+            vec![0xc3], 1);
+        b.define_symbol("_start", Bind::Global, SymType::Func, text, 0, 1);
+        b.build()
+    }
+
+    /// Get an object whose `_start` exits with the value `main` returns.
+    fn exit_with_main() -> Object {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let code = vec![
+            0xe8, 0, 0, 0, 0,               // call main
+            0x89, 0xc7,                     // mov %eax,%edi
+            0xb8, 0x3c, 0, 0, 0,            // mov $60,%eax
+            0x0f, 0x05,                     // syscall
+        ];
+        let text = b.add_section(".text", section::SHF_ALLOC | section::SHF_EXECINSTR, code, 1);
+        b.define_symbol("_start", Bind::Global, SymType::Func, text, 0, 14);
+        let main = b.declare_symbol("main");
+        b.add_relocation(text, 1, main, R_X86_64_PLT32, -4);
+        b.build()
+    }
+
+    /// Get the address of a symbol of a linked executable.
+    fn addr(exe: &Object, name: &str) -> u64 {
+        exe.symbols().iter().find(|s| s.name.as_deref() == Some(name)).unwrap().value
+    }
+
+    /// Get the value of the 32-bit integer at the symbol `x` of a linked
+    /// executable.
+    fn x_value(exe: &Object) -> Option<u32> {
+        let x = exe.symbols().iter().find(|s| s.name.as_deref() == Some("x"))?;
+        let section = &exe.sections()[x.shndx];
+        if section.is_nobits() {
+            return Some(0);
+        }
+        let off = (x.value - section.addr) as usize;
+        Some(util::get_u32(&section.data, off))
+    }
+
+    #[test]
+    fn strong_beats_larger_common() {
+        for common_last in &[true, false] {
+            let mut linker = Linker::new();
+            linker.add_object(start());
+            if *common_last {
+                linker.add_object(strong_x());
+                linker.add_object(common_x());
+            } else {
+                linker.add_object(common_x());
+                linker.add_object(strong_x());
+            }
+            let exe = linker.link().unwrap();
+            assert_eq!(x_value(&exe), Some(42));
+        }
+    }
+
+    #[test]
+    fn link_sample() {
+        let mut linker = Linker::new();
+        linker.add_object(exit_with_main());
+        linker.add_object(Object::from_bytes(&sample("main.o")));
+        let exe = Object::from_bytes(&crate::tests::write(&linker.link().unwrap()));
+        assert!(exe.header().is_exec());
+        assert_eq!(exe.header().entry, addr(&exe, "_start"));
+
+        /* every section is mapped at its file offset */
+        let loads: Vec<_> = exe.segments().iter().filter(|s| s.is_load()).collect();
+        assert!(!loads.is_empty() && loads[0].offset == 0);
+        for section in exe.sections().iter().filter(|s| s.is_alloc()) {
+            let seg = loads.iter()
+                .find(|s| s.vaddr <= section.addr && section.addr + section.size <= s.vaddr + s.memsz)
+                .unwrap();
+            assert_eq!(section.addr - seg.vaddr, section.offset - seg.offset);
+            assert_eq!(seg.vaddr % PAGE, seg.offset % PAGE);
+        }
+
+        /* both calls land on their targets */
+        let text = &exe.sections()[exe.section_index(".text").unwrap()];
+        let target = |call: u64| {
+            let at = (call + 1 - text.addr) as usize;
+            let rel = util::get_u32(&text.data, at) as i32 as i64;
+            (call as i64 + 5 + rel) as u64
+        };
+        assert_eq!(target(addr(&exe, "_start")), addr(&exe, "main"));
+        assert_eq!(target(addr(&exe, "main") + 0x4e - 0x26), addr(&exe, "global_f"));
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn run_linked_sample() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut linker = Linker::new();
+        linker.add_object(exit_with_main());
+        linker.add_object(Object::from_bytes(&sample("main.o")));
+        let path = std::env::temp_dir().join(format!("elf-link-{}", std::process::id()));
+        linker.link().unwrap().to_file(path.to_str().unwrap()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(status.code(), Some(105));
+    }
+
+    #[test]
+    fn rejects_tls() {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let flags = section::SHF_ALLOC | section::SHF_WRITE | section::SHF_TLS;
+        let tdata = b.add_section(".tdata", flags, vec![0; 4], 4);
+        b.define_symbol("counter", Bind::Global, SymType::TLS, tdata, 0, 4);
+        let mut linker = Linker::new();
+        linker.add_object(start());
+        linker.add_object(b.build());
+        assert!(matches!(linker.link(), Err(Error::Tls(name)) if name == ".tdata"));
+    }
+}
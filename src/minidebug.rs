@@ -0,0 +1,119 @@
+//! Regarding MiniDebugInfo (`.gnu_debugdata`).
+//!
+//! Some distributions strip their binaries, but keep an xz compressed
+//! ELF file in `.gnu_debugdata`, holding a symbol table with the local
+//! functions the dynamic symbol table lacks. Decompressing it needs the
+//! `xz` feature.
+
+use std::fmt;
+
+use crate::object::Object;
+
+/// Name of the section holding the MiniDebugInfo.
+pub const GNU_DEBUGDATA: &str = ".gnu_debugdata";
+
+/// Errors found while extracting the MiniDebugInfo.
+#[derive(Debug)]
+pub enum Error {
+    /// The `xz` feature is disabled.
+    Unsupported,
+    /// The contents could not be decompressed, or are not an ELF file.
+    Corrupt(String),
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Get the object embedded in `.gnu_debugdata`, if any.
+        pub fn mini_debuginfo(&self) -> Result<Option<Object>, Error> {
+            let ndx = match self.section_index(GNU_DEBUGDATA) {
+                Some(ndx) => ndx,
+                None      => return Ok(None),
+            };
+            let data = xz_decompress(&self.sections[ndx].data)?;
+            if !Object::is_readable(&data) {
+                return Err(Error::Corrupt("not a readable ELF file".to_string()));
+            }
+            Ok(Some(Object::from_bytes(&data)))
+        }
+    }
+
+    /// Decompresses an xz stream.
+    #[cfg(feature = "xz")]
+    fn xz_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        lzma_rs::xz_decompress(&mut std::io::BufReader::new(data), &mut out)
+            .map_err(|e| Error::Corrupt(e.to_string()))?;
+        Ok(out)
+    }
+
+    /// Decompresses an xz stream.
+    #[cfg(not(feature = "xz"))]
+    fn xz_decompress(_data: &[u8]) -> Result<Vec<u8>, Error> {
+        Err(Error::Unsupported)
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe a MiniDebugInfo error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Unsupported  => write!(f, "xz decompression is disabled"),
+                Error::Corrupt(why) => write!(f, "corrupt MiniDebugInfo: {}", why),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    /// Get the address of a function of the unstripped sample.
+    fn addr(name: &str) -> u64 {
+        let obj = Object::from_bytes(&sample("main"));
+        obj.symbols().iter().find(|s| s.name.as_deref() == Some(name)).unwrap().value
+    }
+
+    /* main-mini is main stripped, with the function symbols the dynamic
+     * symbol table lacks kept in an xz compressed `.gnu_debugdata`, as
+     * the Fedora `find-debuginfo` script does
+     */
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn local_function() {
+        let obj = Object::from_bytes(&sample("main-mini"));
+        assert!(obj.symbols().is_empty());
+        let mini = obj.mini_debuginfo().unwrap().unwrap();
+        assert!(mini.symbols().iter().any(|s| s.name.as_deref() == Some("local_f")));
+
+        let local_f = addr("local_f");
+        let func = obj.symbolize(local_f + 4).unwrap();
+        assert_eq!((func.name.as_str(), func.addr, func.offset), ("local_f", local_f, 4));
+    }
+
+    #[cfg(not(feature = "xz"))]
+    #[test]
+    fn local_function() {
+        let obj = Object::from_bytes(&sample("main-mini"));
+        assert!(matches!(obj.mini_debuginfo(), Err(Error::Unsupported)));
+        assert!(obj.symbolize(addr("local_f")).is_none());
+    }
+
+    #[test]
+    fn corrupt() {
+        let mut obj = Object::from_bytes(&sample("main-mini"));
+        let ndx = obj.section_index(GNU_DEBUGDATA).unwrap();
+        obj.sections_mut()[ndx].data.truncate(32);
+        assert!(obj.mini_debuginfo().is_err());
+        assert!(Object::from_bytes(&sample("main")).mini_debuginfo().unwrap().is_none());
+    }
+}
@@ -0,0 +1,116 @@
+//! Regarding notes (`SHT_NOTE` sections and `PT_NOTE` segments).
+//!
+//! Each note is an owner name with a type and a descriptor. Both the
+//! name and the descriptor are padded to four bytes.
+
+use crate::util;
+
+/// GNU note: ABI tag.
+pub const NT_GNU_ABI_TAG: u32 = 1;
+/// GNU note: build ID.
+pub const NT_GNU_BUILD_ID: u32 = 3;
+/// GNU note: program properties.
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+
+/// Owner name of GNU notes.
+pub const GNU: &str = "GNU";
+
+/// Represents a note.
+#[derive(Clone)]
+pub struct Note {
+    /// Owner name.
+    pub name:   String,
+    /// Note type, depending on the owner.
+    pub ntype:  u32,
+    /// Descriptor.
+    pub desc:   Vec<u8>,
+    /// Offset of the note in its section.
+    pub offset: usize,
+}
+
+/// Simple note methods.
+impl Note {
+    /// Creates a note.
+    pub fn new(name: &str, ntype: u32, desc: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            ntype,
+            desc,
+            offset: 0,
+        }
+    }
+
+    /// Get the offset of the descriptor in the section.
+    pub fn desc_offset(&self) -> usize {
+        self.offset + 12 + util::align(self.name.len() as u64 + 1, 4) as usize
+    }
+
+    /// Check if the note is a GNU build ID.
+    pub fn is_build_id(&self) -> bool {
+        self.name == GNU && self.ntype == NT_GNU_BUILD_ID
+    }
+}
+
+/// Buffer IO methods.
+pub mod io {
+    use super::*;
+
+    impl Note {
+        /// Extract every note from the contents of a note section.
+        pub fn extract_all(data: &[u8]) -> Vec<Self> {
+            let mut notes = vec![];
+            let mut off = 0;
+            while off + 12 <= data.len() {
+                let namesz = util::get_u32(data, off) as usize;
+                let descsz = util::get_u32(data, off + 4) as usize;
+                let ntype = util::get_u32(data, off + 8);
+                let name_off = off + 12;
+                let desc_off = name_off + util::align(namesz as u64, 4) as usize;
+                let end = desc_off + util::align(descsz as u64, 4) as usize;
+                if desc_off + descsz > data.len() {
+                    break;
+                }
+                notes.push(Self {
+                    name: util::get_str(&data[..name_off + namesz], name_off),
+                    ntype,
+                    desc: data[desc_off..desc_off + descsz].to_vec(),
+                    offset: off,
+                });
+                off = end;
+            }
+            notes
+        }
+
+        /// Encode a note, padding included.
+        pub fn encode(&self) -> Vec<u8> {
+            let namesz = self.name.len() + 1;
+            let mut data = vec![];
+            data.extend_from_slice(&(namesz as u32).to_le_bytes());
+            data.extend_from_slice(&(self.desc.len() as u32).to_le_bytes());
+            data.extend_from_slice(&self.ntype.to_le_bytes());
+            data.extend_from_slice(self.name.as_bytes());
+            data.resize(12 + util::align(namesz as u64, 4) as usize, 0);
+            data.extend_from_slice(&self.desc);
+            data.resize(util::align(data.len() as u64, 4) as usize, 0);
+            data
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use crate::object::Object;
+
+    impl Object {
+        /// Get the notes of every note section, along with the section
+        /// index.
+        pub fn notes(&self) -> Vec<(usize, Note)> {
+            self.sections.iter()
+                .enumerate()
+                .filter(|(_, s)| s.is_note())
+                .flat_map(|(i, s)| Note::extract_all(&s.data).into_iter().map(move |n| (i, n)))
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,612 @@
+//! Regarding `objcopy` style section manipulation.
+//!
+//! Sections can be added, removed, renamed, have their flags changed or
+//! their contents dumped. Removing a section renumbers the following
+//! ones, so everything referring to section indices is updated:
+//! - The section header string table index.
+//! - `link` and `info` of every section, and the members of groups.
+//! - `shndx` of every symbol and dynamic symbol.
+//!
+//! Relocation sections of a removed section are removed with it, and so
+//! are the symbols defined in it. Relocations of other sections against
+//! those symbols are an error, like `objcopy` reports, the others are
+//! renumbered. Sections others still link to, like string tables, are
+//! kept.
+//!
+//! Symbols of the symbol table can be renamed, prefixed, have their
+//! binding changed or be stripped. Locals are then sorted back before
+//! globals, and relocations and groups renumbered accordingly. A string
+//! table shared by section and symbol names, as LLVM writes, is rebuilt
+//! with both.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+
+use crate::object::Object;
+use crate::rel::Rela;
+use crate::section::{self, Section, Type as SectionType};
+use crate::sym::{Sym, Bind, Type as SymType, SHN_ABS, SHN_UNDEF};
+use crate::util;
+
+/// First reserved section index, like `SHN_ABS` and `SHN_COMMON`.
+const SHN_LORESERVE: usize = 0xff00;
+
+/// Errors found while removing sections.
+#[derive(Debug)]
+pub enum Error {
+    /// The section with the given index is the null section, the section
+    /// header string table, or does not exist.
+    Reserved(usize),
+    /// Another section links to the section with the given index.
+    Linked(usize),
+    /// A relocation of a kept section refers to the symbol with the
+    /// given name, defined in a removed section.
+    Relocated(String),
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Adds a section with the given contents at the end, and gets
+        /// its index.
+        ///
+        /// The section is not mapped by any segment, so it should not be
+        /// `SHF_ALLOC` in executables and shared objects.
+        pub fn add_section(&mut self, name: &str, flags: u64, data: Vec<u8>) -> usize {
+            let mut section = Section::new(name, SectionType::Progbits, flags);
+            section.addralign = 1;
+            section.size = data.len() as u64;
+            section.data = data;
+            self.sections.push(section);
+            self.layout();
+            self.sections.len() - 1
+        }
+
+        /// Removes a section, along with the relocation sections
+        /// applying to it.
+        ///
+        /// The null section, the section header string table, any
+        /// section another one links to, like the string table of a
+        /// symbol table, and any section relocations of others refer to
+        /// cannot be removed.
+        pub fn remove_section(&mut self, ndx: usize) -> Result<(), Error> {
+            if ndx == 0 || ndx == self.header.shstrndx || ndx >= self.sections.len() {
+                return Err(Error::Reserved(ndx));
+            }
+            let mut removed = vec![ndx];
+            for (i, section) in self.sections.iter().enumerate() {
+                if section.is_rela() && section.info as usize == ndx {
+                    removed.push(i);
+                }
+            }
+            let linked = self.sections.iter()
+                .enumerate()
+                .any(|(i, s)| !removed.contains(&i) && removed.contains(&s.link));
+            if linked {
+                return Err(Error::Linked(ndx));
+            }
+            self.remove_sections(&removed)
+        }
+
+        /// Renames a section, along with the relocation sections
+        /// applying to it.
+        pub fn rename_section(&mut self, ndx: usize, name: &str) {
+            let old = self.sections[ndx].name.clone().unwrap_or_default();
+            for section in &mut self.sections {
+                if section.is_rela() && section.info as usize == ndx
+                    && section.name.as_deref() == Some(&format!(".rela{}", old)) {
+                    section.name = Some(format!(".rela{}", name));
+                }
+            }
+            self.sections[ndx].name = Some(name.to_string());
+            self.layout();
+        }
+
+        /// Sets the flags of a section.
+        pub fn set_section_flags(&mut self, ndx: usize, flags: u64) {
+            self.sections[ndx].flags = flags;
+            self.layout();
+        }
+
+        /// Writes the contents of a section into a file with the given
+        /// name.
+        pub fn dump_section(&self, ndx: usize, filename: &str) -> io::Result<()> {
+            std::fs::write(filename, &self.sections[ndx].data)
+        }
+    }
+}
+
+/// Symbol methods.
+mod symbols {
+    use super::*;
+
+    impl Object {
+        /// Renames every symbol with the given name.
+        pub fn redefine_symbol(&mut self, old: &str, new: &str) {
+            self.edit_symbols(|sym| {
+                if sym.name.as_deref() == Some(old) {
+                    sym.name = Some(new.to_string());
+                }
+            });
+        }
+
+        /// Prefixes the name of every symbol but section and file ones.
+        pub fn prefix_symbols(&mut self, prefix: &str) {
+            self.edit_symbols(|sym| {
+                if sym.is_section() || sym.etype == SymType::File {
+                    return;
+                }
+                if let Some(name) = sym.name.as_mut().filter(|n| !n.is_empty()) {
+                    name.insert_str(0, prefix);
+                }
+            });
+        }
+
+        /// Makes a defined symbol local.
+        pub fn localize_symbol(&mut self, name: &str) {
+            self.edit_symbols(|sym| {
+                if sym.name.as_deref() == Some(name) && !sym.is_undefined() {
+                    sym.bind = Bind::Local;
+                }
+            });
+        }
+
+        /// Makes a defined local symbol global.
+        pub fn globalize_symbol(&mut self, name: &str) {
+            self.edit_symbols(|sym| {
+                if sym.name.as_deref() == Some(name) && !sym.is_undefined()
+                    && sym.is_local() && !sym.is_section() && sym.etype != SymType::File {
+                    sym.bind = Bind::Global;
+                }
+            });
+        }
+
+        /// Makes a defined global symbol weak.
+        pub fn weaken_symbol(&mut self, name: &str) {
+            self.edit_symbols(|sym| {
+                if sym.name.as_deref() == Some(name) && !sym.is_undefined()
+                    && sym.bind == Bind::Global {
+                    sym.bind = Bind::Weak;
+                }
+            });
+        }
+
+        /// Makes every defined global symbol weak.
+        pub fn weaken(&mut self) {
+            self.edit_symbols(|sym| {
+                if !sym.is_undefined() && sym.bind == Bind::Global {
+                    sym.bind = Bind::Weak;
+                }
+            });
+        }
+
+        /// Removes the symbols with the given name, unless a relocation
+        /// refers to them.
+        ///
+        /// Returns whether any was removed.
+        pub fn strip_symbol(&mut self, name: &str) -> bool {
+            let used = self.relocated_symbols();
+            let stripped = self.symbols.iter()
+                .enumerate()
+                .any(|(i, s)| s.name.as_deref() == Some(name) && !used.contains(&i));
+            self.retain_symbols(|sym| sym.name.as_deref() != Some(name));
+            stripped
+        }
+    }
+}
+
+/// Editing methods.
+mod editing {
+    use super::*;
+
+    impl Object {
+        /// Removes the sections with the given indices, renumbering
+        /// everything referring to the others.
+        ///
+        /// Nothing is removed when relocations of kept sections refer to
+        /// symbols defined in removed ones.
+        pub(crate) fn remove_sections(&mut self, removed: &[usize]) -> Result<(), Error> {
+            self.check_relocated(removed)?;
+
+            /* new index of every section */
+            let mut map = vec![None; self.sections.len()];
+            let mut next = 0;
+            for (i, slot) in map.iter_mut().enumerate() {
+                if !removed.contains(&i) {
+                    *slot = Some(next);
+                    next += 1;
+                }
+            }
+            let remap = |ndx: usize| -> usize {
+                if ndx == 0 || ndx >= map.len() {
+                    return ndx;
+                }
+                map[ndx].unwrap_or(0)
+            };
+
+            /* symbols defined in removed sections go away */
+            let defined_in_removed = |sym: &Sym| {
+                sym.shndx != SHN_UNDEF && sym.shndx < SHN_LORESERVE && removed.contains(&sym.shndx)
+            };
+            let symbols = std::mem::take(&mut self.symbols);
+            let mut symmap = vec![None; symbols.len()];
+            let mut kept = vec![];
+            for (i, mut sym) in symbols.into_iter().enumerate() {
+                if i != 0 && defined_in_removed(&sym) {
+                    continue;
+                }
+                if sym.shndx < SHN_LORESERVE {
+                    sym.shndx = remap(sym.shndx);
+                }
+                symmap[i] = Some(kept.len());
+                kept.push(sym);
+            }
+            self.symbols = kept;
+
+            /* dynamic symbols can not go away: they become absolute */
+            for sym in &mut self.dynsyms {
+                if defined_in_removed(sym) {
+                    sym.shndx = SHN_ABS;
+                } else if sym.shndx < SHN_LORESERVE {
+                    sym.shndx = remap(sym.shndx);
+                }
+            }
+
+            /* drop the sections, then fix the references */
+            let symtab = self.sections.iter().position(|s| s.is_symtab());
+            let sections = std::mem::take(&mut self.sections);
+            self.sections = sections.into_iter()
+                .enumerate()
+                .filter(|(i, _)| !removed.contains(i))
+                .map(|(_, s)| s)
+                .collect();
+            for section in &mut self.sections {
+                section.link = remap(section.link);
+                if section.is_rela() || section.flags & section::SHF_INFO_LINK != 0 {
+                    section.info = remap(section.info as usize) as u32;
+                }
+                if section.is_group() {
+                    let mut data = section.data[..4].to_vec();
+                    for member in section.data[4..].chunks_exact(4) {
+                        let ndx = util::get_u32(member, 0) as usize;
+                        if let Some(Some(ndx)) = map.get(ndx) {
+                            data.extend_from_slice(&(*ndx as u32).to_le_bytes());
+                        }
+                    }
+                    section.data = data;
+                }
+            }
+            self.header.shstrndx = remap(self.header.shstrndx);
+
+            if let Some(symtab) = symtab.and_then(|i| map[i]) {
+                self.remap_symbols(symtab, &symmap);
+                self.write_symbols();
+            }
+            self.write_dynsyms();
+            self.layout();
+            Ok(())
+        }
+
+        /// Check no relocation of a kept section, applying to a kept
+        /// section, refers to a symbol defined in a removed one.
+        fn check_relocated(&self, removed: &[usize]) -> Result<(), Error> {
+            for (i, section) in self.sections.iter().enumerate() {
+                let kept = !removed.contains(&i) && !removed.contains(&(section.info as usize));
+                let symtab = self.sections.get(section.link).is_some_and(|s| s.is_symtab());
+                if !section.is_rela() || !kept || !symtab {
+                    continue;
+                }
+                for rela in self.relocations(i) {
+                    let sym = match self.symbols.get(rela.sym) {
+                        Some(sym) if rela.sym != 0 => sym,
+                        _                          => continue,
+                    };
+                    let ndx = sym.shndx;
+                    if ndx != SHN_UNDEF && ndx < SHN_LORESERVE && removed.contains(&ndx) {
+                        let name = match &sym.name {
+                            Some(name) if !sym.is_section() => name.clone(),
+                            _ => self.sections[ndx].name.clone().unwrap_or_default(),
+                        };
+                        return Err(Error::Relocated(name));
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Applies an edit to every symbol of the symbol table, then sorts
+        /// locals back before globals.
+        pub(crate) fn edit_symbols<F>(&mut self, edit: F)
+            where F: FnMut(&mut Sym)
+        {
+            let symtab = match self.sections.iter().position(|s| s.is_symtab()) {
+                Some(symtab) => symtab,
+                None         => return,
+            };
+            self.symbols.iter_mut().skip(1).for_each(edit);
+
+            /* stable sort, keeping the null symbol first */
+            let mut order: Vec<usize> = (1..self.symbols.len()).collect();
+            order.sort_by_key(|&i| !self.symbols[i].is_local());
+            order.insert(0, 0);
+            let mut symmap = vec![None; self.symbols.len()];
+            for (new, &old) in order.iter().enumerate() {
+                symmap[old] = Some(new);
+            }
+            self.symbols = order.iter().map(|&i| self.symbols[i].clone()).collect();
+
+            self.remap_symbols(symtab, &symmap);
+            self.write_symbols();
+            self.layout();
+        }
+
+        /// Keeps the symbols matching a predicate, along with the null
+        /// symbol and those relocations refer to.
+        pub(crate) fn retain_symbols<F>(&mut self, keep: F)
+            where F: Fn(&Sym) -> bool
+        {
+            let symtab = match self.sections.iter().position(|s| s.is_symtab()) {
+                Some(symtab) => symtab,
+                None         => return,
+            };
+            let used = self.relocated_symbols();
+            let mut symmap = vec![None; self.symbols.len()];
+            let mut kept = vec![];
+            for (i, sym) in std::mem::take(&mut self.symbols).into_iter().enumerate() {
+                if i == 0 || used.contains(&i) || keep(&sym) {
+                    symmap[i] = Some(kept.len());
+                    kept.push(sym);
+                }
+            }
+            self.symbols = kept;
+
+            self.remap_symbols(symtab, &symmap);
+            self.write_symbols();
+            self.layout();
+        }
+
+        /// Get the indices of the symbols relocations refer to.
+        pub(crate) fn relocated_symbols(&self) -> HashSet<usize> {
+            let symtab = match self.sections.iter().position(|s| s.is_symtab()) {
+                Some(symtab) => symtab,
+                None         => return HashSet::new(),
+            };
+            (0..self.sections.len())
+                .filter(|&i| self.sections[i].is_rela() && self.sections[i].link == symtab)
+                .flat_map(|i| self.relocations(i))
+                .map(|r| r.sym)
+                .collect()
+        }
+
+        /// Renumbers the symbol references of relocations and groups
+        /// against the symbol table with the given index.
+        ///
+        /// Relocations against symbols mapped to nothing are dropped.
+        pub(crate) fn remap_symbols(&mut self, symtab: usize, symmap: &[Option<usize>]) {
+            for i in 0..self.sections.len() {
+                let section = &self.sections[i];
+                if section.link != symtab {
+                    continue;
+                }
+                if section.is_rela() {
+                    let relocs: Vec<Rela> = self.relocations(i).into_iter()
+                        .filter_map(|mut r| {
+                            r.sym = symmap.get(r.sym).copied().flatten()?;
+                            Some(r)
+                        })
+                        .collect();
+                    self.set_relocations(i, &relocs);
+                } else if section.is_group() {
+                    let sym = symmap.get(section.info as usize).copied().flatten();
+                    self.sections[i].info = sym.unwrap_or(0) as u32;
+                }
+            }
+        }
+
+        /// Encodes the symbols back into the symbol table and its string
+        /// table, with `info` pointing at the first non local symbol.
+        ///
+        /// When the string table also holds the section names, they are
+        /// written first.
+        pub(crate) fn write_symbols(&mut self) {
+            let ndx = match self.sections.iter().position(|s| s.is_symtab()) {
+                Some(ndx) => ndx,
+                None      => return,
+            };
+            let strtab = self.sections[ndx].link;
+            let mut names = util::StrTab::new();
+            if strtab != 0 && strtab == self.header.shstrndx {
+                for section in &mut self.sections {
+                    section.nameoff = names.add(section.name.as_deref().unwrap_or(""));
+                }
+            }
+            let symdata = Sym::encode_into(&mut self.symbols, &mut names);
+            let strdata = names.data;
+            let first_global = self.symbols.iter()
+                .position(|s| !s.is_local())
+                .unwrap_or(self.symbols.len());
+            self.sections[ndx].data = symdata;
+            self.sections[ndx].info = first_global as u32;
+            if strtab != 0 && self.sections.get(strtab).is_some_and(|s| s.is_strtab()) {
+                self.sections[strtab].data = strdata;
+            }
+        }
+
+        /// Writes the dynamic symbols back in place, keeping their names.
+        pub(crate) fn write_dynsyms(&mut self) {
+            let ndx = match self.sections.iter().position(|s| s.is_dynsym()) {
+                Some(ndx) => ndx,
+                None      => return,
+            };
+            let mut data = vec![];
+            for sym in &self.dynsyms {
+                sym.write(&mut data).unwrap();
+            }
+            self.sections[ndx].data = data;
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe a removal error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Reserved(i)      => write!(f, "section {} cannot be removed", i),
+                Error::Linked(i)        => write!(f, "section {} is linked to by another", i),
+                Error::Relocated(name)  => write!(f, "relocation against removed `{}`", name),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample, write};
+
+    #[test]
+    fn remove_linked_string_table() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        let strtab = obj.section_index(".strtab").unwrap();
+        assert!(matches!(obj.remove_section(strtab), Err(Error::Linked(_))));
+        let comment = obj.section_index(".comment").unwrap();
+        assert!(obj.remove_section(comment).is_ok());
+
+        let obj = Object::from_bytes(&write(&obj));
+        assert!(obj.section_index(".comment").is_none());
+        assert!(obj.symbols().iter().any(|s| s.name.as_deref() == Some("global_f")));
+    }
+
+    #[test]
+    fn remove_relocated_section() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        let count = obj.sections().len();
+        let text = obj.section_index(".text").unwrap();
+        assert!(matches!(obj.remove_section(text), Err(Error::Relocated(name)) if name == ".text"));
+        assert_eq!(obj.sections().len(), count);
+
+        /* without the frame descriptions, nothing refers to it anymore */
+        let eh_frame = obj.section_index(".eh_frame").unwrap();
+        obj.remove_section(eh_frame).unwrap();
+        let text = obj.section_index(".text").unwrap();
+        obj.remove_section(text).unwrap();
+        let obj = Object::from_bytes(&write(&obj));
+        assert!(obj.section_index(".rela.text").is_none());
+        assert!(obj.symbols().iter().all(|s| s.name.as_deref() != Some("global_f")));
+    }
+
+    #[test]
+    fn shared_string_table() {
+        /* section and symbol names in one table, as LLVM writes */
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        let symtab = obj.section_index(".symtab").unwrap();
+        obj.sections[symtab].link = obj.header.shstrndx;
+        obj.write_symbols();
+        obj.layout();
+        let strtab = obj.section_index(".strtab").unwrap();
+        obj.remove_section(strtab).unwrap();
+        let mut obj = Object::from_bytes(&write(&obj));
+        let symtab = obj.section_index(".symtab").unwrap();
+        assert_eq!(obj.sections()[symtab].link, obj.header.shstrndx);
+
+        obj.redefine_symbol("global_f", "renamed_f");
+        let comment = obj.section_index(".comment").unwrap();
+        obj.rename_section(comment, ".comment.renamed");
+        let obj = Object::from_bytes(&write(&obj));
+        let names: Vec<_> = obj.sections().iter().map(|s| s.name.clone().unwrap()).collect();
+        assert_eq!(names, ["", ".text", ".rela.text", ".data", ".bss", ".comment.renamed",
+            ".note.GNU-stack", ".eh_frame", ".rela.eh_frame", ".symtab", ".shstrtab"]);
+        let syms: Vec<_> = obj.symbols().iter()
+            .filter(|s| !s.is_section())
+            .map(|s| s.name.clone().unwrap())
+            .collect();
+        assert_eq!(syms, ["", "main.c", "local_f", "renamed_f", "main"]);
+    }
+
+    /// Get the names of the symbols the relocations of a section refer
+    /// to, section symbols by the section name.
+    fn relocated(obj: &Object, name: &str) -> Vec<String> {
+        let rela = obj.section_index(name).unwrap();
+        obj.relocations(rela).iter()
+            .map(|r| {
+                let sym = &obj.symbols()[r.sym];
+                match sym.is_section() {
+                    true  => obj.sections()[sym.shndx].name.clone().unwrap(),
+                    false => sym.name.clone().unwrap(),
+                }
+            })
+            .collect()
+    }
+
+    /// Get the binding of the symbol with the given name.
+    fn bind(obj: &Object, name: &str) -> Bind {
+        obj.symbols().iter().find(|s| s.name.as_deref() == Some(name)).unwrap().bind
+    }
+
+    /// Check locals come first, and the symbol table says so.
+    fn check_order(obj: &Object) {
+        let first_global = obj.symbols().iter().position(|s| !s.is_local()).unwrap();
+        assert!(obj.symbols()[first_global..].iter().all(|s| !s.is_local()));
+        let symtab = obj.section_index(".symtab").unwrap();
+        assert_eq!(obj.sections()[symtab].info as usize, first_global);
+    }
+
+    #[test]
+    fn redefine_and_prefix() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        obj.redefine_symbol("global_f", "renamed_f");
+        obj.prefix_symbols("p_");
+        let obj = Object::from_bytes(&write(&obj));
+        let names: Vec<_> = obj.symbols().iter()
+            .filter(|s| !s.is_section())
+            .map(|s| s.name.clone().unwrap())
+            .collect();
+        assert_eq!(names, ["", "main.c", "p_local_f", "p_renamed_f", "p_main"]);
+        assert_eq!(relocated(&obj, ".rela.text"), ["p_renamed_f"]);
+        assert_eq!(relocated(&obj, ".rela.eh_frame"), [".text"; 3]);
+    }
+
+    #[test]
+    fn change_binding() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        obj.localize_symbol("global_f");
+        obj.globalize_symbol("local_f");
+        obj.weaken_symbol("main");
+        let obj = Object::from_bytes(&write(&obj));
+        check_order(&obj);
+        assert!(bind(&obj, "global_f") == Bind::Local);
+        assert!(bind(&obj, "local_f") == Bind::Global);
+        assert!(bind(&obj, "main") == Bind::Weak);
+        assert_eq!(relocated(&obj, ".rela.text"), ["global_f"]);
+        assert_eq!(relocated(&obj, ".rela.eh_frame"), [".text"; 3]);
+
+        let mut obj = obj;
+        obj.weaken();
+        assert!(bind(&obj, "local_f") == Bind::Weak);
+        assert!(bind(&obj, "global_f") == Bind::Local);
+    }
+
+    #[test]
+    fn strip_and_remap() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        let before = obj.symbols().iter().position(|s| s.name.as_deref() == Some("global_f"));
+        assert!(!obj.strip_symbol("global_f"));
+        assert!(obj.strip_symbol("local_f"));
+        let obj = Object::from_bytes(&write(&obj));
+        check_order(&obj);
+        assert!(obj.symbols().iter().all(|s| s.name.as_deref() != Some("local_f")));
+
+        /* the relocation follows its symbol to its new index */
+        let after = obj.symbols().iter().position(|s| s.name.as_deref() == Some("global_f"));
+        assert_eq!(after.unwrap() + 1, before.unwrap());
+        assert_eq!(relocated(&obj, ".rela.text"), ["global_f"]);
+        assert_eq!(relocated(&obj, ".rela.eh_frame"), [".text"; 3]);
+    }
+}
@@ -0,0 +1,516 @@
+//! Regarding partial linking (`ld -r`) of relocatable objects.
+//!
+//! The objects are combined into a single relocatable object:
+//! - Sections with the same name are concatenated, honoring alignment,
+//!   except members of section groups, which are kept apart.
+//! - COMDAT groups are deduplicated by signature, the first copy wins
+//!   and the members of later copies are dropped.
+//! - Symbols are renumbered: locals are kept per object, globals are
+//!   resolved by name with the same rules as a full link.
+//! - Relocations are rewritten against the merged sections, moving the
+//!   offset of section symbols into the addend.
+//! - Frame descriptions in `.eh_frame` for code in dropped groups are
+//!   removed.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::builder::{ObjectBuilder, NO_SYMBOL};
+use crate::link::{self, Error, Linker};
+use crate::object::Object;
+use crate::section::{self, Section};
+use crate::sym::{Sym, Bind, SHN_UNDEF, SHN_ABS, SHN_COMMON};
+use crate::util;
+
+/// Linking methods.
+mod linking {
+    use super::*;
+
+    /// State of a partial link in progress.
+    struct State<'a> {
+        /// Input objects.
+        objects:    &'a [Object],
+        /// Output sections, before being handed to the builder.
+        outs:       Vec<Section>,
+        /// Output section and offset of every input section.
+        place:      Vec<Vec<Option<(usize, u64)>>>,
+        /// Kept groups: object, signature symbol, flags and members.
+        groups:     Vec<(usize, usize, u32, Vec<usize>)>,
+        /// Definition (or first reference) of every global symbol.
+        globals:    HashMap<String, (usize, usize)>,
+        /// Builder id of every global symbol.
+        global_ids: HashMap<String, usize>,
+        /// Builder id of every local symbol.
+        local_ids:  HashMap<(usize, usize), usize>,
+        /// Kept byte ranges of filtered input sections.
+        remaps:     HashMap<(usize, usize), Ranges>,
+    }
+
+    impl Linker {
+        /// Combines every object added into a single relocatable object.
+        pub fn link_relocatable(&self) -> Result<Object, Error> {
+            let mut state = State {
+                objects:    &self.objects,
+                outs:       vec![],
+                place:      vec![],
+                groups:     vec![],
+                globals:    HashMap::new(),
+                global_ids: HashMap::new(),
+                local_ids:  HashMap::new(),
+                remaps:     HashMap::new(),
+            };
+            state.place_sections();
+            state.resolve_symbols()?;
+
+            let machine = self.objects.first().map_or(0, |o| o.header().machine());
+            let mut builder = ObjectBuilder::new(machine);
+            for section in state.outs.drain(..) {
+                builder.add_raw_section(section);
+            }
+            state.add_symbols(&mut builder);
+            state.add_relocations(&mut builder)?;
+            state.add_groups(&mut builder);
+            Ok(builder.build())
+        }
+    }
+
+    impl<'a> State<'a> {
+        /// Decides where every input section goes.
+        fn place_sections(&mut self) {
+            let mut signatures = HashSet::new();
+            let mut merged: HashMap<String, usize> = HashMap::new();
+
+            let objects = self.objects;
+            for (o, obj) in objects.iter().enumerate() {
+                /* which sections are dropped, and which are kept apart */
+                let mut dropped = HashSet::new();
+                let mut grouped = HashSet::new();
+                for section in obj.sections().iter().filter(|s| s.is_group()) {
+                    /* malformed groups are ignored */
+                    let sig = section.info as usize;
+                    let name = match obj.symbols().get(sig) {
+                        Some(sym) if section.data.len() >= 4 => sym.name.clone().unwrap_or_default(),
+                        _ => continue,
+                    };
+                    let flags = util::get_u32(&section.data, 0);
+                    let members: Vec<usize> = section.data[4..].chunks_exact(4)
+                        .map(|c| util::get_u32(c, 0) as usize)
+                        .collect();
+                    if flags & section::GRP_COMDAT != 0 && !signatures.insert(name) {
+                        dropped.extend(members);
+                        continue;
+                    }
+                    grouped.extend(members.iter().copied());
+                    self.groups.push((o, sig, flags, members));
+                }
+
+                let mut place = vec![];
+                for (i, section) in obj.sections().iter().enumerate() {
+                    let strtab = obj.sections().iter()
+                        .any(|s| s.is_symtab() && s.link == i);
+                    let skip = i == 0 || i == obj.header().shstrndx || strtab
+                        || section.is_symtab() || section.is_rela() || section.is_group()
+                        || dropped.contains(&i);
+                    if skip {
+                        place.push(None);
+                        continue;
+                    }
+
+                    /* group members are never merged */
+                    if grouped.contains(&i) {
+                        place.push(Some((self.outs.len(), 0)));
+                        self.outs.push(section.clone());
+                        continue;
+                    }
+
+                    /* drop frame descriptions of dropped code */
+                    let mut data = &section.data;
+                    let filtered;
+                    if section.name.as_deref() == Some(".eh_frame") && !dropped.is_empty() {
+                        let (kept, remap) = filter_eh_frame(obj, i, &dropped);
+                        filtered = kept;
+                        data = &filtered;
+                        self.remaps.insert((o, i), remap);
+                    }
+
+                    /* everything else is merged by name */
+                    let name = section.name.clone().unwrap_or_default();
+                    let n = match merged.get(&name) {
+                        Some(&n) => n,
+                        None => {
+                            let mut out = section.clone();
+                            out.size = 0;
+                            out.data.clear();
+                            out.flags &= !section::SHF_GROUP;
+                            self.outs.push(out);
+                            merged.insert(name, self.outs.len() - 1);
+                            self.outs.len() - 1
+                        }
+                    };
+                    let out = &mut self.outs[n];
+                    let off = util::align(out.size, section.addralign.max(1) as u64);
+                    out.addralign = out.addralign.max(section.addralign);
+                    if out.is_nobits() {
+                        out.size = off + section.size;
+                    } else {
+                        out.data.resize(off as usize, 0);
+                        out.data.extend_from_slice(data);
+                        out.size = out.data.len() as u64;
+                    }
+                    place.push(Some((n, off)));
+                }
+                self.place.push(place);
+            }
+        }
+
+        /// Check wether a symbol is defined in a kept section.
+        fn is_kept(&self, o: usize, sym: &Sym) -> bool {
+            match sym.shndx {
+                SHN_UNDEF => false,
+                SHN_ABS | SHN_COMMON => true,
+                ndx => self.place[o].get(ndx).copied().flatten().is_some(),
+            }
+        }
+
+        /// Builds the global symbol table.
+        fn resolve_symbols(&mut self) -> Result<(), Error> {
+            for (o, obj) in self.objects.iter().enumerate() {
+                for (i, sym) in obj.symbols().iter().enumerate().skip(1) {
+                    if sym.is_local() {
+                        continue;
+                    }
+                    let name = sym.name.clone().unwrap_or_default();
+                    let old = match self.globals.get(&name) {
+                        Some(&(oo, oi)) => &self.objects[oo].symbols()[oi],
+                        None => {
+                            self.globals.insert(name, (o, i));
+                            continue;
+                        }
+                    };
+                    if !self.is_kept(o, sym) {
+                        /* a reference, or a definition in a dropped copy */
+                        if old.is_undefined() && sym.bind != Bind::Weak {
+                            self.globals.insert(name, (o, i));
+                        }
+                        continue;
+                    }
+                    if old.is_undefined() {
+                        self.globals.insert(name, (o, i));
+                        continue;
+                    }
+                    let (new_rank, old_rank) = (link::rank(sym), link::rank(old));
+                    if new_rank == 2 && old_rank == 2 {
+                        return Err(Error::Duplicate(name));
+                    }
+                    /* size only decides between two common symbols */
+                    let bigger = sym.size > old.size;
+                    if new_rank > old_rank || (new_rank == 1 && old_rank == 1 && bigger) {
+                        self.globals.insert(name, (o, i));
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Adds every kept symbol to the builder.
+        fn add_symbols(&mut self, builder: &mut ObjectBuilder) {
+            /* locals, per object */
+            for (o, obj) in self.objects.iter().enumerate() {
+                for (i, sym) in obj.symbols().iter().enumerate().skip(1) {
+                    if !sym.is_local() || sym.is_section() {
+                        continue;
+                    }
+                    let mut sym = sym.clone();
+                    let section = match sym.shndx {
+                        SHN_UNDEF | SHN_ABS | SHN_COMMON => None,
+                        ndx => match self.place[o].get(ndx).copied().flatten() {
+                            Some((out, off)) => {
+                                sym.value += off;
+                                Some(out)
+                            }
+                            None => continue,
+                        },
+                    };
+                    let id = builder.add_symbol(sym, section);
+                    self.local_ids.insert((o, i), id);
+                }
+            }
+
+            /* globals, in order of first appearance */
+            let mut names: Vec<(&String, &(usize, usize))> = self.globals.iter().collect();
+            names.sort_by_key(|(_, &def)| def);
+            for (name, &(o, i)) in names {
+                let mut sym = self.objects[o].symbols()[i].clone();
+                let section = match sym.shndx {
+                    SHN_UNDEF | SHN_ABS | SHN_COMMON => None,
+                    ndx => match self.place[o].get(ndx).copied().flatten() {
+                        Some((out, off)) => {
+                            sym.value += off;
+                            Some(out)
+                        }
+                        None => {
+                            /* defined in a dropped copy only */
+                            sym.shndx = SHN_UNDEF;
+                            sym.value = 0;
+                            None
+                        }
+                    },
+                };
+                let id = builder.add_symbol(sym, section);
+                self.global_ids.insert(name.clone(), id);
+            }
+        }
+
+        /// Get the builder id for the symbol with index `i` of object
+        /// `o`, and the offset to add to the addend of relocations
+        /// against it.
+        fn symbol(&self, builder: &mut ObjectBuilder, o: usize, i: usize) -> Option<(usize, i64)> {
+            let sym = self.objects[o].symbols().get(i)?;
+            if sym.is_section() {
+                let (out, off) = self.place[o].get(sym.shndx).copied().flatten()?;
+                return Some((builder.section_symbol(out), off as i64));
+            }
+            if sym.is_local() {
+                return self.local_ids.get(&(o, i)).map(|&id| (id, 0));
+            }
+            let name = sym.name.as_deref().unwrap_or("");
+            self.global_ids.get(name).map(|&id| (id, 0))
+        }
+
+        /// Adds every relocation against a kept section to the builder.
+        ///
+        /// Relocations of dropped frame descriptions go away with them,
+        /// any other one that cannot be placed, or whose symbol was
+        /// dropped, is an error.
+        fn add_relocations(&self, builder: &mut ObjectBuilder) -> Result<(), Error> {
+            for (o, obj) in self.objects.iter().enumerate() {
+                for (r, section) in obj.sections().iter().enumerate() {
+                    if !section.is_rela() {
+                        continue;
+                    }
+                    let target = section.info as usize;
+                    let (out, off) = match self.place[o].get(target).copied().flatten() {
+                        Some(place) => place,
+                        None        => continue,
+                    };
+                    let size = obj.sections()[target].size;
+                    let remap = self.remaps.get(&(o, target));
+                    for rela in obj.relocations(r) {
+                        if rela.offset >= size {
+                            let name = obj.sections()[target].name.clone().unwrap_or_default();
+                            return Err(Error::Misplaced(name));
+                        }
+                        let offset = match remap {
+                            Some(remap) => match remap_offset(remap, rela.offset) {
+                                Some(offset) => offset,
+                                None         => continue,
+                            },
+                            None => rela.offset,
+                        };
+                        let (id, adj) = match rela.sym {
+                            0 => (NO_SYMBOL, 0),
+                            i => self.symbol(builder, o, i).ok_or_else(|| {
+                                let sym = obj.symbols().get(i);
+                                Error::Discarded(sym.and_then(|s| s.name.clone()).unwrap_or_default())
+                            })?,
+                        };
+                        builder.add_relocation(out, offset + off, id,
+                            rela.rtype, rela.addend + adj);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Adds every kept section group to the builder.
+        fn add_groups(&self, builder: &mut ObjectBuilder) {
+            for (o, sig, flags, members) in &self.groups {
+                let signature = match self.symbol(builder, *o, *sig) {
+                    Some((id, _)) => id,
+                    None          => continue,
+                };
+                let members: Vec<usize> = members.iter()
+                    .filter_map(|&m| self.place[*o].get(m).copied().flatten())
+                    .map(|(out, _)| out)
+                    .collect();
+                builder.add_group(signature, *flags, &members);
+            }
+        }
+    }
+}
+
+/// Kept byte ranges of a filtered section, as old start, old end and
+/// new start.
+type Ranges = Vec<(u64, u64, u64)>;
+
+/// Removes from the `.eh_frame` section with index `ndx` every frame
+/// description whose code lives in a dropped section.
+///
+/// Returns the kept contents, and the kept byte ranges as old start, old
+/// end and new start. The CIE pointer of kept descriptions is adjusted
+/// to the new distances.
+fn filter_eh_frame(obj: &Object, ndx: usize, dropped: &HashSet<usize>)
+    -> (Vec<u8>, Ranges)
+{
+    let data = &obj.sections()[ndx].data;
+
+    /* the code of a description is given by the relocation of its
+     * initial location, right after the length and CIE pointer
+     */
+    let mut code = HashMap::new();
+    for (r, section) in obj.sections().iter().enumerate() {
+        if section.is_rela() && section.info as usize == ndx {
+            for rela in obj.relocations(r) {
+                if let Some(sym) = obj.symbols().get(rela.sym) {
+                    code.insert(rela.offset, sym.shndx);
+                }
+            }
+        }
+    }
+
+    let mut kept = vec![];
+    let mut remap: Ranges = vec![];
+    let mut off = 0usize;
+    while off + 4 <= data.len() {
+        let len = util::get_u32(data, off) as usize;
+        let end = (off + 4 + len).min(data.len());
+        if len == 0 || off + 8 > data.len() {
+            kept.extend_from_slice(&data[off..end]);
+            remap.push((off as u64, end as u64, (kept.len() - (end - off)) as u64));
+            off = end;
+            continue;
+        }
+
+        let cie_ptr = util::get_u32(data, off + 4) as usize;
+        let is_fde = cie_ptr != 0;
+        let target = code.get(&(off as u64 + 8));
+        if is_fde && target.is_some_and(|t| dropped.contains(t)) {
+            off = end;
+            continue;
+        }
+
+        let start = kept.len();
+        kept.extend_from_slice(&data[off..end]);
+        if is_fde {
+            /* point again at the CIE, wherever it ended up */
+            let cie = (off + 4).checked_sub(cie_ptr);
+            if let Some(new) = cie.and_then(|cie| remap_offset(&remap, cie as u64)) {
+                let ptr = (start + 4) as u32 - new as u32;
+                kept[start + 4..start + 8].copy_from_slice(&ptr.to_le_bytes());
+            }
+        }
+        remap.push((off as u64, end as u64, start as u64));
+        off = end;
+    }
+    (kept, remap)
+}
+
+/// Translate an offset through a list of kept ranges.
+fn remap_offset(remap: &[(u64, u64, u64)], off: u64) -> Option<u64> {
+    remap.iter()
+        .find(|(start, end, _)| *start <= off && off < *end)
+        .map(|(start, _, new)| new + off - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::EM_X86_64;
+    use crate::link::tests::{common_x, strong_x};
+    use crate::rel::x86_64::{R_X86_64_NONE, R_X86_64_PC32, R_X86_64_PLT32};
+    use crate::sym::Type as SymType;
+
+    /// Get an object whose `caller` calls `f`, an inline function in a
+    /// COMDAT group with the given code.
+    fn inline_f(caller: &str, code: u8) -> ObjectBuilder {
+        let mut b = ObjectBuilder::new(EM_X86_64);
+        let text = b.add_section(".text", section::SHF_ALLOC | section::SHF_EXECINSTR,
+            vec![0xe8, 0, 0, 0, 0, 0xc3], 1);
+        let flags = section::SHF_ALLOC | section::SHF_EXECINSTR | section::SHF_GROUP;
+        let inline = b.add_section(".text.f", flags, vec![code, 0xc3], 1);
+        b.define_symbol(caller, Bind::Global, SymType::Func, text, 0, 6);
+        let f = b.define_symbol("f", Bind::Weak, SymType::Func, inline, 0, 2);
+        b.add_relocation(text, 1, f, R_X86_64_PLT32, -4);
+        b.add_group(f, section::GRP_COMDAT, &[inline]);
+        b
+    }
+
+    #[test]
+    fn comdat_dedup() {
+        let mut a = inline_f("a", 0x90);
+        a.add_relocation(0, 0, NO_SYMBOL, R_X86_64_NONE, 0);
+        let mut linker = Linker::new();
+        linker.add_object(a.build());
+        linker.add_object(inline_f("b", 0xcc).build());
+        let obj = Object::from_bytes(&crate::tests::write(&linker.link_relocatable().unwrap()));
+
+        /* a single copy, the first one */
+        let count = |name| obj.sections().iter().filter(|s| s.name.as_deref() == Some(name)).count();
+        assert_eq!((count(".group"), count(".text.f")), (1, 1));
+        let inline = obj.section_index(".text.f").unwrap();
+        assert_eq!(obj.sections()[inline].data, [0x90, 0xc3]);
+        let fs: Vec<usize> = obj.symbols().iter().enumerate()
+            .filter(|(_, s)| s.name.as_deref() == Some("f"))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(fs.len(), 1);
+        assert_eq!(obj.symbols()[fs[0]].shndx, inline);
+
+        /* both callers call it, the symbol-less relocation is kept */
+        let rela = obj.section_index(".rela.text").unwrap();
+        let relocs: Vec<_> = obj.relocations(rela).iter()
+            .map(|r| (r.offset, r.sym, r.rtype))
+            .collect();
+        assert_eq!(relocs, [(1, fs[0], R_X86_64_PLT32), (0, 0, R_X86_64_NONE),
+            (7, fs[0], R_X86_64_PLT32)]);
+    }
+
+    #[test]
+    fn discarded_and_misplaced() {
+        /* a reference to the dropped copy itself */
+        let mut b = inline_f("b", 0xcc);
+        let inline = b.section_symbol(1);
+        b.add_relocation(0, 1, inline, R_X86_64_PC32, -4);
+        let mut linker = Linker::new();
+        linker.add_object(inline_f("a", 0x90).build());
+        linker.add_object(b.build());
+        assert!(matches!(linker.link_relocatable(), Err(Error::Discarded(_))));
+
+        let mut a = inline_f("a", 0x90);
+        a.add_relocation(0, 6, NO_SYMBOL, R_X86_64_NONE, 0);
+        let mut linker = Linker::new();
+        linker.add_object(a.build());
+        assert!(matches!(linker.link_relocatable(), Err(Error::Misplaced(name)) if name == ".text"));
+    }
+
+    #[test]
+    fn malformed_group() {
+        let mut obj = inline_f("a", 0x90).build();
+        let group = obj.section_index(".group").unwrap();
+        obj.sections_mut()[group].info = 100;
+        let mut linker = Linker::new();
+        linker.add_object(obj);
+        assert!(linker.link_relocatable().is_ok());
+    }
+
+    #[test]
+    fn strong_survives_common() {
+        for common_last in &[true, false] {
+            let mut linker = Linker::new();
+            if *common_last {
+                linker.add_object(strong_x());
+                linker.add_object(common_x());
+            } else {
+                linker.add_object(common_x());
+                linker.add_object(strong_x());
+            }
+            let obj = linker.link_relocatable().unwrap();
+            let x = obj.symbols().iter()
+                .find(|s| s.name.as_deref() == Some("x"))
+                .unwrap();
+            assert_ne!(x.shndx, SHN_COMMON);
+            assert_eq!(x.size, 4);
+            let data = &obj.sections()[x.shndx].data;
+            assert_eq!(util::get_u32(data, x.value as usize), 42);
+        }
+    }
+}
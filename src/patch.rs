@@ -0,0 +1,433 @@
+//! Regarding editing the dynamic linking information of executables and
+//! shared objects, like `patchelf` does.
+//!
+//! The interpreter, `DT_NEEDED`, `DT_SONAME`, `DT_RPATH` and
+//! `DT_RUNPATH` can be changed. New strings are appended to `.dynstr`,
+//! and new entries to `.dynamic`. Sections that shrink are padded in
+//! place, while sections that grow are moved into a new writable
+//! `PT_LOAD` segment at the end of the address space:
+//! - The program headers move first into that segment, since the new
+//!   segment needs one more entry. `PT_PHDR` follows them.
+//! - `PT_INTERP`, `PT_DYNAMIC` and `DT_STRTAB` follow the sections they
+//!   describe.
+//!
+//! Later growth reuses the same segment, so an edited file can be
+//! edited again.
+
+use std::fmt;
+
+use crate::dynamic::*;
+use crate::object::Object;
+use crate::segment::{self, Segment, Type as SegmentType};
+use crate::util;
+
+/// Page size used for the new segment.
+const PAGE: u64 = 0x1000;
+
+/// Errors found while editing.
+#[derive(Debug)]
+pub enum Error {
+    /// The object has no dynamic section.
+    NoDynamic,
+    /// The object has no interpreter.
+    NoInterpreter,
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Get the interpreter path, if any.
+        pub fn interpreter(&self) -> Option<String> {
+            let seg = self.segments.iter().find(|s| matches!(s.etype, SegmentType::Interp))?;
+            let section = self.sections.iter().find(|s| s.offset == seg.offset && !s.is_nobits())?;
+            Some(util::get_str(&section.data, 0))
+        }
+
+        /// Sets the interpreter path.
+        pub fn set_interpreter(&mut self, path: &str) -> Result<(), Error> {
+            let seg = self.segments.iter()
+                .position(|s| matches!(s.etype, SegmentType::Interp))
+                .ok_or(Error::NoInterpreter)?;
+            let ndx = self.sections.iter()
+                .position(|s| s.offset == self.segments[seg].offset && !s.is_nobits())
+                .ok_or(Error::NoInterpreter)?;
+            let mut data = path.as_bytes().to_vec();
+            data.push(0);
+            self.resize_section(ndx, data);
+            self.layout();
+            Ok(())
+        }
+
+        /// Get the names of the needed libraries, in order.
+        pub fn needed(&self) -> Vec<String> {
+            self.dynamic().iter()
+                .filter(|d| d.tag == DT_NEEDED)
+                .filter_map(|d| self.dynamic_str(d))
+                .collect()
+        }
+
+        /// Adds a needed library after the existing ones, unless already
+        /// needed.
+        pub fn add_needed(&mut self, name: &str) -> Result<(), Error> {
+            if self.needed().iter().any(|n| n == name) {
+                return Ok(());
+            }
+            let val = self.add_dynstr(name)?;
+            let mut entries = self.dynamic();
+            let at = entries.iter().rposition(|d| d.tag == DT_NEEDED).map_or(0, |i| i + 1);
+            entries.insert(at, Dyn::new(DT_NEEDED, val));
+            self.set_dynamic(entries);
+            self.layout();
+            Ok(())
+        }
+
+        /// Removes a needed library.
+        pub fn remove_needed(&mut self, name: &str) -> Result<(), Error> {
+            self.dynamic_index().ok_or(Error::NoDynamic)?;
+            let entries = self.dynamic().into_iter()
+                .filter(|d| d.tag != DT_NEEDED || self.dynamic_str(d).as_deref() != Some(name))
+                .collect();
+            self.set_dynamic(entries);
+            self.layout();
+            Ok(())
+        }
+
+        /// Replaces a needed library by another one, keeping its place.
+        pub fn replace_needed(&mut self, old: &str, new: &str) -> Result<(), Error> {
+            let val = self.add_dynstr(new)?;
+            let mut entries = self.dynamic();
+            for entry in &mut entries {
+                if entry.tag == DT_NEEDED && self.dynamic_str(entry).as_deref() == Some(old) {
+                    entry.val = val;
+                }
+            }
+            self.set_dynamic(entries);
+            self.layout();
+            Ok(())
+        }
+
+        /// Get the shared object name, if any.
+        pub fn soname(&self) -> Option<String> {
+            self.string_entry(DT_SONAME)
+        }
+
+        /// Sets the shared object name.
+        pub fn set_soname(&mut self, name: &str) -> Result<(), Error> {
+            self.set_string_entry(DT_SONAME, Some(name))
+        }
+
+        /// Get the `DT_RPATH` search path, if any.
+        pub fn rpath(&self) -> Option<String> {
+            self.string_entry(DT_RPATH)
+        }
+
+        /// Sets or removes the `DT_RPATH` search path.
+        pub fn set_rpath(&mut self, path: Option<&str>) -> Result<(), Error> {
+            self.set_string_entry(DT_RPATH, path)
+        }
+
+        /// Get the `DT_RUNPATH` search path, if any.
+        pub fn runpath(&self) -> Option<String> {
+            self.string_entry(DT_RUNPATH)
+        }
+
+        /// Sets or removes the `DT_RUNPATH` search path.
+        pub fn set_runpath(&mut self, path: Option<&str>) -> Result<(), Error> {
+            self.set_string_entry(DT_RUNPATH, path)
+        }
+    }
+}
+
+/// Editing methods.
+mod editing {
+    use super::*;
+
+    impl Object {
+        /// Get the string of the first dynamic entry with the given tag.
+        pub(crate) fn string_entry(&self, tag: i64) -> Option<String> {
+            self.dynamic().iter()
+                .find(|d| d.tag == tag)
+                .and_then(|d| self.dynamic_str(d))
+        }
+
+        /// Sets the string of the dynamic entry with the given tag,
+        /// adding the entry if needed, or removes it.
+        pub(crate) fn set_string_entry(&mut self, tag: i64, value: Option<&str>) -> Result<(), Error> {
+            self.dynamic_index().ok_or(Error::NoDynamic)?;
+            let mut entries = self.dynamic();
+            match value {
+                None => entries.retain(|d| d.tag != tag),
+                Some(value) => {
+                    let val = self.add_dynstr(value)?;
+                    entries = self.dynamic();
+                    match entries.iter_mut().find(|d| d.tag == tag) {
+                        Some(entry) => entry.val = val,
+                        None        => entries.push(Dyn::new(tag, val)),
+                    }
+                }
+            }
+            self.set_dynamic(entries);
+            self.layout();
+            Ok(())
+        }
+
+        /// Adds a string to the dynamic string table, unless already
+        /// there, and gets its offset.
+        pub(crate) fn add_dynstr(&mut self, s: &str) -> Result<u64, Error> {
+            let dynamic = self.dynamic_index().ok_or(Error::NoDynamic)?;
+            let ndx = self.sections[dynamic].link;
+
+            /* reuse a whole string already there */
+            let data = &self.sections[ndx].data;
+            let mut needle = s.as_bytes().to_vec();
+            needle.push(0);
+            let found = data.windows(needle.len())
+                .enumerate()
+                .find(|(i, w)| *w == &needle[..] && (*i == 0 || data[i - 1] == 0));
+            if let Some((off, _)) = found {
+                return Ok(off as u64);
+            }
+
+            /* strings after the last used one may be padding */
+            let used = self.dynamic_value(DT_STRSZ).unwrap_or(data.len() as u64) as usize;
+            let off = used.min(data.len());
+            let mut data = data[..off].to_vec();
+            data.extend_from_slice(&needle);
+            let old_addr = self.sections[ndx].addr;
+            self.resize_section(ndx, data);
+
+            /* the table may have moved */
+            let addr = self.sections[ndx].addr;
+            let size = (off + needle.len()) as u64;
+            let mut entries = self.dynamic();
+            for entry in &mut entries {
+                match entry.tag {
+                    DT_STRTAB if entry.val == old_addr => entry.val = addr,
+                    DT_STRSZ => entry.val = size,
+                    _ => (),
+                }
+            }
+            self.set_dynamic(entries);
+            Ok(off as u64)
+        }
+
+        /// Replaces the dynamic entries, keeping spare `DT_NULL` ones.
+        pub(crate) fn set_dynamic(&mut self, entries: Vec<Dyn>) {
+            let ndx = self.dynamic_index().unwrap();
+            let mut data = vec![];
+            for entry in entries.iter().chain(Some(&Dyn::new(DT_NULL, 0))) {
+                entry.write(&mut data).unwrap();
+            }
+            self.resize_section(ndx, data);
+        }
+
+        /// Replaces the contents of an allocated section.
+        ///
+        /// Smaller contents are zero padded in place, bigger ones are
+        /// moved into the extension segment with some spare room, unless
+        /// they are already at its end. Segments describing exactly that section follow it.
+        pub(crate) fn resize_section(&mut self, ndx: usize, mut data: Vec<u8>) {
+            let old = self.sections[ndx].clone();
+            if data.len() as u64 <= old.size {
+                data.resize(old.size as usize, 0);
+                self.sections[ndx].data = data;
+                return;
+            }
+
+            let ext = self.extension_segment();
+            let seg = &mut self.segments[ext];
+            let at_end = old.offset + old.size == seg.offset + seg.filesz
+                && old.offset >= seg.offset;
+            let (offset, addr) = if at_end {
+                (old.offset, old.addr)
+            } else {
+                /* leave room for further growth */
+                let len = util::align(data.len() as u64 * 3 / 2, 16);
+                data.resize(len as usize, 0);
+                let align = old.addralign.max(1) as u64;
+                let offset = util::align(seg.offset + seg.filesz, align);
+                (offset, seg.vaddr + offset - seg.offset)
+            };
+            seg.filesz = offset + data.len() as u64 - seg.offset;
+            seg.memsz = seg.filesz;
+
+            let size = data.len() as u64;
+            let section = &mut self.sections[ndx];
+            section.offset = offset;
+            section.addr = addr;
+            section.size = size;
+            section.data = data;
+
+            for seg in &mut self.segments {
+                let describes = matches!(seg.etype, SegmentType::Interp | SegmentType::Dynamic);
+                if describes && seg.offset == old.offset {
+                    seg.offset = offset;
+                    seg.vaddr = addr;
+                    seg.paddr = addr;
+                    seg.filesz = size;
+                    seg.memsz = size;
+                }
+            }
+        }
+
+        /// Get the index of the segment holding moved sections, creating
+        /// it if needed.
+        ///
+        /// The segment is recognized by holding the program headers,
+        /// past the first page of the file.
+        pub(crate) fn extension_segment(&mut self) -> usize {
+            let phoff = self.header.phoff;
+            let found = self.segments.iter().position(|s| {
+                s.is_load() && s.offset >= PAGE && s.offset <= phoff
+                    && phoff < s.offset + s.filesz
+            });
+            if let Some(i) = found {
+                return i;
+            }
+
+            /* past everything loaded, both in the file and in memory */
+            let mut file_end = 0;
+            let mut mem_end = 0;
+            for seg in self.segments.iter().filter(|s| s.is_load()) {
+                file_end = file_end.max(seg.offset + seg.filesz);
+                mem_end = mem_end.max(seg.vaddr + seg.memsz);
+            }
+            for section in self.sections.iter().filter(|s| s.is_alloc() && !s.is_nobits()) {
+                file_end = file_end.max(section.offset + section.size);
+            }
+            let offset = util::align(file_end, PAGE);
+            let vaddr = util::align(mem_end, PAGE);
+
+            let mut seg = Segment::new(SegmentType::Load, segment::PF_R | segment::PF_W);
+            seg.offset = offset;
+            seg.vaddr = vaddr;
+            seg.paddr = vaddr;
+            seg.align = PAGE;
+            let at = self.segments.iter().rposition(|s| s.is_load()).map_or(0, |i| i + 1);
+            self.segments.insert(at, seg);
+
+            /* the program headers move at its start */
+            let phsize = self.header.phentsize as u64 * self.segments.len() as u64;
+            self.header.phoff = offset;
+            self.segments[at].filesz = phsize;
+            self.segments[at].memsz = phsize;
+            for seg in &mut self.segments {
+                if matches!(seg.etype, SegmentType::Phdr) {
+                    seg.offset = offset;
+                    seg.vaddr = vaddr;
+                    seg.paddr = vaddr;
+                    seg.filesz = phsize;
+                    seg.memsz = phsize;
+                }
+            }
+            at
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Error {
+        /// Describe an editing error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::NoDynamic        => write!(f, "no dynamic section"),
+                Error::NoInterpreter    => write!(f, "no interpreter"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample, write};
+
+    /// Edit the sample executable, and read it back.
+    fn patched<F: FnOnce(&mut Object)>(edit: F) -> Object {
+        let mut obj = Object::from_bytes(&sample("main"));
+        edit(&mut obj);
+        Object::from_bytes(&write(&obj))
+    }
+
+    /// Check a section is loaded from the right file bytes by the
+    /// extension segment.
+    fn check_moved(obj: &Object, name: &str) {
+        let section = &obj.sections()[obj.section_index(name).unwrap()];
+        let ext = obj.segments().iter().rfind(|s| s.is_load()).unwrap();
+        assert!(ext.offset >= PAGE && ext.offset <= obj.header().phoff);
+        assert!(ext.offset <= section.offset);
+        assert!(section.offset + section.size <= ext.offset + ext.filesz);
+        assert_eq!(section.addr - ext.vaddr, section.offset - ext.offset);
+        assert!(obj.load_plan().is_ok());
+    }
+
+    /// Run an executable and get its exit code.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn run(obj: &Object, name: &str) -> Option<i32> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("elf-patch-{}-{}", name, std::process::id()));
+        obj.to_file(path.to_str().unwrap()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        status.code()
+    }
+
+    #[test]
+    fn longer_interpreter() {
+        let path = "/lib64/../lib64/./././././ld-linux-x86-64.so.2";
+        let obj = patched(|obj| obj.set_interpreter(path).unwrap());
+        assert_eq!(obj.interpreter().as_deref(), Some(path));
+        check_moved(&obj, ".interp");
+        let interp = obj.segments().iter().find(|s| matches!(s.etype, SegmentType::Interp)).unwrap();
+        let section = &obj.sections()[obj.section_index(".interp").unwrap()];
+        assert_eq!((interp.offset, interp.vaddr), (section.offset, section.addr));
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(run(&obj, "interp"), Some(105));
+    }
+
+    #[test]
+    fn add_needed() {
+        let obj = patched(|obj| obj.add_needed("libm.so.6").unwrap());
+        assert_eq!(obj.needed(), ["libc.so.6", "libm.so.6"]);
+        check_moved(&obj, ".dynstr");
+        let dynstr = &obj.sections()[obj.section_index(".dynstr").unwrap()];
+        assert_eq!(obj.dynamic_value(DT_STRTAB), Some(dynstr.addr));
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(run(&obj, "needed"), Some(105));
+    }
+
+    #[test]
+    fn set_rpath() {
+        let obj = patched(|obj| obj.set_rpath(Some("$ORIGIN/lib:/opt/sample/lib")).unwrap());
+        assert_eq!(obj.rpath().as_deref(), Some("$ORIGIN/lib:/opt/sample/lib"));
+        assert_eq!(obj.needed(), ["libc.so.6"]);
+        check_moved(&obj, ".dynstr");
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        assert_eq!(run(&obj, "rpath"), Some(105));
+
+        /* editing again reuses the extension segment */
+        let mut obj = obj;
+        let loads = obj.segments().iter().filter(|s| s.is_load()).count();
+        obj.set_rpath(None).unwrap();
+        obj.set_runpath(Some("/a/much/longer/run/path/than/before/to/force/growth")).unwrap();
+        let obj = Object::from_bytes(&write(&obj));
+        assert_eq!(obj.segments().iter().filter(|s| s.is_load()).count(), loads);
+        assert_eq!(obj.rpath(), None);
+        assert!(obj.runpath().is_some());
+        check_moved(&obj, ".dynstr");
+    }
+
+    #[test]
+    fn no_dynamic() {
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        assert!(obj.dynamic_str(&Dyn::new(DT_NEEDED, 0)).is_none());
+        assert!(matches!(obj.add_needed("libm.so.6"), Err(Error::NoDynamic)));
+    }
+}
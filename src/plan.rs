@@ -0,0 +1,203 @@
+//! Regarding the plan for loading an executable into memory.
+//!
+//! The plan is the list of `PT_LOAD` segments in address order, with
+//! what each one needs: where it goes, which bytes of the file to copy,
+//! how much to zero after them, and with which permissions. It is
+//! validated once, so loaders only have to follow it.
+
+use std::fmt;
+
+use crate::segment::{self, Segment};
+
+/// A region of memory to load.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    /// Virtual address.
+    pub vaddr:      u64,
+    /// Physical address.
+    pub paddr:      u64,
+    /// Offset of the contents in the file.
+    pub offset:     u64,
+    /// Size of the contents in the file.
+    pub filesz:     u64,
+    /// Amount of zero bytes following the contents, like `.bss`.
+    pub zerosz:     u64,
+    /// Permission flags (`PF_R`, `PF_W`, `PF_X`).
+    pub flags:      u32,
+    /// Required alignment.
+    pub align:      u64,
+}
+
+/// Errors found while validating the segments.
+#[derive(Debug)]
+pub enum Error {
+    /// The segments with the given indices overlap in memory.
+    Overlap(usize, usize),
+    /// The segments with the given indices overlap in physical memory.
+    PhysicalOverlap(usize, usize),
+    /// The segment with the given index has an offset not congruent to
+    /// its address, or an alignment not a power of two.
+    Misaligned(usize),
+    /// The segment with the given index is bigger in the file than in
+    /// memory.
+    FileSize(usize),
+    /// The segment with the given index lies past the end of the file.
+    Truncated(usize),
+}
+
+/// Simple region methods.
+impl Region {
+    /// Creates a region from a loadable segment.
+    pub fn new(seg: &Segment) -> Self {
+        Self {
+            vaddr:  seg.vaddr,
+            paddr:  seg.paddr,
+            offset: seg.offset,
+            filesz: seg.filesz,
+            zerosz: seg.memsz.saturating_sub(seg.filesz),
+            flags:  seg.flags(),
+            align:  seg.align,
+        }
+    }
+
+    /// Get the size in memory.
+    pub fn memsz(&self) -> u64 {
+        self.filesz + self.zerosz
+    }
+
+    /// Check if the region is readable.
+    pub fn is_readable(&self) -> bool {
+        self.flags & segment::PF_R != 0
+    }
+
+    /// Check if the region is writable.
+    pub fn is_writable(&self) -> bool {
+        self.flags & segment::PF_W != 0
+    }
+
+    /// Check if the region is executable.
+    pub fn is_executable(&self) -> bool {
+        self.flags & segment::PF_X != 0
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Region {
+        /// Print a region as a line of a loading table.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let perm = |set: bool, c: char| if set { c } else { '-' };
+            write!(f, "{:#018x} {:#018x} {:#010x} {:#010x} {:#010x} {}{}{} {:#x}",
+                self.vaddr, self.paddr, self.offset, self.filesz, self.zerosz,
+                perm(self.is_readable(), 'r'),
+                perm(self.is_writable(), 'w'),
+                perm(self.is_executable(), 'x'),
+                self.align)
+        }
+    }
+
+    impl fmt::Display for Error {
+        /// Describe a validation error.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::Overlap(a, b)    => write!(f, "segments {} and {} overlap", a, b),
+                Error::PhysicalOverlap(a, b) => {
+                    write!(f, "segments {} and {} overlap in physical memory", a, b)
+                }
+                Error::Misaligned(i)    => write!(f, "segment {} is misaligned", i),
+                Error::FileSize(i)      => write!(f, "segment {} is bigger in file than in memory", i),
+                Error::Truncated(i)     => write!(f, "segment {} lies past the end of file", i),
+            }
+        }
+    }
+}
+
+/// Get the indices of the first two regions overlapping, given regions
+/// sorted by the address `addr` gives.
+fn overlap<F>(loads: &[(usize, Region)], addr: F) -> Option<(usize, usize)>
+    where F: Fn(&Region) -> u64
+{
+    loads.windows(2)
+        .find(|pair| addr(&pair[0].1).saturating_add(pair[0].1.memsz()) > addr(&pair[1].1))
+        .map(|pair| (pair[0].0, pair[1].0))
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use crate::object::Object;
+
+    impl Object {
+        /// Get the regions to load, ordered by virtual address.
+        ///
+        /// Every `PT_LOAD` segment is checked to have a power of two
+        /// alignment with its offset congruent to its address, to be no
+        /// bigger in the file than in memory nor past its end, and to not
+        /// overlap any other one, at either its virtual or physical
+        /// address.
+        pub fn load_plan(&self) -> Result<Vec<Region>, Error> {
+            let size = self.file_size();
+            let mut loads = vec![];
+            for (i, seg) in self.segments.iter().enumerate() {
+                if !seg.is_load() {
+                    continue;
+                }
+                if seg.filesz > seg.memsz {
+                    return Err(Error::FileSize(i));
+                }
+                if seg.offset.checked_add(seg.filesz).is_none_or(|end| end > size) {
+                    return Err(Error::Truncated(i));
+                }
+                if seg.align > 1 && (!seg.align.is_power_of_two()
+                    || seg.offset % seg.align != seg.vaddr % seg.align) {
+                    return Err(Error::Misaligned(i));
+                }
+                loads.push((i, Region::new(seg)));
+            }
+
+            loads.sort_by_key(|(_, r)| r.vaddr);
+            if let Some((i, j)) = overlap(&loads, |r| r.vaddr) {
+                return Err(Error::Overlap(i, j));
+            }
+            let mut physical = loads.clone();
+            physical.sort_by_key(|(_, r)| r.paddr);
+            if let Some((i, j)) = overlap(&physical, |r| r.paddr) {
+                return Err(Error::PhysicalOverlap(i, j));
+            }
+            Ok(loads.into_iter().map(|(_, r)| r).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::Object;
+    use crate::tests::sample;
+    use super::*;
+
+    #[test]
+    fn physical_overlap() {
+        let mut obj = Object::from_bytes(&sample("main"));
+        assert!(obj.load_plan().is_ok());
+        let loads: Vec<usize> = (0..obj.segments.len())
+            .filter(|&i| obj.segments[i].is_load())
+            .collect();
+        let paddr = obj.segments[loads[0]].paddr;
+        obj.segments[loads[1]].paddr = paddr;
+        assert!(matches!(obj.load_plan(), Err(Error::PhysicalOverlap(_, _))));
+    }
+
+    #[test]
+    fn past_end_of_file() {
+        let mut obj = Object::from_bytes(&sample("main"));
+        let size = sample("main").len() as u64;
+        assert_eq!(obj.file_size(), size);
+        let load = obj.segments.iter().rposition(|s| s.is_load()).unwrap();
+        let seg = &mut obj.segments[load];
+        seg.filesz = size - seg.offset + 1;
+        seg.memsz = seg.memsz.max(seg.filesz);
+        assert!(matches!(obj.load_plan(), Err(Error::Truncated(i)) if i == load));
+    }
+}
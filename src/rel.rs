@@ -0,0 +1,115 @@
+//! Regarding relocation entries.
+//!
+//! Only entries with explicit addends (`SHT_RELA`) are handled, which is
+//! what x86-64 uses.
+
+/// Relocation types for x86-64.
+pub mod x86_64 {
+    pub const R_X86_64_NONE: u32 = 0;
+    pub const R_X86_64_64: u32 = 1;
+    pub const R_X86_64_PC32: u32 = 2;
+    pub const R_X86_64_GOT32: u32 = 3;
+    pub const R_X86_64_PLT32: u32 = 4;
+    pub const R_X86_64_COPY: u32 = 5;
+    pub const R_X86_64_GLOB_DAT: u32 = 6;
+    pub const R_X86_64_JUMP_SLOT: u32 = 7;
+    pub const R_X86_64_RELATIVE: u32 = 8;
+    pub const R_X86_64_GOTPCREL: u32 = 9;
+    pub const R_X86_64_32: u32 = 10;
+    pub const R_X86_64_32S: u32 = 11;
+    pub const R_X86_64_16: u32 = 12;
+    pub const R_X86_64_PC16: u32 = 13;
+    pub const R_X86_64_8: u32 = 14;
+    pub const R_X86_64_PC8: u32 = 15;
+    pub const R_X86_64_PC64: u32 = 24;
+    pub const R_X86_64_IRELATIVE: u32 = 37;
+    pub const R_X86_64_GOTPCRELX: u32 = 41;
+    pub const R_X86_64_REX_GOTPCRELX: u32 = 42;
+}
+
+/// Size in bytes of a relocation entry.
+pub const RELA_SIZE: u64 = 24;
+
+/// Represents a relocation entry with addend.
+#[derive(Clone)]
+pub struct Rela {
+    /// Offset into the target section (or address, for dynamic ones).
+    pub offset:     u64,        // 64-bits
+    /// Index of the symbol in the linked symbol table.
+    pub sym:        usize,      // \_ 64-bits
+    /// Relocation type.
+    pub rtype:      u32,        // /
+    /// Constant addend.
+    pub addend:     i64,        // 64-bits
+}
+
+/// Simple rela methods.
+impl Rela {
+    /// Creates a relocation entry.
+    pub fn new(offset: u64, sym: usize, rtype: u32, addend: i64) -> Self {
+        Self { offset, sym, rtype, addend }
+    }
+}
+
+/// Buffer IO methods.
+pub mod io {
+    use super::*;
+    use std::io::{self, Write};
+    use crate::util;
+
+    impl Rela {
+        /// Extract all relocation entries from the contents of a rela
+        /// section.
+        pub fn extract_all(data: &[u8]) -> Vec<Self> {
+            data.chunks_exact(RELA_SIZE as usize)
+                .map(|e| {
+                    let info = util::get_u64(e, 8);
+                    Self {
+                        offset: util::get_u64(e, 0),
+                        sym:    (info >> 32) as usize,
+                        rtype:  info as u32,
+                        addend: util::get_u64(e, 16) as i64,
+                    }
+                })
+                .collect()
+        }
+
+        /// Write a relocation entry into a file **at current offset**.
+        pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            let info = (self.sym as u64) << 32 | self.rtype as u64;
+            util::write_u64(file, self.offset)?;
+            util::write_u64(file, info)?;
+            util::write_u64(file, self.addend as u64)
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use crate::object::Object;
+
+    impl Object {
+        /// Get the relocation entries of the rela section with the given
+        /// index.
+        pub fn relocations(&self, ndx: usize) -> Vec<Rela> {
+            let section = &self.sections[ndx];
+            if !section.is_rela() {
+                return vec![];
+            }
+            Rela::extract_all(&section.data)
+        }
+
+        /// Replace the relocation entries of the rela section with the
+        /// given index.
+        ///
+        /// Call `layout` afterwards to update the file layout.
+        pub fn set_relocations(&mut self, ndx: usize, relocs: &[Rela]) {
+            let mut data = vec![];
+            for rela in relocs {
+                rela.write(&mut data).unwrap();
+            }
+            self.sections[ndx].data = data;
+        }
+    }
+}
@@ -4,8 +4,8 @@
 //! - better documentation.**
 
 /// Posible section types.
-#[derive(PartialEq)]
-enum Type {
+#[derive(PartialEq, Clone, Copy)]
+pub enum Type {
     Null,
     Progbits,
     Symtab,
@@ -18,50 +18,66 @@ enum Type {
     Rel,
     Shlib,
     Dynsym,
-    Unhandled,
+    Group,
+    GnuHash,
+    GnuVerdef,
+    GnuVerneed,
+    GnuVersym,
+    Unhandled(u32),
 }
 
 /// Represents a whole section in an ELF file.
+#[derive(Clone)]
 pub struct Section {
     /// Index into shstrtab for this section's name.
     pub nameoff:    usize,      // 32-bits
     /// Indicates the type of this section.
-    etype:          Type,       // 32-bits
-    flags:          u64,        // 64-bits
-    addr:           u64,        // 64-bits      
+    pub etype:      Type,       // 32-bits
+    pub flags:      u64,        // 64-bits
+    pub addr:       u64,        // 64-bits
     pub offset:     u64,        // 64-bits
     pub size:       u64,        // 64-bits
-    link:           usize,      // 32-bits
-    info:           u32,        // 32-bits
-    addralign:      usize,      // 64-bits
+    pub link:       usize,      // 32-bits
+    pub info:       u32,        // 32-bits
+    pub addralign:  usize,      // 64-bits
     pub entsize:    u64,        // 64-bits
 
     /// Extracted name string.
     pub name:       Option<String>,
+    /// Extracted contents (empty for `nobits` sections).
+    ///
+    /// Compressed sections keep their compressed bytes here, header
+    /// included; `Object::section_data` decompresses them.
+    pub data:       Vec<u8>,
 }
 
 impl Type {
     /// Default type.
     pub fn empty() -> Self {
-        Self::Unhandled
+        Self::Unhandled(0)
     }
 
     /// String slice representation for type.
     fn as_str(&self) -> &'static str {
         match self {
-            Self::Null     => "null",
-            Self::Progbits => "progbits",
-            Self::Symtab   => "symtab",
-            Self::Strtab   => "strtab",
-            Self::Rela     => "rela",
-            Self::Hash     => "hash",
-            Self::Dynamic  => "dynamic",
-            Self::Note     => "note",
-            Self::Nobits   => "nobits",
-            Self::Rel      => "rel",
-            Self::Shlib    => "shlib",
-            Self::Dynsym   => "dynsym",
-            _              => "unhandled",
+            Self::Null       => "null",
+            Self::Progbits   => "progbits",
+            Self::Symtab     => "symtab",
+            Self::Strtab     => "strtab",
+            Self::Rela       => "rela",
+            Self::Hash       => "hash",
+            Self::Dynamic    => "dynamic",
+            Self::Note       => "note",
+            Self::Nobits     => "nobits",
+            Self::Rel        => "rel",
+            Self::Shlib      => "shlib",
+            Self::Dynsym     => "dynsym",
+            Self::Group      => "group",
+            Self::GnuHash    => "gnu hash",
+            Self::GnuVerdef  => "verdef",
+            Self::GnuVerneed => "verneed",
+            Self::GnuVersym  => "versym",
+            _                => "unhandled",
         }
     }
 
@@ -69,23 +85,70 @@ impl Type {
     /// **TODO: missing type parsing.**
     pub fn new(etype: u32) -> Self {
         match etype {
-            0  => Self::Null,
-            1  => Self::Progbits,
-            2  => Self::Symtab,
-            3  => Self::Strtab,
-            4  => Self::Rela,
-            5  => Self::Hash,
-            6  => Self::Dynamic,
-            7  => Self::Note,
-            8  => Self::Nobits,
-            9  => Self::Rel,
-            10 => Self::Shlib,
-            11 => Self::Dynsym,
-            _  => Self::Unhandled,
+            0          => Self::Null,
+            1          => Self::Progbits,
+            2          => Self::Symtab,
+            3          => Self::Strtab,
+            4          => Self::Rela,
+            5          => Self::Hash,
+            6          => Self::Dynamic,
+            7          => Self::Note,
+            8          => Self::Nobits,
+            9          => Self::Rel,
+            10         => Self::Shlib,
+            11         => Self::Dynsym,
+            17         => Self::Group,
+            0x6ffffff6 => Self::GnuHash,
+            0x6ffffffd => Self::GnuVerdef,
+            0x6ffffffe => Self::GnuVerneed,
+            0x6fffffff => Self::GnuVersym,
+            _          => Self::Unhandled(etype),
+        }
+    }
+
+    /// Get real value of the type.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Null         => 0,
+            Self::Progbits     => 1,
+            Self::Symtab       => 2,
+            Self::Strtab       => 3,
+            Self::Rela         => 4,
+            Self::Hash         => 5,
+            Self::Dynamic      => 6,
+            Self::Note         => 7,
+            Self::Nobits       => 8,
+            Self::Rel          => 9,
+            Self::Shlib        => 10,
+            Self::Dynsym       => 11,
+            Self::Group        => 17,
+            Self::GnuHash      => 0x6ffffff6,
+            Self::GnuVerdef    => 0x6ffffffd,
+            Self::GnuVerneed   => 0x6ffffffe,
+            Self::GnuVersym    => 0x6fffffff,
+            Self::Unhandled(v) => *v,
         }
     }
 }
 
+/// Section flag: writable during execution.
+pub const SHF_WRITE: u64 = 0x1;
+/// Section flag: occupies memory during execution.
+pub const SHF_ALLOC: u64 = 0x2;
+/// Section flag: contains executable instructions.
+pub const SHF_EXECINSTR: u64 = 0x4;
+/// Section flag: `info` holds a section index.
+pub const SHF_INFO_LINK: u64 = 0x40;
+/// Section flag: member of a section group.
+pub const SHF_GROUP: u64 = 0x200;
+/// Section flag: holds thread local storage.
+pub const SHF_TLS: u64 = 0x400;
+/// Section flag: contents are compressed, after a compression header.
+pub const SHF_COMPRESSED: u64 = 0x800;
+
+/// Group flag: COMDAT group, only one copy is kept when linking.
+pub const GRP_COMDAT: u32 = 0x1;
+
 /// Simple section methods.
 impl Section {
     /// Default section.
@@ -103,6 +166,17 @@ impl Section {
             entsize:    0,
 
             name:       None,
+            data:       vec![],
+        }
+    }
+
+    /// Creates a section with the given name, type and flags.
+    pub fn new(name: &str, etype: Type, flags: u64) -> Self {
+        Self {
+            etype,
+            flags,
+            name: Some(name.to_string()),
+            ..Self::empty()
         }
     }
 
@@ -120,6 +194,79 @@ impl Section {
     pub fn is_strtab(&self) -> bool {
         self.etype == Type::Strtab
     }
+
+    /// Check if the section holds relocations with addends.
+    pub fn is_rela(&self) -> bool {
+        self.etype == Type::Rela
+    }
+
+    /// Check if the section is a section group.
+    pub fn is_group(&self) -> bool {
+        self.etype == Type::Group
+    }
+
+    /// Check if the section is a dynamic symbol table.
+    pub fn is_dynsym(&self) -> bool {
+        self.etype == Type::Dynsym
+    }
+
+    /// Check if the section holds dynamic linking entries.
+    pub fn is_dynamic(&self) -> bool {
+        self.etype == Type::Dynamic
+    }
+
+    /// Check if the section holds notes.
+    pub fn is_note(&self) -> bool {
+        self.etype == Type::Note
+    }
+
+    /// Check if the section is a System V symbol hash table.
+    pub fn is_hash(&self) -> bool {
+        self.etype == Type::Hash
+    }
+
+    /// Check if the section is a GNU symbol hash table.
+    pub fn is_gnu_hash(&self) -> bool {
+        self.etype == Type::GnuHash
+    }
+
+    /// Check if the section holds symbol version definitions.
+    pub fn is_verdef(&self) -> bool {
+        self.etype == Type::GnuVerdef
+    }
+
+    /// Check if the section holds symbol version requirements.
+    pub fn is_verneed(&self) -> bool {
+        self.etype == Type::GnuVerneed
+    }
+
+    /// Check if the section holds the symbol version table.
+    pub fn is_versym(&self) -> bool {
+        self.etype == Type::GnuVersym
+    }
+
+    /// Check if the section occupies memory during execution.
+    pub fn is_alloc(&self) -> bool {
+        self.flags & SHF_ALLOC != 0
+    }
+
+    /// Check if the contents are compressed, after a compression
+    /// header.
+    pub fn is_compressed(&self) -> bool {
+        self.flags & SHF_COMPRESSED != 0
+    }
+
+    /// Check if the section occupies no space in the file.
+    pub fn is_nobits(&self) -> bool {
+        self.etype == Type::Nobits
+    }
+
+    /// Check if the section holds debugging information, by its name.
+    pub fn is_debug(&self) -> bool {
+        let name = self.name.as_deref().unwrap_or("");
+        [".debug", ".zdebug", ".stab", ".line", ".gdb_index"].iter()
+            .any(|p| name.starts_with(p))
+    }
 }
 
 /// Format methods.
@@ -139,28 +286,42 @@ mod format {
 
 /// File IO methods.
 mod io {
-    use std::fs::File;
+    use std::io::{self, Read, Write};
     use super::super::util;
     use super::*;
 
     impl Section {
         /// Extract section from file **at current offset**
-        pub fn extract(file: &mut File) -> Self {
+        pub fn extract<R: Read>(file: &mut R) -> Self {
             let mut new = Self::empty();
 
             new.nameoff   = util::read_u32(file) as usize;
             new.etype     = Type::new(util::read_u32(file));
-            new.flags     = util::read_u64(file) as u64;
-            new.addr      = util::read_u64(file) as u64;
-            new.offset    = util::read_u64(file) as u64;
-            new.size      = util::read_u64(file) as u64;
+            new.flags     = util::read_u64(file);
+            new.addr      = util::read_u64(file);
+            new.offset    = util::read_u64(file);
+            new.size      = util::read_u64(file);
             new.link      = util::read_u32(file) as usize;
-            new.info      = util::read_u32(file) as u32;
+            new.info      = util::read_u32(file);
             new.addralign = util::read_u64(file) as usize;
-            new.entsize   = util::read_u64(file) as u64;
+            new.entsize   = util::read_u64(file);
 
             new
         }
+
+        /// Write section header into file **at current offset**
+        pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            util::write_u32(file, self.nameoff as u32)?;
+            util::write_u32(file, self.etype.value())?;
+            util::write_u64(file, self.flags)?;
+            util::write_u64(file, self.addr)?;
+            util::write_u64(file, self.offset)?;
+            util::write_u64(file, self.size)?;
+            util::write_u32(file, self.link as u32)?;
+            util::write_u32(file, self.info)?;
+            util::write_u64(file, self.addralign as u64)?;
+            util::write_u64(file, self.entsize)
+        }
     }
 }
 
@@ -1,7 +1,8 @@
 //! Regarding program headers (segments).
 
 /// The posible types for a segment.
-enum Type {
+#[derive(PartialEq, Clone, Copy)]
+pub enum Type {
     Null,
     Load,
     Dynamic,
@@ -10,22 +11,35 @@ enum Type {
     Shlib,
     Phdr,
     Tls,
-    Unhandled,
+    GnuEhFrame,
+    GnuStack,
+    GnuRelro,
+    Unhandled(u32),
 }
 
+/// Segment flag: executable.
+pub const PF_X: u32 = 0x1;
+/// Segment flag: writable.
+pub const PF_W: u32 = 0x2;
+/// Segment flag: readable.
+pub const PF_R: u32 = 0x4;
+
 /// Contains the data from the segment flags.
+#[derive(Clone)]
 struct Flags {
+    value: u32,
 }
 
 /// Represents a whole segment entry.
+#[derive(Clone)]
 pub struct Segment {
-    etype:      Type,       // 32-bits
+    pub etype:  Type,       // 32-bits
     flags:      Flags,      // 32-bits
-    offset:     u64,        // 64-bits
+    pub offset: u64,        // 64-bits
     pub vaddr:  u64,        // 64-bits
     pub paddr:  u64,        // 64-bits
-    filesz:     u64,        // 64-bits
-    memsz:      u64,        // 64-bits
+    pub filesz: u64,        // 64-bits
+    pub memsz:  u64,        // 64-bits
     pub align:  u64,        // 64-bits
 }
 
@@ -33,12 +47,43 @@ pub struct Segment {
 impl Type {
     /// Default type.
     pub fn empty() -> Self {
-        Self::Unhandled
+        Self::Unhandled(0)
     }
 
     /// Type from real value.
-    pub fn new(_etype: u32) -> Self {
-        Self::Unhandled
+    pub fn new(etype: u32) -> Self {
+        match etype {
+            0          => Self::Null,
+            1          => Self::Load,
+            2          => Self::Dynamic,
+            3          => Self::Interp,
+            4          => Self::Note,
+            5          => Self::Shlib,
+            6          => Self::Phdr,
+            7          => Self::Tls,
+            0x6474e550 => Self::GnuEhFrame,
+            0x6474e551 => Self::GnuStack,
+            0x6474e552 => Self::GnuRelro,
+            _          => Self::Unhandled(etype),
+        }
+    }
+
+    /// Get real value of the type.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Null         => 0,
+            Self::Load         => 1,
+            Self::Dynamic      => 2,
+            Self::Interp       => 3,
+            Self::Note         => 4,
+            Self::Shlib        => 5,
+            Self::Phdr         => 6,
+            Self::Tls          => 7,
+            Self::GnuEhFrame   => 0x6474e550,
+            Self::GnuStack     => 0x6474e551,
+            Self::GnuRelro     => 0x6474e552,
+            Self::Unhandled(v) => *v,
+        }
     }
 }
 
@@ -47,12 +92,14 @@ impl Flags {
     /// Default flags
     pub fn empty() -> Self {
         Self {
+            value: 0,
         }
     }
 
     /// Flags from real value.
-    pub fn new(_flags: u32) -> Self {
+    pub fn new(flags: u32) -> Self {
         Self {
+            value: flags,
         }
     }
 }
@@ -73,6 +120,25 @@ impl Segment {
         }
     }
 
+    /// Creates a segment of the given type and flags.
+    pub fn new(etype: Type, flags: u32) -> Self {
+        Self {
+            etype,
+            flags: Flags::new(flags),
+            ..Self::empty()
+        }
+    }
+
+    /// Get the segment flags (`PF_R`, `PF_W`, `PF_X`).
+    pub fn flags(&self) -> u32 {
+        self.flags.value
+    }
+
+    /// Check if the segment is loadable.
+    pub fn is_load(&self) -> bool {
+        self.etype == Type::Load
+    }
+
     /// Get string representation of segment type.
     pub fn type_str(&self) -> &'static str {
         self.etype.as_str()
@@ -87,15 +153,18 @@ mod format {
         /// Get string slice representation of type.
         pub fn as_str(&self) -> &'static str {
             match self {
-                Self::Null      => "null",
-                Self::Load      => "loadable segment",
-                Self::Dynamic   => "dynamic linking info",
-                Self::Interp    => "interpreter",
-                Self::Note      => "aux info",
-                Self::Shlib     => "reserved",
-                Self::Phdr      => "header entry",
-                Self::Tls       => "tls",
-                Self::Unhandled => "unhandled",
+                Self::Null          => "null",
+                Self::Load          => "loadable segment",
+                Self::Dynamic       => "dynamic linking info",
+                Self::Interp        => "interpreter",
+                Self::Note          => "aux info",
+                Self::Shlib         => "reserved",
+                Self::Phdr          => "header entry",
+                Self::Tls           => "tls",
+                Self::GnuEhFrame    => "eh frame header",
+                Self::GnuStack      => "stack flags",
+                Self::GnuRelro      => "read-only after relocation",
+                Self::Unhandled(_)  => "unhandled",
             }
         }
     }
@@ -104,12 +173,12 @@ mod format {
 /// File IO methods.
 mod io {
     use super::*;
-    use std::fs::File;
+    use std::io::{self, Read, Write};
     use crate::util;
 
     impl Segment {
         /// Extract a segment from a file at current position.
-        pub fn extract(file: &mut File) -> Self {
+        pub fn extract<R: Read>(file: &mut R) -> Self {
             let mut new = Self::empty();
 
             new.etype  = Type::new(util::read_u32(file));
@@ -123,5 +192,17 @@ mod io {
 
             new
         }
+
+        /// Write a segment into a file at current position.
+        pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            util::write_u32(file, self.etype.value())?;
+            util::write_u32(file, self.flags.value)?;
+            util::write_u64(file, self.offset)?;
+            util::write_u64(file, self.vaddr)?;
+            util::write_u64(file, self.paddr)?;
+            util::write_u64(file, self.filesz)?;
+            util::write_u64(file, self.memsz)?;
+            util::write_u64(file, self.align)
+        }
     }
 }
@@ -0,0 +1,332 @@
+//! Regarding split DWARF, built with `-gsplit-dwarf`.
+//!
+//! Most of the debugging information of a compilation unit is left out
+//! of the linked object, in a `.dwo` file named by the skeleton unit
+//! kept in `.debug_info`. The skeleton gives the addresses (`.debug_addr`)
+//! and line table, the split unit everything else, in `.dwo` suffixed
+//! sections.
+//!
+//! The `.dwo` files may be gathered into a `.dwp` package next to the
+//! object, where `.debug_cu_index` tells where the contribution of each
+//! unit is in every section, by the identifier shared by skeleton and
+//! split units. Both the GNU (version 2) and DWARF 5 indexes are read.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::debugfile::read_object;
+use crate::dwarf::{Dwarf, Reader};
+use crate::info::*;
+use crate::object::Object;
+
+pub const DW_SECT_INFO: u32 = 1;
+pub const DW_SECT_ABBREV: u32 = 3;
+pub const DW_SECT_LINE: u32 = 4;
+pub const DW_SECT_LOCLISTS: u32 = 5;
+pub const DW_SECT_STR_OFFSETS: u32 = 6;
+pub const DW_SECT_MACRO: u32 = 7;
+pub const DW_SECT_RNGLISTS: u32 = 8;
+
+/// The index of a package, `.debug_cu_index` or `.debug_tu_index`.
+#[derive(Clone, Debug)]
+pub struct UnitIndex {
+    /// Version, 2 for GNU packages or 5.
+    pub version:    u16,
+    /// Section of every column (`DW_SECT_*`).
+    pub sections:   Vec<u32>,
+    /// Offset and size of the contributions of every unit to each
+    /// column, by unit identifier.
+    pub units:      HashMap<u64, Vec<(u64, u64)>>,
+}
+
+/// Simple unit index methods.
+impl UnitIndex {
+    /// Parses an index section, unless it is empty, of an unknown
+    /// version, or its counts do not fit in it.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 16 {
+            return None;
+        }
+        let mut r = Reader::new(data);
+        let version = match r.u32()? {
+            2 => 2,
+            v if v & 0xffff == 5 => 5,
+            _ => return None,
+        };
+        let section_count = r.u32()? as usize;
+        let unit_count = r.u32()? as usize;
+        let slot_count = r.u32()? as usize;
+
+        /* slots, sections, then offsets and sizes of every unit */
+        let cells = unit_count.checked_mul(section_count)?;
+        let size = slot_count.checked_mul(12)?
+            .checked_add(section_count.checked_mul(4)?)?
+            .checked_add(cells.checked_mul(8)?)?
+            .checked_add(16)?;
+        if size > data.len() {
+            return None;
+        }
+
+        let signatures = (0..slot_count).map(|_| r.u64()).collect::<Option<Vec<u64>>>()?;
+        let rows = (0..slot_count).map(|_| r.u32()).collect::<Option<Vec<u32>>>()?;
+        let sections = (0..section_count).map(|_| r.u32()).collect::<Option<Vec<u32>>>()?;
+        let offsets = (0..cells).map(|_| r.u32()).collect::<Option<Vec<u32>>>()?;
+        let sizes = (0..cells).map(|_| r.u32()).collect::<Option<Vec<u32>>>()?;
+
+        let mut units = HashMap::new();
+        for (&signature, &row) in signatures.iter().zip(&rows) {
+            /* rows are numbered from one, zero being an empty slot */
+            let row = match (row as usize).checked_sub(1) {
+                Some(row) if row < unit_count => row,
+                _ => continue,
+            };
+            let columns = (0..section_count)
+                .map(|c| {
+                    let at = row * section_count + c;
+                    (offsets[at] as u64, sizes[at] as u64)
+                })
+                .collect();
+            units.insert(signature, columns);
+        }
+        Some(Self { version, sections, units })
+    }
+
+    /// Get the contribution of a unit to a section (`DW_SECT_*`), as
+    /// offset and size.
+    ///
+    /// Version 2 numbers its sections alike, but for range lists, which
+    /// it lacks.
+    pub fn contribution(&self, id: u64, section: u32) -> Option<(u64, u64)> {
+        if self.version == 2 && section == DW_SECT_RNGLISTS {
+            return None;
+        }
+        let column = self.sections.iter().position(|&s| s == section)?;
+        self.units.get(&id).map(|columns| columns[column])
+    }
+}
+
+/// Simple unit methods.
+impl Unit {
+    /// Check if the unit is a skeleton, its entries being in a split
+    /// unit.
+    pub fn is_skeleton(&self) -> bool {
+        self.unit_type == DW_UT_SKELETON || (self.dwo_id.is_some() && self.dwo_name.is_some())
+    }
+}
+
+/// Loading methods.
+mod loading {
+    use super::*;
+
+    impl<'a> DebugInfo<'a> {
+        /// Loads the split units of the skeleton units, and get how many
+        /// were found.
+        ///
+        /// The package is the path of the object with a `.dwp` suffix.
+        /// Units missing from it are searched in `.dwo` files, by name,
+        /// relative to the compilation directory or to the object.
+        pub fn load_split_units(&mut self, path: Option<&Path>) -> usize {
+            let package = path.and_then(|p| {
+                let mut name = p.as_os_str().to_os_string();
+                name.push(".dwp");
+                read_object(Path::new(&name))
+            });
+            let index = package.as_ref()
+                .and_then(|p| p.dwarf_section(".debug_cu_index"))
+                .and_then(|data| UnitIndex::parse(&data));
+
+            let mut found = 0;
+            for i in 0..self.units().len() {
+                let skeleton = &self.units()[i];
+                if !skeleton.is_skeleton() || self.splits.iter().any(|s| s.skeleton == i) {
+                    continue;
+                }
+                let id = match skeleton.dwo_id {
+                    Some(id) => id,
+                    None     => continue,
+                };
+                let dwarf = match (&package, &index) {
+                    (Some(package), Some(index)) if index.units.contains_key(&id) => {
+                        split_dwarf(package, Some((index, id)), self.dwarf())
+                    }
+                    _ => match dwo_paths(skeleton, path).iter().find_map(|p| read_object(p)) {
+                        Some(dwo) => split_dwarf(&dwo, None, self.dwarf()),
+                        None      => continue,
+                    },
+                };
+                if let Some(info) = split_info(dwarf, skeleton, id) {
+                    self.splits.push(SplitUnit { skeleton: i, info });
+                    found += 1;
+                }
+            }
+            found
+        }
+    }
+
+    /// Get the candidate paths of the `.dwo` file of a skeleton unit.
+    fn dwo_paths(skeleton: &Unit, path: Option<&Path>) -> Vec<PathBuf> {
+        let name = match &skeleton.dwo_name {
+            Some(name) => Path::new(name),
+            None       => return vec![],
+        };
+        let mut paths = vec![];
+        match &skeleton.comp_dir {
+            Some(dir) => paths.push(Path::new(dir).join(name)),
+            None      => paths.push(name.to_path_buf()),
+        }
+        if let Some(dir) = path.and_then(Path::parent) {
+            paths.push(dir.join(name));
+            if let Some(file) = name.file_name() {
+                paths.push(dir.join(file));
+            }
+        }
+        paths
+    }
+
+    /// Gathers the split DWARF sections of a `.dwo` file, or of the
+    /// contribution of a unit to a `.dwp` package.
+    ///
+    /// Addresses and, before version 5, range lists are the ones of the
+    /// skeleton.
+    fn split_dwarf<'a>(obj: &Object, index: Option<(&UnitIndex, u64)>, skeleton: &Dwarf<'a>) -> Dwarf<'a> {
+        let section = |name: &str, sect: Option<u32>| -> Cow<'a, [u8]> {
+            let data = obj.dwarf_section(name).unwrap_or(Cow::Borrowed(&[]));
+            let range = match (index, sect) {
+                (Some((index, id)), Some(sect)) => index.contribution(id, sect),
+                _ => None,
+            };
+            match range {
+                Some((offset, size)) => {
+                    let end = offset.saturating_add(size);
+                    Cow::Owned(data.get(offset as usize..end as usize).unwrap_or(&[]).to_vec())
+                }
+                None if index.is_some() && sect.is_some() => Cow::Borrowed(&[]),
+                None => Cow::Owned(data.into_owned()),
+            }
+        };
+        Dwarf {
+            info:        section(".debug_info.dwo", Some(DW_SECT_INFO)),
+            abbrev:      section(".debug_abbrev.dwo", Some(DW_SECT_ABBREV)),
+            str:         section(".debug_str.dwo", None),
+            line_str:    Cow::Borrowed(&[]),
+            line:        section(".debug_line.dwo", Some(DW_SECT_LINE)),
+            str_offsets: section(".debug_str_offsets.dwo", Some(DW_SECT_STR_OFFSETS)),
+            addr:        skeleton.addr.clone(),
+            rnglists:    section(".debug_rnglists.dwo", Some(DW_SECT_RNGLISTS)),
+            ranges:      skeleton.ranges.clone(),
+        }
+    }
+
+    /// Get the split unit of a skeleton unit out of split DWARF sections,
+    /// with what it lacks from the skeleton.
+    fn split_info<'a>(dwarf: Dwarf<'a>, skeleton: &Unit, id: u64) -> Option<DebugInfo<'a>> {
+        let mut unit = dwarf.units().into_iter().find(|u| u.dwo_id == Some(id))?;
+        unit.addr_base = skeleton.addr_base;
+        if unit.encoding.version < 5 {
+            unit.rnglists_base = skeleton.rnglists_base;
+        }
+        /* entries may need the bases to be read */
+        if let Some(root) = dwarf.entry(&unit, unit.entries) {
+            dwarf.read_unit_entry(&mut unit, &root);
+        }
+        if unit.low_pc == 0 {
+            unit.low_pc = skeleton.low_pc;
+        }
+        unit.comp_dir = unit.comp_dir.or_else(|| skeleton.comp_dir.clone());
+        unit.name = unit.name.or_else(|| skeleton.name.clone());
+        unit.dwo_name = skeleton.dwo_name.clone();
+
+        let mut info = DebugInfo::new(dwarf);
+        info.units = vec![unit];
+        Some(info)
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Parses the units of `.debug_info`, along with the split units
+        /// of the skeleton ones.
+        ///
+        /// The path of the object is needed to find its `.dwp` package,
+        /// or `.dwo` files given by a relative name.
+        pub fn split_debug_info(&self, path: Option<&Path>) -> DebugInfo<'_> {
+            let mut info = self.debug_info();
+            info.load_split_units(path);
+            info
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    /// Built from `inline.c` with `-gsplit-dwarf`, its `.dwo` file being
+    /// named relative to the crate root.
+    const SAMPLE: &str = "split";
+
+    /// Built from `inline.c` with `-gdwarf-4 -gsplit-dwarf`, its `.dwo`
+    /// file only packaged into `split4.dwp` by `dwp`.
+    const PACKAGED: &str = "split4";
+
+    /// Identifier of the unit of `split4`.
+    const PACKAGED_ID: u64 = 0xa9c71cb25a682fae;
+
+    #[test]
+    fn split_frames() {
+        let obj = Object::from_bytes(&sample(SAMPLE));
+        let mut info = obj.debug_info();
+        assert!(info.inline_frames(0x1048).is_empty());
+        assert_eq!(info.load_split_units(None), 1);
+
+        for frames in [info.inline_frames(0x1048), obj.inline_frames(0x1048)].iter() {
+            let names: Vec<_> = frames.iter().map(|f| f.function.as_deref().unwrap()).collect();
+            assert_eq!(names, ["offset", "main"]);
+            assert_eq!(frames[0].location.as_ref().unwrap().line, 6);
+        }
+    }
+
+    #[test]
+    fn cu_index() {
+        let dwp = Object::from_bytes(&sample(&format!("{}.dwp", PACKAGED)));
+        let data = dwp.dwarf_section(".debug_cu_index").unwrap();
+        let index = UnitIndex::parse(&data).unwrap();
+        assert_eq!(index.version, 2);
+        assert_eq!(index.sections, [DW_SECT_INFO, DW_SECT_ABBREV, DW_SECT_LINE, 5, DW_SECT_STR_OFFSETS]);
+        assert_eq!(index.units.len(), 1);
+        assert_eq!(index.contribution(PACKAGED_ID, DW_SECT_INFO), Some((0, 0xe2)));
+        assert_eq!(index.contribution(PACKAGED_ID, DW_SECT_STR_OFFSETS), Some((0, 0x20)));
+        assert_eq!(index.contribution(PACKAGED_ID, DW_SECT_RNGLISTS), None);
+        assert_eq!(index.contribution(0, DW_SECT_INFO), None);
+
+        /* counts are checked against the size of the section */
+        assert!((0..data.len()).all(|len| UnitIndex::parse(&data[..len]).is_none()));
+        for (at, count) in [(8, 0x1000_0000), (12, 0xffff_ffff), (4, 0x8000_0000)] {
+            let mut bogus = data.to_vec();
+            bogus[at..at + 4].copy_from_slice(&u32::to_le_bytes(count));
+            assert!(UnitIndex::parse(&bogus).is_none(), "{:#x} {:#x}", at, count);
+        }
+        let mut bogus = data.to_vec();
+        bogus[4..12].copy_from_slice(&[0xff; 8]);
+        assert!(UnitIndex::parse(&bogus).is_none());
+    }
+
+    #[test]
+    fn packaged_frames() {
+        let obj = Object::from_bytes(&sample(PACKAGED));
+        assert!(obj.inline_frames(0x1048).is_empty());
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("samples").join(PACKAGED);
+        let info = obj.split_debug_info(Some(&path));
+        assert_eq!(info.splits.len(), 1);
+        let frames = info.inline_frames(0x1048);
+        let names: Vec<_> = frames.iter().map(|f| f.function.as_deref().unwrap()).collect();
+        assert_eq!(names, ["offset", "main"]);
+        assert_eq!(frames[0].location.as_ref().unwrap().line, 6);
+    }
+}
@@ -0,0 +1,240 @@
+//! Regarding `strip` style removal of debugging information and symbols,
+//! and splitting debugging information into a separate file.
+//!
+//! There are three strip modes:
+//! - Debug: debugging sections go, along with their relocations.
+//! - Unneeded: debug, then local symbols no relocation refers to.
+//! - All: debug, then every symbol no relocation refers to. The symbol
+//!   table itself goes when nothing refers to it anymore.
+//!
+//! The separate debug file keeps every section header, so addresses
+//! still match, but only debugging sections, notes and symbol tables
+//! keep their contents. The others become `SHT_NOBITS`. The stripped
+//! file then points at it through a `.gnu_debuglink` section, holding
+//! its name and CRC-32.
+
+use std::io;
+use std::path::Path;
+
+use crate::object::Object;
+use crate::objcopy::Error;
+use crate::section::{Section, Type as SectionType};
+use crate::util;
+
+/// Name of the section pointing at the separate debug file.
+pub const DEBUGLINK: &str = ".gnu_debuglink";
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Removes the debugging sections, like `strip --strip-debug`.
+        ///
+        /// Fails when relocations of other sections refer to them.
+        pub fn strip_debug(&mut self) -> Result<(), Error> {
+            let debug: Vec<usize> = (1..self.sections.len())
+                .filter(|&i| self.sections[i].is_debug())
+                .collect();
+            let removed: Vec<usize> = (1..self.sections.len())
+                .filter(|&i| {
+                    let section = &self.sections[i];
+                    debug.contains(&i) || section.is_rela() && debug.contains(&(section.info as usize))
+                })
+                .collect();
+            if !removed.is_empty() {
+                self.remove_sections(&removed)?;
+            }
+            Ok(())
+        }
+
+        /// Removes the debugging sections and the symbols not needed by
+        /// relocations, like `strip --strip-unneeded`.
+        ///
+        /// Global symbols are kept, since other objects may need them.
+        pub fn strip_unneeded(&mut self) -> Result<(), Error> {
+            self.strip_debug()?;
+            self.retain_symbols(|sym| !sym.is_local());
+            Ok(())
+        }
+
+        /// Removes the debugging sections and every symbol, like
+        /// `strip --strip-all`.
+        ///
+        /// Symbols relocations refer to are kept, so relocatable objects
+        /// still link.
+        pub fn strip_all(&mut self) -> Result<(), Error> {
+            self.strip_debug()?;
+            self.retain_symbols(|_| false);
+
+            /* drop the symbol table once nothing needs it */
+            let symtab = match self.sections.iter().position(|s| s.is_symtab()) {
+                Some(symtab) => symtab,
+                None         => return Ok(()),
+            };
+            let linked = self.sections.iter().any(|s| s.is_rela() && s.link == symtab);
+            if self.symbols.len() > 1 || linked {
+                return Ok(());
+            }
+            let mut removed = vec![symtab];
+            let strtab = self.sections[symtab].link;
+            let shared = self.sections.iter()
+                .enumerate()
+                .any(|(i, s)| i != symtab && s.link == strtab);
+            if strtab != 0 && strtab != self.header.shstrndx && !shared {
+                removed.push(strtab);
+            }
+            self.symbols.clear();
+            self.remove_sections(&removed)
+        }
+
+        /// Get a copy of the object keeping only debugging information,
+        /// like `objcopy --only-keep-debug`.
+        ///
+        /// Every other section becomes `SHT_NOBITS`, keeping its address
+        /// and size.
+        pub fn only_keep_debug(&self) -> Object {
+            let mut debug = Object::empty();
+            debug.header = self.header.clone();
+            debug.segments = self.segments.clone();
+            debug.symbols = self.symbols.clone();
+            debug.sections = self.sections.iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    let keep = i == 0 || i == self.header.shstrndx || s.is_debug()
+                        || s.is_symtab() || s.is_nobits()
+                        || s.etype == SectionType::Note
+                        || self.sections.iter().any(|t| t.is_symtab() && t.link == i)
+                        || s.is_rela() && self.sections.get(s.info as usize)
+                            .is_some_and(|t| t.is_debug());
+                    if keep {
+                        return s.clone();
+                    }
+                    Section {
+                        etype: SectionType::Nobits,
+                        data: vec![],
+                        ..s.clone()
+                    }
+                })
+                .collect();
+            debug.layout();
+            debug
+        }
+
+        /// Moves the debugging information into a separate object, and
+        /// gets it.
+        ///
+        /// Write it into a file, then call `add_debuglink` with its path.
+        pub fn split_debug(&mut self) -> Result<Object, Error> {
+            let debug = self.only_keep_debug();
+            self.strip_debug()?;
+            Ok(debug)
+        }
+
+        /// Adds a `.gnu_debuglink` section pointing at the given separate
+        /// debug file, or replaces the existing one.
+        ///
+        /// Only the file name is recorded, along with the CRC-32 of its
+        /// contents.
+        pub fn add_debuglink<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+            let path = path.as_ref();
+            let contents = std::fs::read(path)?;
+            let name = path.file_name()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?
+                .to_string_lossy();
+
+            let mut data = name.as_bytes().to_vec();
+            data.push(0);
+            data.resize(util::align(data.len() as u64, 4) as usize, 0);
+            data.extend_from_slice(&util::crc32(&contents).to_le_bytes());
+
+            match self.section_index(DEBUGLINK) {
+                Some(ndx) => self.sections[ndx].data = data,
+                None => {
+                    let ndx = self.add_section(DEBUGLINK, 0, data);
+                    self.sections[ndx].addralign = 4;
+                }
+            }
+            self.layout();
+            Ok(())
+        }
+
+        /// Get the file name and CRC-32 recorded in `.gnu_debuglink`, if
+        /// any.
+        pub fn debuglink(&self) -> Option<(String, u32)> {
+            let data = &self.sections[self.section_index(DEBUGLINK)?].data;
+            let name = util::get_str(data, 0);
+            let off = util::align(name.len() as u64 + 1, 4) as usize;
+            if off + 4 > data.len() {
+                return None;
+            }
+            Some((name, util::get_u32(data, off)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample, write};
+
+    #[test]
+    fn strip_debug() {
+        let orig = Object::from_bytes(&sample("inline"));
+        assert!(orig.sections().iter().any(|s| s.is_debug()));
+        let mut obj = Object::from_bytes(&sample("inline"));
+        obj.strip_debug().unwrap();
+        let obj = Object::from_bytes(&write(&obj));
+        assert!(obj.sections().iter().all(|s| !s.is_debug()));
+        assert_eq!(obj.symbols().len(), orig.symbols().len());
+
+        /* everything loaded is left alone */
+        let alloc = |obj: &Object| -> Vec<(u64, u64, Vec<u8>)> {
+            obj.sections().iter()
+                .filter(|s| s.is_alloc())
+                .map(|s| (s.addr, s.offset, s.data.clone()))
+                .collect()
+        };
+        assert_eq!(alloc(&obj), alloc(&orig));
+    }
+
+    #[test]
+    fn only_keep_debug() {
+        let orig = Object::from_bytes(&sample("inline"));
+        let debug = Object::from_bytes(&write(&orig.only_keep_debug()));
+        assert_eq!(debug.sections().len(), orig.sections().len());
+        for (kept, old) in debug.sections().iter().zip(orig.sections()) {
+            assert_eq!((&kept.name, kept.addr, kept.size), (&old.name, old.addr, old.size));
+            if old.is_debug() || old.is_symtab() {
+                assert!(kept.data == old.data);
+            } else if old.is_alloc() && !old.is_note() {
+                assert!(kept.is_nobits());
+            }
+        }
+
+        /* relocations pointing nowhere are not kept */
+        let mut obj = Object::from_bytes(&sample("main.o"));
+        let rela = obj.section_index(".rela.text").unwrap();
+        obj.sections_mut()[rela].info = 1000;
+        assert!(obj.only_keep_debug().sections()[rela].is_nobits());
+    }
+
+    #[test]
+    fn debuglink() {
+        assert_eq!(util::crc32(b"123456789"), 0xcbf4_3926);
+
+        let mut obj = Object::from_bytes(&sample("inline"));
+        let debug = write(&obj.split_debug().unwrap());
+        let path = std::env::temp_dir().join(format!("elf-strip-{}.debug", std::process::id()));
+        std::fs::write(&path, &debug).unwrap();
+        obj.add_debuglink(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let obj = Object::from_bytes(&write(&obj));
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(obj.debuglink(), Some((name.clone(), util::crc32(&debug))));
+        let data = &obj.sections()[obj.section_index(DEBUGLINK).unwrap()].data;
+        assert_eq!(data.len() % 4, 0);
+        assert_eq!(&data[..name.len() + 1], format!("{}\0", name).as_bytes());
+    }
+}
@@ -5,8 +5,9 @@
 
 /// Posible symbol types.
 /// Obtained from the lower 4 bits of the info byte.
-#[derive(PartialEq)]
-enum Type {
+#[derive(PartialEq, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Type {
     NoType,
     Object,
     Func,
@@ -15,39 +16,51 @@ enum Type {
     Common,
     TLS,
     Num,
-    Unhandled,
+    Unhandled(u8),
 }
 
 /// Posible symbol bindings.
 /// Obtained from the higher 4 bits of the info byte.
-#[derive(PartialEq)]
-enum Bind {
+#[derive(PartialEq, Clone, Copy)]
+pub enum Bind {
     Local,
     Global,
     Weak,
-    Unhandled,
+    Unhandled(u8),
 }
 
+/// Section index for undefined symbols.
+pub const SHN_UNDEF: usize = 0;
+/// Section index for absolute symbols.
+pub const SHN_ABS: usize = 0xfff1;
+/// Section index for common symbols.
+pub const SHN_COMMON: usize = 0xfff2;
+
 /// Represents an individual entry in a symbol table.
+#[derive(Clone)]
 pub struct Sym {
     /// Index into the symbol string table.
     pub nameoff:    usize,      // 32-bits
-    etype:          Type,       // \_ 8-bits
-    bind:           Bind,       // /
-    other:          u8,         // 8-bits
+    pub etype:      Type,       // \_ 8-bits
+    pub bind:       Bind,       // /
+    pub other:      u8,         // 8-bits
     pub shndx:      usize,      // 16-bits
     pub value:      u64,        // 64-bits
-    size:           u64,        // 64-bits
+    pub size:       u64,        // 64-bits
 
     /// Extracted name string.
     pub name:       Option<String>,
+    /// Version name, for dynamic symbols with version info.
+    pub version:    Option<String>,
+    /// Wether the version is hidden (not the default version).
+    pub hidden:     bool,
 }
 
 /// Simple type methods.
 impl Type {
     /// Default type.
     pub fn empty() -> Self {
-        Self::Unhandled
+        Self::NoType
     }
 
     /// Get type from value of `info`.
@@ -61,7 +74,25 @@ impl Type {
             2 => Self::Func,
             3 => Self::Section,
             4 => Self::File,
-            _ => Self::Unhandled,
+            5 => Self::Common,
+            6 => Self::TLS,
+            7 => Self::Num,
+            t => Self::Unhandled(t),
+        }
+    }
+
+    /// Get real value of the type.
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::NoType       => 0,
+            Self::Object       => 1,
+            Self::Func         => 2,
+            Self::Section      => 3,
+            Self::File         => 4,
+            Self::Common       => 5,
+            Self::TLS          => 6,
+            Self::Num          => 7,
+            Self::Unhandled(t) => *t,
         }
     }
 
@@ -76,7 +107,7 @@ impl Type {
             Self::Common    => "common",
             Self::TLS       => "tls",
             Self::Num       => "num",
-            Self::Unhandled => "unhandled",
+            Self::Unhandled(_) => "unhandled",
         }
     }
 
@@ -86,7 +117,7 @@ impl Type {
 impl Bind {
     /// Default bind.
     pub fn empty() -> Self {
-        Self::Unhandled
+        Self::Local
     }
 
     /// Get bind from value of info.
@@ -98,7 +129,17 @@ impl Bind {
             0 => Self::Local,
             1 => Self::Global,
             2 => Self::Weak,
-            _ => Self::Unhandled,
+            b => Self::Unhandled(b),
+        }
+    }
+
+    /// Get real value of the bind.
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::Local        => 0,
+            Self::Global       => 1,
+            Self::Weak         => 2,
+            Self::Unhandled(b) => *b,
         }
     }
 
@@ -108,7 +149,7 @@ impl Bind {
             Self::Local     => "local",
             Self::Global    => "global",
             Self::Weak      => "weak",
-            Self::Unhandled => "unhandled",
+            Self::Unhandled(_) => "unhandled",
         }
     }
 }
@@ -127,6 +168,8 @@ impl Sym {
             size:       0,
 
             name:       None,
+            version:    None,
+            hidden:     false,
         }
     }
 
@@ -144,6 +187,32 @@ impl Sym {
     pub fn is_section(&self) -> bool {
         self.etype == Type::Section
     }
+
+    /// Wether the symbol is undefined (refers to another object).
+    pub fn is_undefined(&self) -> bool {
+        self.shndx == SHN_UNDEF
+    }
+
+    /// Wether the symbol is local to its object.
+    pub fn is_local(&self) -> bool {
+        self.bind == Bind::Local
+    }
+
+    /// Get the symbol name decorated with its version.
+    ///
+    /// Follows the usual notation: `name@@VERSION` for the default
+    /// version of a defined symbol and `name@VERSION` for references
+    /// and hidden versions.
+    pub fn versioned_name(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("");
+        match &self.version {
+            Some(v) if self.hidden || self.is_undefined() => {
+                format!("{}@{}", name, v)
+            }
+            Some(v) => format!("{}@@{}", name, v),
+            None    => name.to_string(),
+        }
+    }
 }
 
 /// Format methods.
@@ -171,12 +240,12 @@ mod format {
 /// File IO methods.
 pub mod io {
     use super::*;
-    use std::fs::File;
+    use std::io::{self, Read, Write};
     use super::super::util;
 
     impl Sym {
         /// Extract a symbol from a file **at current offset**.
-        pub fn extract(file: &mut File) -> Self {
+        pub fn extract<R: Read>(file: &mut R) -> Self {
             let mut new = Self::empty();
 
             new.nameoff = util::read_u32(file) as usize;
@@ -190,6 +259,54 @@ pub mod io {
 
             new
         }
+
+        /// Write a symbol into a file **at current offset**.
+        pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+            let info = self.bind.value() << 4 | (self.etype.value() & 0x0f);
+            util::write_u32(file, self.nameoff as u32)?;
+            util::write_u8(file, info)?;
+            util::write_u8(file, self.other)?;
+            util::write_u16(file, self.shndx as u16)?;
+            util::write_u64(file, self.value)?;
+            util::write_u64(file, self.size)
+        }
+
+        /// Encode a whole symbol table, along with its string table.
+        ///
+        /// Updates `nameoff` of every symbol to match the new string
+        /// table. Section symbols get an empty name, since their name
+        /// comes from the section they represent.
+        pub fn encode_table(syms: &mut [Sym]) -> (Vec<u8>, Vec<u8>) {
+            let mut strtab = util::StrTab::new();
+            let symtab = Self::encode_into(syms, &mut strtab);
+            (symtab, strtab.data)
+        }
+
+        /// Like `encode_table`, adding the names to an existing string
+        /// table.
+        pub fn encode_into(syms: &mut [Sym], strtab: &mut util::StrTab) -> Vec<u8> {
+            let mut symtab = vec![];
+            for sym in syms.iter_mut() {
+                sym.nameoff = match &sym.name {
+                    Some(name) if !sym.is_section() => strtab.add(name),
+                    _ => 0,
+                };
+                sym.write(&mut symtab).unwrap();
+            }
+            symtab
+        }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_round_trip() {
+        for t in 0..16 {
+            assert_eq!(Type::new(t).value(), t);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Regarding symbolization of addresses, finding the function an
+//! address belongs to.
+//!
+//! Function symbols come from every table available, in order:
+//! - The symbol table, unless stripped.
+//! - The dynamic symbol table.
+//! - The symbol table of the MiniDebugInfo, if any.
+//!
+//! The same function found in several tables is kept once, and the
+//! result is sorted by address, so each lookup is a binary search. A
+//! `Symbolizer` keeps them around for many lookups.
+
+use crate::object::Object;
+use crate::sym::{Sym, Type as SymType};
+
+/// A function an address belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    /// Function name.
+    pub name:   String,
+    /// Start address.
+    pub addr:   u64,
+    /// Size in bytes, zero if unknown.
+    pub size:   u64,
+    /// Offset of the address into the function.
+    pub offset: u64,
+}
+
+/// Finds the functions addresses belong to.
+pub struct Symbolizer {
+    /// Defined function symbols, sorted by address.
+    functions: Vec<Sym>,
+}
+
+/// Simple symbolizer methods.
+impl Symbolizer {
+    /// Creates a symbolizer for the functions of an object.
+    pub fn new(obj: &Object) -> Self {
+        Self {
+            functions: obj.function_symbols(),
+        }
+    }
+
+    /// Get the function symbols, sorted by address.
+    pub fn functions(&self) -> &[Sym] {
+        &self.functions
+    }
+
+    /// Get the function the given address belongs to, if any.
+    ///
+    /// Addresses past the end of a function with a known size belong to
+    /// none.
+    pub fn lookup(&self, addr: u64) -> Option<Function> {
+        let at = self.functions.partition_point(|s| s.value <= addr);
+        let sym = &self.functions[at.checked_sub(1)?];
+        if sym.size != 0 && addr >= sym.value + sym.size {
+            return None;
+        }
+        Some(Function {
+            name:   sym.name.clone().unwrap_or_default(),
+            addr:   sym.value,
+            size:   sym.size,
+            offset: addr - sym.value,
+        })
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Get the defined function symbols of every table, sorted by
+        /// address.
+        ///
+        /// A MiniDebugInfo that cannot be decompressed is ignored.
+        pub fn function_symbols(&self) -> Vec<Sym> {
+            let mini = self.mini_debuginfo().ok().flatten();
+            let mini_symbols = mini.as_ref().map_or(&[][..], |m| &m.symbols[..]);
+
+            let mut functions: Vec<Sym> = self.symbols.iter()
+                .chain(self.dynsyms.iter())
+                .chain(mini_symbols.iter())
+                .filter(|s| s.etype == SymType::Func && !s.is_undefined())
+                .filter(|s| s.name.as_deref().is_some_and(|n| !n.is_empty()))
+                .cloned()
+                .collect();
+            functions.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+            functions.dedup_by(|a, b| a.value == b.value && a.name == b.name);
+            functions
+        }
+
+        /// Get the function the given address belongs to, if any.
+        ///
+        /// Every call collects the symbols again, decompressing the
+        /// MiniDebugInfo, so use a `Symbolizer` for more than one lookup.
+        pub fn symbolize(&self, addr: u64) -> Option<Function> {
+            Symbolizer::new(self).lookup(addr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    #[test]
+    fn lookup() {
+        let obj = Object::from_bytes(&sample("main"));
+        let symbolizer = Symbolizer::new(&obj);
+        assert!(symbolizer.functions().windows(2).all(|w| w[0].value <= w[1].value));
+
+        let func = symbolizer.lookup(0x112b + 3).unwrap();
+        assert_eq!((func.name.as_str(), func.addr, func.offset), ("global_f", 0x112b, 3));
+        assert_eq!(obj.symbolize(0x112b + 3), Some(func.clone()));
+        let past = symbolizer.lookup(func.addr + func.size);
+        assert!(past.is_none_or(|f| f.name != "global_f"));
+        assert!(symbolizer.lookup(0).is_none());
+    }
+}
@@ -0,0 +1,22 @@
+//! Minimal bindings to the Linux memory mapping calls, for the loaders.
+//!
+//! The constants are those of x86-64 Linux, the only target the loaders
+//! are built for.
+
+extern "C" {
+    pub fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, off: i64) -> *mut u8;
+    pub fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+    pub fn munmap(addr: *mut u8, len: usize) -> i32;
+    pub fn getauxval(atype: u64) -> u64;
+}
+
+pub const PROT_NONE: i32 = 0x0;
+pub const PROT_READ: i32 = 0x1;
+pub const PROT_WRITE: i32 = 0x2;
+pub const PROT_EXEC: i32 = 0x4;
+pub const MAP_PRIVATE: i32 = 0x02;
+pub const MAP_ANONYMOUS: i32 = 0x20;
+pub const MAP_FIXED_NOREPLACE: i32 = 0x100000;
+
+/// Value returned by `mmap` on failure.
+pub const MAP_FAILED: *mut u8 = !0usize as *mut u8;
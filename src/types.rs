@@ -0,0 +1,706 @@
+//! Regarding types described by DWARF, and the layout of structures,
+//! the way `pahole` shows them.
+//!
+//! Types are entries of `.debug_info`, referring to each other by
+//! offset: a pointer to its pointee, a typedef to its definition, an
+//! array to its element type. A `TypeTable` keeps every type of every
+//! unit by offset, so they can be named and sized.
+//!
+//! The layout of a structure lists its members with their offset and
+//! size, along with the holes between them, the padding at the end and
+//! where cachelines start. Holes inside the storage of bitfields are not
+//! reported.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::dwarf::Value;
+use crate::info::*;
+use crate::object::Object;
+
+/// Default cacheline size in bytes.
+pub const CACHELINE: u64 = 64;
+
+/// Distance between the offsets of the types of split units.
+pub const SPLIT_OFFSET: u64 = 1 << 40;
+
+/// Posible kinds of types.
+/// Obtained from the tag of their entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Base,
+    Struct,
+    Union,
+    Class,
+    Enum,
+    Typedef,
+    Array,
+    Pointer,
+    Reference,
+    Const,
+    Volatile,
+    Restrict,
+    Atomic,
+    Subroutine,
+    Unhandled(u64),
+}
+
+/// A type.
+#[derive(Clone, Debug)]
+pub struct Type {
+    /// Offset of the entry in `.debug_info`.
+    pub offset:     u64,
+    /// Kind of type.
+    pub kind:       Kind,
+    /// Name, if not anonymous.
+    pub name:       Option<String>,
+    /// Size in bytes, if given by the entry.
+    pub size:       Option<u64>,
+    /// Offset of the type it refers to, none meaning `void`.
+    pub target:     Option<u64>,
+    /// Members, of structures, unions and classes.
+    pub members:    Vec<Member>,
+    /// Element count of every dimension, of arrays.
+    pub dims:       Vec<Option<u64>>,
+    /// Wether it is only declared.
+    pub declaration: bool,
+}
+
+/// A member of a structure, union or class.
+#[derive(Clone, Debug)]
+pub struct Member {
+    /// Name, none for anonymous members and base classes.
+    pub name:       Option<String>,
+    /// Offset of its type.
+    pub ty:         Option<u64>,
+    /// Offset in bits from the start of the structure.
+    pub bit_offset: u64,
+    /// Size in bits, of bitfields.
+    pub bit_size:   Option<u64>,
+    /// Wether it is a base class.
+    pub inherited:  bool,
+}
+
+/// The types of every unit, by offset.
+///
+/// Types of split units are kept too, their offsets moved past
+/// `SPLIT_OFFSET` times the number of the split unit, so they are
+/// unique.
+pub struct TypeTable {
+    /// The types.
+    types:  HashMap<u64, Type>,
+}
+
+/// The layout of a member.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemberLayout {
+    /// Name, none for anonymous members and base classes.
+    pub name:       Option<String>,
+    /// Name of its type.
+    pub type_name:  String,
+    /// Declaration of the member, as in C.
+    pub declaration: String,
+    /// Offset in bytes.
+    pub offset:     u64,
+    /// Size in bytes, of its type for bitfields.
+    pub size:       u64,
+    /// Offset of the bitfield in its storage, and its size in bits.
+    pub bitfield:   Option<(u64, u64)>,
+    /// Hole in bytes before the next member, or the end.
+    pub hole:       u64,
+    /// Wether a cacheline starts inside the member.
+    pub straddles:  bool,
+}
+
+/// The layout of a structure, union or class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    /// Kind of type.
+    pub kind:       Kind,
+    /// Name, if not anonymous.
+    pub name:       Option<String>,
+    /// Size in bytes.
+    pub size:       u64,
+    /// Cacheline size in bytes.
+    pub cacheline:  u64,
+    /// Members, by offset.
+    pub members:    Vec<MemberLayout>,
+    /// Padding in bytes at the end.
+    pub padding:    u64,
+}
+
+/// Simple kind methods.
+impl Kind {
+    /// Default kind.
+    pub fn empty() -> Self {
+        Self::Base
+    }
+
+    /// Get kind from the tag of its entry.
+    pub fn new(tag: u64) -> Self {
+        match tag {
+            DW_TAG_BASE_TYPE                => Self::Base,
+            DW_TAG_STRUCTURE_TYPE           => Self::Struct,
+            DW_TAG_UNION_TYPE               => Self::Union,
+            DW_TAG_CLASS_TYPE               => Self::Class,
+            DW_TAG_ENUMERATION_TYPE         => Self::Enum,
+            DW_TAG_TYPEDEF                  => Self::Typedef,
+            DW_TAG_ARRAY_TYPE               => Self::Array,
+            DW_TAG_POINTER_TYPE             => Self::Pointer,
+            DW_TAG_REFERENCE_TYPE
+                | DW_TAG_RVALUE_REFERENCE_TYPE => Self::Reference,
+            DW_TAG_CONST_TYPE               => Self::Const,
+            DW_TAG_VOLATILE_TYPE            => Self::Volatile,
+            DW_TAG_RESTRICT_TYPE            => Self::Restrict,
+            DW_TAG_ATOMIC_TYPE              => Self::Atomic,
+            DW_TAG_SUBROUTINE_TYPE          => Self::Subroutine,
+            t => Self::Unhandled(t),
+        }
+    }
+
+    /// Get the tag of the entry.
+    pub fn value(&self) -> u64 {
+        match self {
+            Self::Base          => DW_TAG_BASE_TYPE,
+            Self::Struct        => DW_TAG_STRUCTURE_TYPE,
+            Self::Union         => DW_TAG_UNION_TYPE,
+            Self::Class         => DW_TAG_CLASS_TYPE,
+            Self::Enum          => DW_TAG_ENUMERATION_TYPE,
+            Self::Typedef       => DW_TAG_TYPEDEF,
+            Self::Array         => DW_TAG_ARRAY_TYPE,
+            Self::Pointer       => DW_TAG_POINTER_TYPE,
+            Self::Reference     => DW_TAG_REFERENCE_TYPE,
+            Self::Const         => DW_TAG_CONST_TYPE,
+            Self::Volatile      => DW_TAG_VOLATILE_TYPE,
+            Self::Restrict      => DW_TAG_RESTRICT_TYPE,
+            Self::Atomic        => DW_TAG_ATOMIC_TYPE,
+            Self::Subroutine    => DW_TAG_SUBROUTINE_TYPE,
+            Self::Unhandled(t)  => *t,
+        }
+    }
+
+    /// String slice representation of the kind, as in C.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Base          => "base",
+            Self::Struct        => "struct",
+            Self::Union         => "union",
+            Self::Class         => "class",
+            Self::Enum          => "enum",
+            Self::Typedef       => "typedef",
+            Self::Array         => "array",
+            Self::Pointer       => "pointer",
+            Self::Reference     => "reference",
+            Self::Const         => "const",
+            Self::Volatile      => "volatile",
+            Self::Restrict      => "restrict",
+            Self::Atomic        => "_Atomic",
+            Self::Subroutine    => "subroutine",
+            Self::Unhandled(_)  => "unhandled",
+        }
+    }
+
+    /// Check if types of this kind have members.
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self, Self::Struct | Self::Union | Self::Class)
+    }
+}
+
+/// Simple type table methods.
+impl TypeTable {
+    /// Decodes the types of every unit.
+    pub fn new(info: &DebugInfo) -> Self {
+        let mut types = HashMap::new();
+        let parts = std::iter::once(info).chain(info.split_units().iter().map(|s| &s.info));
+        for (part, info) in parts.enumerate() {
+            let base = part as u64 * SPLIT_OFFSET;
+            for unit in info.units() {
+                let dies = info.dwarf().entries(unit);
+                for (i, die) in dies.iter().enumerate() {
+                    if let Kind::Unhandled(_) = Kind::new(die.tag) {
+                        continue;
+                    }
+                    let children = dies[i + 1..].iter()
+                        .take_while(|d| d.depth > die.depth)
+                        .filter(|d| d.depth == die.depth + 1);
+                    let mut ty = decoding::decode(info, unit, die, children);
+                    ty.offset += base;
+                    ty.target = ty.target.map(|t| t + base);
+                    for member in &mut ty.members {
+                        member.ty = member.ty.map(|t| t + base);
+                    }
+                    types.insert(ty.offset, ty);
+                }
+            }
+        }
+        Self { types }
+    }
+
+    /// Get a type by the offset of its entry.
+    pub fn get(&self, offset: u64) -> Option<&Type> {
+        self.types.get(&offset)
+    }
+
+    /// Get every type, in no particular order.
+    pub fn types(&self) -> impl Iterator<Item = &Type> {
+        self.types.values()
+    }
+
+    /// Get the named structures, unions and classes which are defined,
+    /// by name.
+    ///
+    /// Types defined by several units are kept once.
+    pub fn aggregates(&self) -> Vec<&Type> {
+        let mut aggregates: Vec<&Type> = self.types.values()
+            .filter(|t| t.kind.is_aggregate() && !t.declaration && t.name.is_some())
+            .collect();
+        aggregates.sort_by(|a, b| (&a.name, a.offset).cmp(&(&b.name, b.offset)));
+        aggregates.dedup_by(|a, b| a.kind == b.kind && a.name == b.name && a.size == b.size);
+        aggregates
+    }
+
+    /// Find a defined structure, union or class by name.
+    pub fn find(&self, name: &str) -> Option<&Type> {
+        self.aggregates().into_iter().find(|t| t.name.as_deref() == Some(name))
+    }
+
+    /// Get the size in bytes of a type, through typedefs and
+    /// qualifiers.
+    pub fn size_of(&self, offset: Option<u64>) -> Option<u64> {
+        let ty = self.get(offset?)?;
+        if let Some(size) = ty.size {
+            return Some(size);
+        }
+        match ty.kind {
+            Kind::Typedef | Kind::Const | Kind::Volatile | Kind::Restrict | Kind::Atomic => {
+                self.size_of(ty.target)
+            }
+            Kind::Array => {
+                let count = ty.dims.iter().try_fold(1, |n, d| Some(n * (*d)?))?;
+                Some(self.size_of(ty.target)? * count)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the name of a type, as written in C.
+    ///
+    /// Anonymous structures and the like are `struct {...}`.
+    pub fn name_of(&self, offset: Option<u64>) -> String {
+        let ty = match offset {
+            None         => return "void".to_string(),
+            Some(offset) => match self.get(offset) {
+                Some(ty) => ty,
+                None     => return "?".to_string(),
+            },
+        };
+        let name = ty.name.as_deref();
+        match ty.kind {
+            Kind::Base | Kind::Typedef => name.unwrap_or("?").to_string(),
+            Kind::Struct | Kind::Union | Kind::Class | Kind::Enum => {
+                format!("{} {}", ty.kind.as_str(), name.unwrap_or("{...}"))
+            }
+            Kind::Pointer       => format!("{} *", self.name_of(ty.target)),
+            Kind::Reference     => format!("{} &", self.name_of(ty.target)),
+            Kind::Const | Kind::Volatile | Kind::Atomic => {
+                format!("{} {}", ty.kind.as_str(), self.name_of(ty.target))
+            }
+            Kind::Restrict      => format!("{} restrict", self.name_of(ty.target)),
+            Kind::Array         => {
+                let dims: String = ty.dims.iter()
+                    .map(|d| d.map_or("[]".to_string(), |n| format!("[{}]", n)))
+                    .collect();
+                format!("{}{}", self.name_of(ty.target), dims)
+            }
+            Kind::Subroutine    => format!("{} ()", self.name_of(ty.target)),
+            Kind::Unhandled(_)  => "?".to_string(),
+        }
+    }
+
+    /// Get the declaration of something of a type, as in C.
+    ///
+    /// Arrays put their dimensions after the name, and bitfields their
+    /// size.
+    pub fn declaration(&self, offset: Option<u64>, name: Option<&str>, bit_size: Option<u64>) -> String {
+        let array = offset.and_then(|o| self.get(o)).filter(|t| t.kind == Kind::Array);
+        let mut decl = match array {
+            Some(ty) => {
+                let dims: String = ty.dims.iter()
+                    .map(|d| d.map_or("[]".to_string(), |n| format!("[{}]", n)))
+                    .collect();
+                format!("{} {}{}", self.name_of(ty.target), name.unwrap_or(""), dims)
+            }
+            None => format!("{} {}", self.name_of(offset), name.unwrap_or("")),
+        };
+        if let Some(bits) = bit_size {
+            decl = format!("{}:{}", decl, bits);
+        }
+        decl.trim_end().to_string()
+    }
+
+    /// Get the layout of a structure, union or class, with cachelines
+    /// of the given size, the default one if zero.
+    pub fn layout(&self, ty: &Type, cacheline: u64) -> Layout {
+        let cacheline = if cacheline == 0 { CACHELINE } else { cacheline };
+        let size = ty.size.unwrap_or(0);
+        let mut members: Vec<MemberLayout> = ty.members.iter()
+            .map(|m| {
+                let type_size = self.size_of(m.ty).unwrap_or(0);
+                let offset = match m.bit_size {
+                    /* bitfields are shown within storage of their type */
+                    Some(_) if type_size != 0 => m.bit_offset / 8 / type_size * type_size,
+                    _ => m.bit_offset / 8,
+                };
+                MemberLayout {
+                    name:       m.name.clone(),
+                    type_name:  self.name_of(m.ty),
+                    declaration: self.declaration(m.ty, m.name.as_deref(), m.bit_size),
+                    offset,
+                    size:       type_size,
+                    bitfield:   m.bit_size.map(|bits| (m.bit_offset - offset * 8, bits)),
+                    hole:       0,
+                    straddles:  offset / cacheline != (offset.saturating_add(type_size.max(1)) - 1) / cacheline,
+                }
+            })
+            .collect();
+        members.sort_by_key(|m| m.offset);
+
+        /* holes come after the end of the previous members */
+        let padding = if ty.kind == Kind::Union {
+            size.saturating_sub(members.iter().map(|m| m.size).max().unwrap_or(0))
+        } else {
+            let mut end = 0;
+            let mut last: Option<usize> = None;
+            for i in 0..members.len() {
+                if let Some(last) = last.filter(|_| members[i].offset > end) {
+                    members[last].hole = members[i].offset - end;
+                }
+                if members[i].end() >= end {
+                    end = members[i].end();
+                    last = Some(i);
+                }
+            }
+            size.saturating_sub(end)
+        };
+        Layout {
+            kind:       ty.kind,
+            name:       ty.name.clone(),
+            size,
+            cacheline,
+            members,
+            padding,
+        }
+    }
+
+    /// Get the layout of every named structure, union and class, by
+    /// name.
+    pub fn layouts(&self, cacheline: u64) -> Vec<Layout> {
+        self.aggregates().into_iter().map(|t| self.layout(t, cacheline)).collect()
+    }
+}
+
+/// Simple member layout methods.
+impl MemberLayout {
+    /// Get the offset of the end of the member, past the storage of its
+    /// type for bitfields, unless they overflow it.
+    pub fn end(&self) -> u64 {
+        match self.bitfield {
+            Some((bit, bits)) => self.offset + self.size.max((bit + bits).div_ceil(8)),
+            None              => self.offset + self.size,
+        }
+    }
+}
+
+/// Simple layout methods.
+impl Layout {
+    /// Get the holes between members, as offset and size in bytes.
+    pub fn holes(&self) -> Vec<(u64, u64)> {
+        self.members.iter()
+            .filter(|m| m.hole != 0)
+            .map(|m| (m.end(), m.hole))
+            .collect()
+    }
+
+    /// Get the number of cachelines used.
+    pub fn cachelines(&self) -> u64 {
+        self.size.div_ceil(self.line_size())
+    }
+
+    /// Get the cacheline size, the default one if zero.
+    fn line_size(&self) -> u64 {
+        if self.cacheline == 0 { CACHELINE } else { self.cacheline }
+    }
+
+    /// Get the sum of the sizes of the members, in bytes.
+    ///
+    /// Members sharing storage, bitfields or union members, are only
+    /// counted once.
+    pub fn sum_members(&self) -> u64 {
+        let mut sum = 0;
+        let mut end = 0;
+        for m in &self.members {
+            if m.end() > end {
+                sum += m.end() - m.offset.max(end);
+                end = m.end();
+            }
+        }
+        sum
+    }
+
+    /// Check if the members would fit in fewer cachelines, were the
+    /// holes and padding removed.
+    pub fn is_packable(&self) -> bool {
+        self.sum_members().div_ceil(self.line_size()) < self.cachelines()
+    }
+}
+
+/// Decoding methods.
+mod decoding {
+    use super::*;
+
+    /// Decodes a type entry, given its children.
+    pub(crate) fn decode<'d, I>(info: &DebugInfo, unit: &Unit, die: &Die, children: I) -> Type
+    where
+        I: Iterator<Item = &'d Die>,
+    {
+        let dwarf = info.dwarf();
+        let kind = Kind::new(die.tag);
+        let name = die.attr(DW_AT_NAME).and_then(|v| dwarf.unit_string(unit, v));
+        let mut size = die.attr(DW_AT_BYTE_SIZE).and_then(Value::udata);
+        if size.is_none() && matches!(kind, Kind::Pointer | Kind::Reference) {
+            size = Some(unit.encoding.address_size as u64);
+        }
+
+        let mut members = vec![];
+        let mut dims = vec![];
+        for child in children {
+            match child.tag {
+                DW_TAG_MEMBER | DW_TAG_INHERITANCE if kind.is_aggregate() => {
+                    /* static members have no location outside unions */
+                    let location = die_location(child);
+                    if child.attr(DW_AT_DECLARATION).is_some()
+                        || (location.is_none() && kind != Kind::Union && child.attr(DW_AT_DATA_BIT_OFFSET).is_none()) {
+                        continue;
+                    }
+                    members.push(member(info, unit, child, location.unwrap_or(0)));
+                }
+                DW_TAG_SUBRANGE_TYPE if kind == Kind::Array => {
+                    let count = child.attr(DW_AT_COUNT).and_then(Value::udata)
+                        .or_else(|| {
+                            /* an upper bound of -1 means a flexible array */
+                            let upper = child.attr(DW_AT_UPPER_BOUND)?;
+                            match upper.sdata()? {
+                                -1    => Some(0),
+                                upper => Some(upper as u64 + 1),
+                            }
+                        });
+                    dims.push(count);
+                }
+                _ => (),
+            }
+        }
+
+        Type {
+            offset:     die.offset,
+            kind,
+            name,
+            size,
+            target:     die.attr(DW_AT_TYPE).and_then(|v| reference(unit, v)),
+            members,
+            dims,
+            declaration: die.attr(DW_AT_DECLARATION).is_some(),
+        }
+    }
+
+    /// Decodes a member, located at the given byte offset.
+    fn member(info: &DebugInfo, unit: &Unit, die: &Die, location: u64) -> Member {
+        let ty = die.attr(DW_AT_TYPE).and_then(|v| reference(unit, v));
+        let bit_size = die.attr(DW_AT_BIT_SIZE).and_then(Value::udata);
+        let bit_offset = if let Some(bit) = die.attr(DW_AT_DATA_BIT_OFFSET).and_then(Value::udata) {
+            bit
+        } else if let (Some(bits), Some(bit)) = (bit_size, die.attr(DW_AT_BIT_OFFSET).and_then(Value::sdata)) {
+            /* before version 4, bits are numbered from the most
+             * significant one of the storage */
+            let storage = die.attr(DW_AT_BYTE_SIZE).and_then(Value::udata)
+                .or_else(|| type_size(info, unit, die))
+                .unwrap_or(0);
+            (location * 8 + storage * 8).wrapping_sub(bit as u64 + bits)
+        } else {
+            location * 8
+        };
+        Member {
+            name:       die.attr(DW_AT_NAME).and_then(|v| info.dwarf().unit_string(unit, v)),
+            ty,
+            bit_offset,
+            bit_size,
+            inherited:  die.tag == DW_TAG_INHERITANCE,
+        }
+    }
+
+    /// Get the byte size of the type of an entry, through typedefs and
+    /// qualifiers, while the table is not built yet.
+    fn type_size(info: &DebugInfo, unit: &Unit, die: &Die) -> Option<u64> {
+        let (mut unit, mut die) = info.reference(unit, die.attr(DW_AT_TYPE)?)?;
+        for _ in 0..8 {
+            if let Some(size) = die.attr(DW_AT_BYTE_SIZE).and_then(Value::udata) {
+                return Some(size);
+            }
+            let next = info.reference(unit, die.attr(DW_AT_TYPE)?)?;
+            unit = next.0;
+            die = next.1;
+        }
+        None
+    }
+
+    /// Get the byte offset of a member, a constant or a simple
+    /// expression.
+    fn die_location(die: &Die) -> Option<u64> {
+        match die.attr(DW_AT_DATA_MEMBER_LOCATION)? {
+            /* DW_OP_plus_uconst */
+            Value::Block(expr) | Value::Exprloc(expr) if expr.first() == Some(&0x23) => {
+                crate::dwarf::Reader::at(expr, 1).uleb()
+            }
+            value => value.udata(),
+        }
+    }
+
+    /// Get the offset in `.debug_info` of a referenced entry.
+    fn reference(unit: &Unit, value: &Value) -> Option<u64> {
+        match *value {
+            Value::Ref(off)     => Some(unit.offset + off),
+            Value::RefAddr(off) => Some(off),
+            _ => None,
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+
+    impl Object {
+        /// Decodes the types of every unit.
+        ///
+        /// Split units are only loaded from the `.dwo` files their
+        /// skeletons name; use `split_debug_info` with the path of the
+        /// object for packages.
+        pub fn type_table(&self) -> TypeTable {
+            TypeTable::new(&self.split_debug_info(None))
+        }
+
+        /// Get the layout of a structure, union or class by name, with
+        /// cachelines of the default size.
+        pub fn struct_layout(&self, name: &str) -> Option<Layout> {
+            let table = self.type_table();
+            let ty = table.find(name)?;
+            Some(table.layout(ty, CACHELINE))
+        }
+    }
+}
+
+/// Format methods.
+mod format {
+    use super::*;
+
+    impl fmt::Display for Layout {
+        /// Show the layout like `pahole` does.
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "{} {} {{", self.kind.as_str(), self.name.as_deref().unwrap_or("{...}"))?;
+            let mut next = 1;
+            for m in &self.members {
+                let line = m.offset / self.line_size();
+                if line >= next {
+                    let boundary = line * self.line_size();
+                    if m.offset == boundary {
+                        writeln!(f, "\t/* --- cacheline {} boundary ({} bytes) --- */", line, boundary)?;
+                    } else {
+                        writeln!(f, "\t/* --- cacheline {} boundary ({} bytes) was {} bytes ago --- */",
+                            line, boundary, m.offset - boundary)?;
+                    }
+                    next = line + 1;
+                }
+                let decl = format!("{};", m.declaration);
+                match m.bitfield {
+                    Some((bit, _)) => writeln!(f, "\t{:<40} /* {:5}:{:2} {:4} */", decl, m.offset, bit, m.size)?,
+                    None           => writeln!(f, "\t{:<40} /* {:5} {:7} */", decl, m.offset, m.size)?,
+                }
+                if m.hole != 0 {
+                    writeln!(f)?;
+                    writeln!(f, "\t/* XXX {} byte{} hole, try to pack */", m.hole, plural(m.hole))?;
+                    writeln!(f)?;
+                }
+            }
+            writeln!(f)?;
+            let holes = self.holes();
+            writeln!(f, "\t/* size: {}, cachelines: {}, members: {} */",
+                self.size, self.cachelines(), self.members.len())?;
+            if !holes.is_empty() {
+                writeln!(f, "\t/* sum members: {}, holes: {}, sum holes: {} */",
+                    self.sum_members(), holes.len(), holes.iter().map(|h| h.1).sum::<u64>())?;
+            }
+            if self.padding != 0 {
+                writeln!(f, "\t/* padding: {} */", self.padding)?;
+            }
+            let last = self.size % self.line_size();
+            if last != 0 {
+                writeln!(f, "\t/* last cacheline: {} byte{} */", last, plural(last))?;
+            }
+            if self.is_packable() {
+                writeln!(f, "\t/* could fit in {} cachelines once packed */",
+                    self.sum_members().div_ceil(self.line_size()))?;
+            }
+            write!(f, "}};")
+        }
+    }
+
+    /// Get the plural suffix for a count.
+    fn plural(n: u64) -> &'static str {
+        if n == 1 { "" } else { "s" }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample;
+
+    /// Built from `layout.c` with `-g -c`.
+    const SAMPLE: &str = "layout.o";
+
+    #[test]
+    fn struct_layout() {
+        let layout = Object::from_bytes(&sample(SAMPLE)).struct_layout("layout").unwrap();
+        let members: Vec<_> = layout.members.iter()
+            .map(|m| (m.name.as_deref().unwrap(), m.offset, m.size, m.bitfield))
+            .collect();
+        assert_eq!(members, [
+            ("tag", 0, 1, None),
+            ("count", 4, 4, None),
+            ("flags", 8, 4, Some((0, 3))),
+            ("mode", 8, 4, Some((3, 5))),
+            ("value", 16, 8, None),
+            ("id", 24, 2, None),
+        ]);
+
+        /* as pahole shows it, but for the bit hole after mode */
+        assert_eq!((layout.size, layout.cachelines(), layout.padding), (32, 1, 6));
+        assert_eq!(layout.holes(), [(1, 3), (12, 4)]);
+        assert_eq!(layout.sum_members(), 19);
+        assert!(!layout.is_packable());
+        let shown = layout.to_string();
+        assert!(shown.contains("\tunsigned int mode:5;                     /*     8: 3    4 */\n"));
+        assert!(shown.contains("/* size: 32, cachelines: 1, members: 6 */"));
+        assert!(shown.contains("/* sum members: 19, holes: 2, sum holes: 7 */"));
+    }
+
+    #[test]
+    fn zero_cacheline() {
+        let obj = Object::from_bytes(&sample(SAMPLE));
+        let table = obj.type_table();
+        let mut layout = table.layout(table.find("layout").unwrap(), 0);
+        assert_eq!(layout.cacheline, CACHELINE);
+        layout.cacheline = 0;
+        assert_eq!(layout.cachelines(), 1);
+        assert!(layout.to_string().contains("/* last cacheline: 32 bytes */"));
+    }
+}
@@ -3,31 +3,144 @@
 //! **TODO:
 //! - Error checking.**
 
-use std::fs::File;
-use std::io::Read;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
 
 /// Read one byte.
-pub fn read_u8(file: &mut File) -> u8 {
+pub fn read_u8<R: Read>(file: &mut R) -> u8 {
     let mut buf = [0u8; 1];
-    file.read(&mut buf).unwrap();
+    file.read_exact(&mut buf).unwrap();
     u8::from_ne_bytes(buf)
 }
 /// Read two bytes.
-pub fn read_u16(file: &mut File) -> u16 {
+pub fn read_u16<R: Read>(file: &mut R) -> u16 {
     let mut buf = [0u8; 2];
-    file.read(&mut buf).unwrap();
+    file.read_exact(&mut buf).unwrap();
     u16::from_ne_bytes(buf)
 }
 /// Read four bytes.
-pub fn read_u32(file: &mut File) -> u32 {
+pub fn read_u32<R: Read>(file: &mut R) -> u32 {
     let mut buf = [0u8; 4];
-    file.read(&mut buf).unwrap();
+    file.read_exact(&mut buf).unwrap();
     u32::from_ne_bytes(buf)
 }
 /// Read eight bytes.
-pub fn read_u64(file: &mut File) -> u64 {
+pub fn read_u64<R: Read>(file: &mut R) -> u64 {
     let mut buf = [0u8; 8];
-    file.read(&mut buf).unwrap();
+    file.read_exact(&mut buf).unwrap();
     u64::from_ne_bytes(buf)
 }
+/// Read `len` bytes.
+pub fn read_bytes<R: Read>(file: &mut R, len: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; len];
+    file.read_exact(&mut buf).unwrap();
+    buf
+}
+
+/// Get two bytes from a buffer at the given offset.
+pub fn get_u16(buf: &[u8], off: usize) -> u16 {
+    let mut b = [0u8; 2];
+    b.copy_from_slice(&buf[off..off + 2]);
+    u16::from_ne_bytes(b)
+}
+/// Get four bytes from a buffer at the given offset.
+pub fn get_u32(buf: &[u8], off: usize) -> u32 {
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&buf[off..off + 4]);
+    u32::from_ne_bytes(b)
+}
+/// Get eight bytes from a buffer at the given offset.
+pub fn get_u64(buf: &[u8], off: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[off..off + 8]);
+    u64::from_ne_bytes(b)
+}
+/// Get a null-terminated string from a buffer at the given offset.
+pub fn get_str(buf: &[u8], off: usize) -> String {
+    let s = buf.get(off..).unwrap_or(&[]);
+    let end = s.iter().position(|&c| c == b'\0').unwrap_or(s.len());
+    String::from_utf8_lossy(&s[..end]).into_owned()
+}
+
+/// Write one byte.
+pub fn write_u8<W: Write>(file: &mut W, v: u8) -> io::Result<()> {
+    file.write_all(&v.to_ne_bytes())
+}
+/// Write two bytes.
+pub fn write_u16<W: Write>(file: &mut W, v: u16) -> io::Result<()> {
+    file.write_all(&v.to_ne_bytes())
+}
+/// Write four bytes.
+pub fn write_u32<W: Write>(file: &mut W, v: u32) -> io::Result<()> {
+    file.write_all(&v.to_ne_bytes())
+}
+/// Write eight bytes.
+pub fn write_u64<W: Write>(file: &mut W, v: u64) -> io::Result<()> {
+    file.write_all(&v.to_ne_bytes())
+}
+
+/// A string table being built.
+pub struct StrTab {
+    /// Table contents, starting with an empty string.
+    pub data: Vec<u8>,
+    /// Offset of each string already in the table.
+    offsets: HashMap<String, usize>,
+}
+
+impl StrTab {
+    /// Creates a table holding just the empty string.
+    pub fn new() -> Self {
+        Self {
+            data:    vec![0u8],
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Add a string into the table, reusing an existing entry when
+    /// possible, and get its offset.
+    pub fn add(&mut self, s: &str) -> usize {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(&off) = self.offsets.get(s) {
+            return off;
+        }
+        let off = self.data.len();
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(b'\0');
+        self.offsets.insert(s.to_string(), off);
+        off
+    }
+}
+
+/// Round `v` up to a multiple of `align`.
+pub fn align(v: u64, align: u64) -> u64 {
+    if align <= 1 {
+        return v;
+    }
+    v.div_ceil(align) * align
+}
 
+/// CRC-32 lookup table, for the reflected polynomial `0xedb88320`.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CRC-32 of a buffer, as used by `.gnu_debuglink`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &b| {
+        CRC32_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8)
+    })
+}
@@ -0,0 +1,285 @@
+//! Regarding symbol versioning.
+//!
+//! Dynamic objects may attach versions to their symbols through three
+//! sections:
+//! - `.gnu.version` (versym): one 16-bit index per dynamic symbol.
+//! - `.gnu.version_d` (verdef): the versions this object defines.
+//! - `.gnu.version_r` (verneed): the versions this object requires from
+//!   other objects.
+
+use std::cmp::Ordering;
+
+/// Versym index for local symbols.
+pub const VER_NDX_LOCAL: u16 = 0;
+/// Versym index for unversioned global symbols.
+pub const VER_NDX_GLOBAL: u16 = 1;
+/// Versym bit marking a hidden version.
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+
+/// A version definition (from `.gnu.version_d`).
+pub struct Verdef {
+    /// Definition flags (`VER_FLG_BASE`, `VER_FLG_WEAK`).
+    pub flags:  u16,
+    /// Versym index referring to this definition.
+    pub ndx:    u16,
+    /// ELF hash of the version name.
+    pub hash:   u32,
+    /// Version name, followed by the names of its parents.
+    pub names:  Vec<String>,
+}
+
+/// A version requirement on another object (from `.gnu.version_r`).
+pub struct Verneed {
+    /// Name of the object the versions are required from.
+    pub file:   String,
+    /// Versions required from `file`.
+    pub aux:    Vec<Vernaux>,
+}
+
+/// A single required version.
+pub struct Vernaux {
+    /// ELF hash of the version name.
+    pub hash:   u32,
+    /// Requirement flags (`VER_FLG_WEAK`).
+    pub flags:  u16,
+    /// Versym index referring to this requirement.
+    pub other:  u16,
+    /// Version name.
+    pub name:   String,
+}
+
+/// Simple verdef methods.
+impl Verdef {
+    /// Get the version name, unless the definition has none.
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(String::as_str)
+    }
+}
+
+/// Compare two version names, e.g. `GLIBC_2.14` and `GLIBC_2.2.5`.
+///
+/// The names are compared by the dot separated numbers following the
+/// last `_`, falling back to plain string comparison when there are
+/// none.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (numbers(a), numbers(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+/// Get the family of a version name, e.g. `GLIBC` for `GLIBC_2.14`.
+///
+/// Names without a numeric part, like `GLIBC_PRIVATE`, are their own
+/// family.
+pub fn prefix(name: &str) -> &str {
+    match (name.rfind('_'), numbers(name)) {
+        (Some(n), Some(_)) => &name[..n],
+        _                  => name,
+    }
+}
+
+/// Get the numeric part of a version name, e.g. `[2, 14]` for
+/// `GLIBC_2.14`.
+fn numbers(name: &str) -> Option<Vec<u32>> {
+    let n = name.rfind('_')?;
+    name[n + 1..].split('.')
+        .map(|p| p.parse().ok())
+        .collect()
+}
+
+/// Buffer IO methods.
+pub mod io {
+    use super::*;
+    use crate::util;
+
+    impl Verdef {
+        /// Extract all version definitions from the contents of a
+        /// verdef section, with names taken from `strtab`.
+        pub fn extract_all(data: &[u8], strtab: &[u8]) -> Vec<Self> {
+            let mut defs = vec![];
+            let mut off = 0usize;
+            while off + 20 <= data.len() {
+                let flags = util::get_u16(data, off + 2);
+                let ndx   = util::get_u16(data, off + 4);
+                let cnt   = util::get_u16(data, off + 6);
+                let hash  = util::get_u32(data, off + 8);
+                let aux   = util::get_u32(data, off + 12) as usize;
+                let next  = util::get_u32(data, off + 16) as usize;
+
+                /* walk the auxiliary name entries */
+                let mut names = vec![];
+                let mut a = off + aux;
+                for _ in 0..cnt {
+                    if a + 8 > data.len() {
+                        break;
+                    }
+                    let name = util::get_u32(data, a) as usize;
+                    names.push(util::get_str(strtab, name));
+                    a += util::get_u32(data, a + 4) as usize;
+                }
+
+                defs.push(Self { flags, ndx, hash, names });
+                if next == 0 {
+                    break;
+                }
+                off += next;
+            }
+            defs
+        }
+    }
+
+    impl Verneed {
+        /// Extract all version requirements from the contents of a
+        /// verneed section, with names taken from `strtab`.
+        pub fn extract_all(data: &[u8], strtab: &[u8]) -> Vec<Self> {
+            let mut needs = vec![];
+            let mut off = 0usize;
+            while off + 16 <= data.len() {
+                let cnt  = util::get_u16(data, off + 2);
+                let file = util::get_u32(data, off + 4) as usize;
+                let aux  = util::get_u32(data, off + 8) as usize;
+                let next = util::get_u32(data, off + 12) as usize;
+
+                /* walk the required versions */
+                let mut auxs = vec![];
+                let mut a = off + aux;
+                for _ in 0..cnt {
+                    if a + 16 > data.len() {
+                        break;
+                    }
+                    let name = util::get_u32(data, a + 8) as usize;
+                    auxs.push(Vernaux {
+                        hash:  util::get_u32(data, a),
+                        flags: util::get_u16(data, a + 4),
+                        other: util::get_u16(data, a + 6),
+                        name:  util::get_str(strtab, name),
+                    });
+                    a += util::get_u32(data, a + 12) as usize;
+                }
+
+                needs.push(Self {
+                    file: util::get_str(strtab, file),
+                    aux:  auxs,
+                });
+                if next == 0 {
+                    break;
+                }
+                off += next;
+            }
+            needs
+        }
+    }
+}
+
+/// Object methods.
+mod object {
+    use super::*;
+    use crate::object::Object;
+
+    impl Object {
+        /// Get the version definitions of the object.
+        pub fn verdefs(&self) -> &[Verdef] {
+            &self.verdefs
+        }
+
+        /// Get the version requirements of the object.
+        pub fn verneeds(&self) -> &[Verneed] {
+            &self.verneeds
+        }
+
+        /// Get the name of the version with the given versym index.
+        ///
+        /// Returns the version name and, for required versions, the
+        /// name of the object it is required from.
+        pub fn version_name(&self, ndx: u16) -> Option<(&str, Option<&str>)> {
+            let ndx = ndx & !VERSYM_HIDDEN;
+            if ndx == VER_NDX_LOCAL || ndx == VER_NDX_GLOBAL {
+                return None;
+            }
+            for def in &self.verdefs {
+                if def.ndx == ndx {
+                    return Some((def.name()?, None));
+                }
+            }
+            for need in &self.verneeds {
+                for aux in &need.aux {
+                    if aux.other == ndx {
+                        return Some((&aux.name, Some(&need.file)));
+                    }
+                }
+            }
+            None
+        }
+
+        /// Get the minimum version required from each library.
+        ///
+        /// For every required object and version family (e.g. `GLIBC`
+        /// from `libc.so.6`), returns the newest version referenced,
+        /// which is the oldest version of that library the object can
+        /// run against.
+        pub fn required_versions(&self) -> Vec<(String, String)> {
+            let mut req: Vec<(String, String)> = vec![];
+            for need in &self.verneeds {
+                for aux in &need.aux {
+                    let found = req.iter_mut().find(|(file, v)| {
+                        *file == need.file && prefix(v) == prefix(&aux.name)
+                    });
+                    match found {
+                        Some((_, v)) => {
+                            if compare(&aux.name, v) == Ordering::Greater {
+                                *v = aux.name.clone();
+                            }
+                        }
+                        None => req.push((need.file.clone(), aux.name.clone())),
+                    }
+                }
+            }
+            req.sort();
+            req
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Object;
+    use crate::tests::sample;
+
+    #[test]
+    fn sample_versions() {
+        let obj = Object::from_bytes(&sample("main"));
+        assert!(obj.verdefs().is_empty());
+        let needs = obj.verneeds();
+        assert_eq!(needs.len(), 1);
+        assert_eq!(needs[0].file, "libc.so.6");
+        let aux = &needs[0].aux;
+        assert_eq!(aux.len(), 1);
+        assert_eq!((aux[0].name.as_str(), aux[0].other), ("GLIBC_2.2.5", 2));
+
+        let names: Vec<_> = obj.dynsyms().iter().map(|s| s.versioned_name()).collect();
+        assert_eq!(names[2], "__libc_start_main@GLIBC_2.2.5");
+        assert_eq!(names[3], "__gmon_start__");
+        assert_eq!(obj.version_name(2), Some(("GLIBC_2.2.5", Some("libc.so.6"))));
+        assert_eq!(obj.required_versions(), [("libc.so.6".to_string(), "GLIBC_2.2.5".to_string())]);
+    }
+
+    #[test]
+    fn nameless_verdef() {
+        /* one definition without auxiliary entries */
+        let mut data = vec![0u8; 20];
+        data[0..2].copy_from_slice(&1u16.to_le_bytes());
+        data[4..6].copy_from_slice(&2u16.to_le_bytes());
+        let defs = Verdef::extract_all(&data, b"\0");
+        assert_eq!(defs.len(), 1);
+        assert_eq!((defs[0].ndx, defs[0].name()), (2, None));
+    }
+
+    #[test]
+    fn compare_names() {
+        assert_eq!(compare("GLIBC_2.14", "GLIBC_2.2.5"), Ordering::Greater);
+        assert_eq!(prefix("GLIBC_2.14"), "GLIBC");
+        assert_eq!(prefix("GLIBC_PRIVATE"), "GLIBC_PRIVATE");
+    }
+}